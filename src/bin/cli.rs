@@ -1 +1,336 @@
-fn main() {}
\ No newline at end of file
+use structopt::StructOpt;
+use w::{DEFAULT_PORT, Result};
+use w::client::{Client, Subscriber};
+use w::connection::Connection;
+use w::frame::Frame;
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+
+// 交互模式下的历史记录文件
+const HISTORY_FILE: &str = ".w_cli_history";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // 开启日志记录
+    let _ = tracing_subscriber::fmt::try_init();
+    let cli = Cli::from_args(); // 解析命令行参数
+    let port = cli.port.as_deref().unwrap_or(DEFAULT_PORT);
+
+    let addr = format!("{}:{}", cli.host, port);
+
+    match cli.command {
+        Some(Command::Get { key }) => {
+            let mut client = Client::connect(&addr).await?;
+            match client.get(&key).await? {
+                Some(value) => print_frame(&Frame::Bulk(value)),
+                None => print_frame(&Frame::Null),
+            }
+        }
+        Some(Command::Set { key, value, ex, px }) => {
+            let mut client = Client::connect(&addr).await?;
+            let expire = ex.map(Duration::from_secs)
+                .or_else(|| px.map(Duration::from_millis));
+            match expire {
+                Some(expire) => client.set_expires(&key, value, expire).await?,
+                None => client.set(&key, value).await?,
+            }
+            println!("OK");
+        }
+        Some(Command::Publish { channel, message }) => {
+            let mut client = Client::connect(&addr).await?;
+            let num = client.publish(&channel, message).await?;
+            print_frame(&Frame::Integer(num as i64));
+        }
+        Some(Command::Subscribe { channels }) => {
+            let client = Client::connect(&addr).await?;
+            print_messages(client.subscribe(channels).await?).await?;
+        }
+        Some(Command::Psubscribe { patterns }) => {
+            let client = Client::connect(&addr).await?;
+            print_messages(client.psubscribe(patterns).await?).await?;
+        }
+        // 没带子命令就进入交互模式
+        None => {
+            let socket = TcpStream::connect(&addr).await?;
+            let mut connection = Connection::new(socket);
+            repl(&mut connection, &addr).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "w-redis-cli", version = env ! ("CARGO_PKG_VERSION"), author = env ! ("CARGO_PKG_AUTHORS"), about = "Issue Redis commands")]
+struct Cli {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    #[structopt(name = "hostname", long = "--host", default_value = "127.0.0.1")]
+    host: String,
+
+    #[structopt(name = "port", long = "--port")]
+    port: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Get the value of key.
+    Get {
+        /// Name of key to get
+        key: String,
+    },
+    /// Set key to hold the string value.
+    Set {
+        /// Name of key to set
+        key: String,
+
+        /// Value to set.
+        #[structopt(parse(from_str = bytes_from_str))]
+        value: Bytes,
+
+        /// Expire the value after specified amount of seconds
+        #[structopt(long = "--ex", conflicts_with = "px")]
+        ex: Option<u64>,
+
+        /// Expire the value after specified amount of milliseconds
+        #[structopt(long = "--px")]
+        px: Option<u64>,
+    },
+    /// Post a message to the given channel.
+    Publish {
+        /// Name of channel
+        channel: String,
+
+        #[structopt(parse(from_str = bytes_from_str))]
+        /// Message to publish
+        message: Bytes,
+    },
+    /// Subscribe the client to one or more channels and print the messages.
+    Subscribe {
+        /// Specific channel or channels
+        #[structopt(required = true)]
+        channels: Vec<String>,
+    },
+    /// Subscribe the client to channels matching the given glob patterns and print the messages.
+    Psubscribe {
+        /// Specific pattern or patterns
+        #[structopt(required = true)]
+        patterns: Vec<String>,
+    },
+}
+
+// 一直打印收到的消息，直到连接断开
+async fn print_messages(mut subscriber: Subscriber) -> Result<()> {
+    println!("Reading messages... (press Ctrl-C to quit)");
+
+    while let Some(msg) = subscriber.next_message().await? {
+        let mut frame = vec![];
+        match msg.pattern {
+            Some(pattern) => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"pmessage")));
+                frame.push(Frame::Bulk(Bytes::from(pattern)));
+            }
+            None => frame.push(Frame::Bulk(Bytes::from_static(b"message"))),
+        }
+        frame.push(Frame::Bulk(Bytes::from(msg.channel)));
+        frame.push(Frame::Bulk(msg.content));
+        print_frame(&Frame::Array(frame));
+    }
+    Ok(())
+}
+
+// 发送一条命令并等待服务端的响应
+async fn request(connection: &mut Connection, frame: Frame) -> Result<Frame> {
+    connection.write_frame(&frame).await?;
+    match connection.read_frame().await? {
+        Some(response) => Ok(response),
+        None => Err("connection reset by server".into()),
+    }
+}
+
+// 交互模式，和redis-cli一样直接把输入的参数原样发给服务端
+async fn repl(connection: &mut Connection, addr: &str) -> Result<()> {
+    let history = std::env::var("HOME")
+        .map(|home| format!("{}/{}", home, HISTORY_FILE))
+        .unwrap_or_else(|_| HISTORY_FILE.to_string());
+
+    let mut editor = Editor::<()>::new();
+    let _ = editor.load_history(&history);
+
+    let prompt = format!("{}> ", addr);
+    loop {
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            // ctrl-c 和 ctrl-d 都直接退出
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line);
+
+        if line.eq_ignore_ascii_case("quit") || line.eq_ignore_ascii_case("exit") {
+            break;
+        }
+
+        let args = match split_args(line) {
+            Some(args) => args,
+            None => {
+                println!("Invalid argument(s)");
+                continue;
+            }
+        };
+
+        let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+        let num_args = args.len() - 1;
+        let frame = Frame::Array(args.into_iter().map(Frame::Bulk).collect());
+        let response = request(connection, frame).await?;
+        print_frame(&response);
+
+        match &name[..] {
+            // 进入订阅模式之后一直打印收到的消息，直到连接断开
+            "subscribe" | "psubscribe" => {
+                println!("Reading messages... (press Ctrl-C to quit)");
+                while let Some(response) = connection.read_frame().await? {
+                    print_frame(&response);
+                }
+                break;
+            }
+            // 每个频道都会有一条确认
+            "unsubscribe" | "punsubscribe" => {
+                for _ in 1..num_args {
+                    if let Some(response) = connection.read_frame().await? {
+                        print_frame(&response);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let _ = editor.save_history(&history);
+    Ok(())
+}
+
+// 按空白切分参数，支持用双引号或单引号包起来的参数
+fn split_args(line: &str) -> Option<Vec<Bytes>> {
+    let mut args = vec![];
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let quote = match chars.peek() {
+            None => return Some(args),
+            Some(&c) if c == '"' || c == '\'' => {
+                chars.next();
+                Some(c)
+            }
+            Some(_) => None,
+        };
+
+        let mut arg = String::new();
+        loop {
+            match (chars.next(), quote) {
+                // 引号没闭合
+                (None, Some(_)) => return None,
+                (None, None) => break,
+                (Some(c), Some(q)) if c == q => {
+                    // 闭合的引号后面必须是空白或者结束
+                    if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                        return None;
+                    }
+                    break;
+                }
+                (Some('\\'), Some('"')) => match chars.next() {
+                    Some('n') => arg.push('\n'),
+                    Some('r') => arg.push('\r'),
+                    Some('t') => arg.push('\t'),
+                    Some(c) => arg.push(c),
+                    None => return None,
+                },
+                (Some(c), None) if c.is_whitespace() => break,
+                (Some(c), _) => arg.push(c),
+            }
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
+// 按照redis-cli的格式打印响应
+fn print_frame(frame: &Frame) {
+    for line in format_frame(frame) {
+        println!("{}", line);
+    }
+}
+
+fn format_frame(frame: &Frame) -> Vec<String> {
+    match frame {
+        Frame::Simple(s) => vec![s.clone()],
+        Frame::Error(e) => vec![format!("(error) {}", e)],
+        Frame::Integer(n) => vec![format!("(integer) {}", n)],
+        Frame::Bulk(b) => vec![format!("{:?}", String::from_utf8_lossy(b))],
+        Frame::Null => vec!["(nil)".to_string()],
+        Frame::Boolean(b) => vec![format!("({})", b)],
+        Frame::Double(d) => vec![format!("(double) {}", d)],
+        Frame::BigNumber(n) => vec![format!("(big number) {}", n)],
+        Frame::Verbatim(_, b) => vec![format!("{:?}", String::from_utf8_lossy(b))],
+        Frame::Array(items) | Frame::Push(items) if items.is_empty() => vec!["(empty array)".to_string()],
+        Frame::Array(items) | Frame::Push(items) => format_items(items.iter().map(format_frame), ")"),
+        Frame::Set(items) if items.is_empty() => vec!["(empty set)".to_string()],
+        Frame::Set(items) => format_items(items.iter().map(format_frame), "~"),
+        Frame::Map(pairs) if pairs.is_empty() => vec!["(empty hash)".to_string()],
+        Frame::Map(pairs) => format_items(pairs.iter().map(format_pair), "#"),
+        Frame::Attribute(attributes, data) => {
+            let mut lines = format_items(attributes.iter().map(format_pair), "|");
+            lines.extend(format_frame(data));
+            lines
+        }
+    }
+}
+
+// 聚合类型每个元素前面加上序号，多行的元素后面几行要对齐
+fn format_items(items: impl ExactSizeIterator<Item = Vec<String>>, marker: &str) -> Vec<String> {
+    let width = items.len().to_string().len();
+    let mut lines = vec![];
+    for (i, item) in items.enumerate() {
+        let prefix = format!("{:>width$}{} ", i + 1, marker, width = width);
+        let padding = " ".repeat(prefix.len());
+        for (j, line) in item.into_iter().enumerate() {
+            if j == 0 {
+                lines.push(format!("{}{}", prefix, line));
+            } else {
+                lines.push(format!("{}{}", padding, line));
+            }
+        }
+    }
+    lines
+}
+
+fn format_pair((key, value): &(Frame, Frame)) -> Vec<String> {
+    let key = format_frame(key).join(" ");
+    let mut lines = format_frame(value);
+    let prefix = format!("{} => ", key);
+    let padding = " ".repeat(prefix.chars().count());
+    for (j, line) in lines.iter_mut().enumerate() {
+        if j == 0 {
+            line.insert_str(0, &prefix);
+        } else {
+            line.insert_str(0, &padding);
+        }
+    }
+    lines
+}
+
+fn bytes_from_str(src: &str) -> Bytes {
+    Bytes::from(src.to_string())
+}
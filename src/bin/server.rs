@@ -1,22 +1,38 @@
 use structopt::StructOpt;
 use w::{DEFAULT_PORT, Result, server};
+use std::time::Duration;
 use tokio::net::{TcpListener};
 use tokio::signal;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // 开启日志记录
-    tracing_subscriber::fmt::try_init();
+    let _ = tracing_subscriber::fmt::try_init();
     let cli = Cli::from_args(); // 解析命令行参数
     let port = cli.port.as_deref().unwrap_or(DEFAULT_PORT);
 
     let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
-    server::run(listener, signal::ctrl_c()).await
+    let mut config = server::Config::default();
+    if let Some(databases) = cli.databases {
+        config.databases = databases;
+    }
+    if let Some(limit) = cli.script_time_limit {
+        config.script_time_limit = Duration::from_millis(limit);
+    }
+    server::run_with_config(listener, signal::ctrl_c(), config).await
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "w-redis-server", version = env ! ("CARGO_PKG_VERSION"), author = env ! ("CARGO_PKG_AUTHORS"), about = "A Redis server")]
 struct Cli {
     #[structopt(name = "port", long = "--port")]
-    port: Option<String>
+    port: Option<String>,
+
+    // 库的数量，默认16个
+    #[structopt(name = "databases", long = "--databases")]
+    databases: Option<usize>,
+
+    // 脚本执行多少毫秒之后其他连接开始收到BUSY，默认5000
+    #[structopt(name = "script-time-limit", long = "--script-time-limit")]
+    script_time_limit: Option<u64>,
 }
@@ -0,0 +1,265 @@
+use tokio::net::{TcpStream, ToSocketAddrs};
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::debug;
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::cmd::{Get, Set, Publish, Subscribe, Unsubscribe, PSubscribe, PUnsubscribe};
+
+// 连到redis-server的客户端，一个Client对应一条tcp连接
+#[derive(Debug)]
+pub struct Client {
+    connection: Connection,
+}
+
+// 订阅模式下的客户端，只能订阅、取消订阅和接收消息
+#[derive(Debug)]
+pub struct Subscriber {
+    client: Client,
+    subscribed_channels: Vec<String>,
+    subscribed_patterns: Vec<String>,
+}
+
+// 订阅的频道里收到的消息，通过模式订阅收到的会带上匹配的模式
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub pattern: Option<String>,
+    pub channel: String,
+    pub content: Bytes,
+}
+
+impl Client {
+    // 建立连接
+    pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<Client> {
+        let socket = TcpStream::connect(addr).await?;
+        let connection = Connection::new(socket);
+
+        Ok(Client { connection })
+    }
+
+    // 获取key的值，key不存在返回None
+    pub async fn get(&mut self, key: impl AsRef<[u8]>) -> crate::Result<Option<Bytes>> {
+        let frame = Get::new(Bytes::copy_from_slice(key.as_ref())).into_frame();
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    // 设置key的值，不带过期时间
+    pub async fn set(&mut self, key: impl AsRef<[u8]>, value: Bytes) -> crate::Result<()> {
+        self.set_cmd(Set::new(Bytes::copy_from_slice(key.as_ref()), value, None)).await
+    }
+
+    // 设置key的值，过了expiration之后自动删除
+    pub async fn set_expires(&mut self, key: impl AsRef<[u8]>, value: Bytes, expiration: Duration) -> crate::Result<()> {
+        self.set_cmd(Set::new(Bytes::copy_from_slice(key.as_ref()), value, Some(expiration))).await
+    }
+
+    async fn set_cmd(&mut self, cmd: Set) -> crate::Result<()> {
+        let frame = cmd.into_frame();
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    // 往频道里发消息，返回收到消息的订阅者数量
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        let frame = Publish::new(channel, message).into_frame();
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) if response >= 0 => Ok(response as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    // 订阅频道，之后这条连接只能用来收消息了，所以直接消耗掉Client
+    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
+        self.subscribe_cmd(Subscribe::new(&channels).into_frame(), "subscribe", &channels).await?;
+
+        let mut subscriber = Subscriber::new(self);
+        add_subscribed(&mut subscriber.subscribed_channels, &channels);
+        Ok(subscriber)
+    }
+
+    // 按glob模式订阅，同样会消耗掉Client
+    pub async fn psubscribe(mut self, patterns: Vec<String>) -> crate::Result<Subscriber> {
+        self.subscribe_cmd(PSubscribe::new(&patterns).into_frame(), "psubscribe", &patterns).await?;
+
+        let mut subscriber = Subscriber::new(self);
+        add_subscribed(&mut subscriber.subscribed_patterns, &patterns);
+        Ok(subscriber)
+    }
+
+    async fn subscribe_cmd(&mut self, frame: Frame, kind: &str, names: &[String]) -> crate::Result<()> {
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        // 每个频道(模式)服务端都会回一条确认
+        for name in names {
+            let response = self.read_response().await?;
+            match response {
+                Frame::Array(ref frame) | Frame::Push(ref frame) => match frame.as_slice() {
+                    [Frame::Bulk(reply), Frame::Bulk(subscribed), ..]
+                    if *reply == kind && *subscribed == name.as_bytes() => {}
+                    _ => return Err(response.to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // 读取服务端的响应，错误响应直接转成Err返回
+    async fn read_response(&mut self) -> crate::Result<Frame> {
+        let response = self.connection.read_frame().await?;
+        debug!(?response);
+
+        match response {
+            Some(Frame::Error(msg)) => Err(msg.into()),
+            Some(frame) => Ok(frame),
+            // 读到一半服务端把连接关掉了
+            None => Err("connection reset by server".into()),
+        }
+    }
+}
+
+impl Subscriber {
+    fn new(client: Client) -> Self {
+        Self {
+            client,
+            subscribed_channels: vec![],
+            subscribed_patterns: vec![],
+        }
+    }
+
+    // 当前订阅的频道
+    pub fn get_subscribed(&self) -> &[String] {
+        &self.subscribed_channels
+    }
+
+    // 当前订阅的模式
+    pub fn get_subscribed_patterns(&self) -> &[String] {
+        &self.subscribed_patterns
+    }
+
+    // 等待下一条消息，连接断开了返回None
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        match self.client.connection.read_frame().await? {
+            Some(frame) => {
+                debug!(?frame);
+
+                let (pattern, channel, content) = match frame {
+                    Frame::Array(ref array) | Frame::Push(ref array) => match array.as_slice() {
+                        [Frame::Bulk(kind), Frame::Bulk(channel), Frame::Bulk(content)]
+                        if *kind == "message" => (None, channel, content),
+                        [Frame::Bulk(kind), Frame::Bulk(pattern), Frame::Bulk(channel), Frame::Bulk(content)]
+                        if *kind == "pmessage" => (Some(pattern), channel, content),
+                        _ => return Err(frame.to_error()),
+                    },
+                    frame => return Err(frame.to_error()),
+                };
+
+                let to_string = |bytes: &Bytes| {
+                    std::str::from_utf8(bytes)
+                        .map(|s| s.to_string())
+                        .map_err(|_| frame.to_error())
+                };
+
+                Ok(Some(Message {
+                    pattern: pattern.map(to_string).transpose()?,
+                    channel: to_string(channel)?,
+                    content: content.clone(),
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // 继续订阅更多的频道
+    pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        self.client.subscribe_cmd(Subscribe::new(channels).into_frame(), "subscribe", channels).await?;
+
+        add_subscribed(&mut self.subscribed_channels, channels);
+        Ok(())
+    }
+
+    // 继续订阅更多的模式
+    pub async fn psubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        self.client.subscribe_cmd(PSubscribe::new(patterns).into_frame(), "psubscribe", patterns).await?;
+
+        add_subscribed(&mut self.subscribed_patterns, patterns);
+        Ok(())
+    }
+
+    // 取消订阅，不传频道表示取消所有订阅
+    pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        let frame = Unsubscribe::new(channels).into_frame();
+        let num = if channels.is_empty() {
+            self.subscribed_channels.len()
+        } else {
+            channels.len()
+        };
+
+        let unsubscribed = self.unsubscribe_cmd(frame, "unsubscribe", num).await?;
+        self.subscribed_channels.retain(|c| !unsubscribed.contains(c));
+        Ok(())
+    }
+
+    // 取消模式订阅，不传模式表示取消所有模式订阅
+    pub async fn punsubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        let frame = PUnsubscribe::new(patterns).into_frame();
+        let num = if patterns.is_empty() {
+            self.subscribed_patterns.len()
+        } else {
+            patterns.len()
+        };
+
+        let unsubscribed = self.unsubscribe_cmd(frame, "punsubscribe", num).await?;
+        self.subscribed_patterns.retain(|p| !unsubscribed.contains(p));
+        Ok(())
+    }
+
+    // 不传参数的时候服务端会对每个已订阅的频道(模式)回一条确认，返回确认过的名字
+    async fn unsubscribe_cmd(&mut self, frame: Frame, kind: &str, num: usize) -> crate::Result<Vec<String>> {
+        debug!(request = ?frame);
+        self.client.connection.write_frame(&frame).await?;
+
+        let mut unsubscribed = vec![];
+        for _ in 0..num {
+            let response = self.client.read_response().await?;
+            match response {
+                Frame::Array(ref frame) | Frame::Push(ref frame) => match frame.as_slice() {
+                    [Frame::Bulk(reply), Frame::Bulk(name), ..] if *reply == kind => {
+                        unsubscribed.push(String::from_utf8_lossy(name).into_owned());
+                    }
+                    _ => return Err(response.to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+
+        Ok(unsubscribed)
+    }
+}
+
+// 重复订阅同一个频道服务端只算一次，这里也要去重
+fn add_subscribed(subscribed: &mut Vec<String>, names: &[String]) {
+    for name in names {
+        if !subscribed.contains(name) {
+            subscribed.push(name.clone());
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use bytes::Bytes;
+use std::time::Duration;
+use crate::parse::{Parse, ParseError};
+use crate::db::{Db, BlockingPop};
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::shutdown::Shutdown;
+use tracing::debug;
+
+// BLPOP key [key ...] timeout
+#[derive(Debug, Clone, PartialEq)]
+pub struct BLPop {
+    keys: Vec<Bytes>,
+    timeout: Duration,
+}
+
+// BRPOP key [key ...] timeout
+#[derive(Debug, Clone, PartialEq)]
+pub struct BRPop {
+    keys: Vec<Bytes>,
+    timeout: Duration,
+}
+
+// BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout
+#[derive(Debug, Clone, PartialEq)]
+pub struct BLMove {
+    source: Bytes,
+    destination: Bytes,
+    wherefrom: Direction,
+    whereto: Direction,
+    timeout: Duration,
+}
+
+// 从列表的哪一边弹出或者插入
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+impl BLPop {
+    // timeout为0表示一直等下去
+    pub fn new(keys: Vec<Bytes>, timeout: Duration) -> Self {
+        Self { keys, timeout }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BLPop> {
+        let (keys, timeout) = parse_pop(parse)?;
+        Ok(Self { keys, timeout })
+    }
+
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        apply_pop(db, dst, shutdown, self.keys, Direction::Left, self.timeout).await
+    }
+
+    // 事务和脚本里不等待，列表都是空的就回复Null
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        pop_frame(pop_now(db, self.keys, Direction::Left, None))
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pop_frame("blpop", self.keys, self.timeout)
+    }
+}
+
+impl BRPop {
+    pub fn new(keys: Vec<Bytes>, timeout: Duration) -> Self {
+        Self { keys, timeout }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BRPop> {
+        let (keys, timeout) = parse_pop(parse)?;
+        Ok(Self { keys, timeout })
+    }
+
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        apply_pop(db, dst, shutdown, self.keys, Direction::Right, self.timeout).await
+    }
+
+    // 事务和脚本里不等待，列表都是空的就回复Null
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        pop_frame(pop_now(db, self.keys, Direction::Right, None))
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pop_frame("brpop", self.keys, self.timeout)
+    }
+}
+
+impl BLMove {
+    pub fn new(
+        source: impl Into<Bytes>,
+        destination: impl Into<Bytes>,
+        wherefrom: Direction,
+        whereto: Direction,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            destination: destination.into(),
+            wherefrom,
+            whereto,
+            timeout,
+        }
+    }
+
+    pub fn source(&self) -> &Bytes {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BLMove> {
+        let source = parse.next_byte()?;
+        let destination = parse.next_byte()?;
+        let wherefrom = Direction::parse(&parse.next_string()?)?;
+        let whereto = Direction::parse(&parse.next_string()?)?;
+        let timeout = parse_timeout(&parse.next_byte()?)?;
+
+        Ok(Self { source, destination, wherefrom, whereto, timeout })
+    }
+
+    // 回复挪过去的元素，超时回复Null
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        let destination = Some((self.destination, self.whereto == Direction::Left));
+        let popped = block(db, shutdown, vec![self.source], self.wherefrom, destination, self.timeout).await;
+        if shutdown.is_shutdown() && popped.is_none() {
+            return Ok(());
+        }
+
+        let response = move_frame(popped);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let destination = Some((self.destination, self.whereto == Direction::Left));
+        move_frame(pop_now(db, vec![self.source], self.wherefrom, destination))
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("blmove".as_bytes()));
+        frame.push_bulk(self.source);
+        frame.push_bulk(self.destination);
+        frame.push_bulk(Bytes::from(self.wherefrom.as_str().as_bytes()));
+        frame.push_bulk(Bytes::from(self.whereto.as_str().as_bytes()));
+        frame.push_bulk(format_timeout(self.timeout));
+        frame
+    }
+}
+
+impl Direction {
+    fn parse(s: &str) -> crate::Result<Direction> {
+        if s.eq_ignore_ascii_case("LEFT") {
+            Ok(Direction::Left)
+        } else if s.eq_ignore_ascii_case("RIGHT") {
+            Ok(Direction::Right)
+        } else {
+            Err("ERR syntax error".into())
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Left => "LEFT",
+            Direction::Right => "RIGHT",
+        }
+    }
+}
+
+// 最后一个参数是超时时间，前面的都是key
+fn parse_pop(parse: &mut Parse) -> crate::Result<(Vec<Bytes>, Duration)> {
+    let mut args = vec![parse.next_byte()?, parse.next_byte()?];
+    loop {
+        match parse.next_byte() {
+            Ok(arg) => args.push(arg),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let timeout = parse_timeout(&args.pop().unwrap())?;
+    Ok((args, timeout))
+}
+
+// 超时时间的单位是秒，可以带小数，精确到毫秒
+fn parse_timeout(timeout: &[u8]) -> crate::Result<Duration> {
+    let secs = std::str::from_utf8(timeout)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|secs| secs.is_finite())
+        .ok_or("ERR timeout is not a float or out of range")?;
+    if secs < 0.0 {
+        return Err("ERR timeout is negative".into());
+    }
+    Ok(Duration::from_millis((secs * 1000.0).round() as u64))
+}
+
+// tokio的定时器最多只能等两年多，超时时间再长也分几段睡
+const MAX_SLEEP: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+// 睡到超时为止，超时时间是0的话一直等下去
+pub(crate) async fn sleep_timeout(timeout: Duration) {
+    if timeout == Duration::from_secs(0) {
+        std::future::pending::<()>().await;
+    }
+
+    let mut remaining = timeout;
+    while !remaining.is_zero() {
+        let step = remaining.min(MAX_SLEEP);
+        tokio::time::sleep(step).await;
+        remaining -= step;
+    }
+}
+
+fn format_timeout(timeout: Duration) -> Bytes {
+    Bytes::from(format!("{}.{:03}", timeout.as_secs(), timeout.subsec_millis()))
+}
+
+// 回复弹出的key和元素，超时回复Null
+async fn apply_pop(
+    db: &Db,
+    dst: &mut Connection,
+    shutdown: &mut Shutdown,
+    keys: Vec<Bytes>,
+    wherefrom: Direction,
+    timeout: Duration,
+) -> crate::Result<()> {
+    let popped = block(db, shutdown, keys, wherefrom, None, timeout).await;
+    // 服务关闭了，直接放掉这个连接
+    if shutdown.is_shutdown() && popped.is_none() {
+        return Ok(());
+    }
+
+    let response = pop_frame(popped);
+    debug!(?response);
+    dst.write_frame(&response).await?;
+    Ok(())
+}
+
+// 阻塞直到弹出一个元素，超时或者服务关闭返回None
+async fn block(
+    db: &Db,
+    shutdown: &mut Shutdown,
+    keys: Vec<Bytes>,
+    wherefrom: Direction,
+    destination: Option<(Bytes, bool)>,
+    timeout: Duration,
+) -> Option<crate::Result<(Bytes, Bytes)>> {
+    let (id, mut rx) = match db.blocking_pop(keys, wherefrom == Direction::Left, destination, true) {
+        Ok(BlockingPop::Ready(key, element)) => return Some(Ok((key, element))),
+        Ok(BlockingPop::Blocked(id, rx)) => (id, rx),
+        Ok(BlockingPop::Empty) => return None,
+        Err(err) => return Some(Err(err)),
+    };
+
+    let sleep = sleep_timeout(timeout);
+
+    // 等待期间不占着许可，事务和脚本可以先执行
+    let popped = db.unlocked(async {
+        tokio::select! {
+            res = &mut rx => Some(res.ok()),
+            _ = sleep => None,
+            _ = shutdown.recv() => None,
+        }
+    }).await;
+    if let Some(popped) = popped {
+        return popped;
+    }
+
+    // 取消等待和元素送过来可能同时发生，已经送过来了就不能丢掉
+    if db.unblock(id) {
+        None
+    } else {
+        rx.await.ok()
+    }
+}
+
+// 只弹一次，没有元素的话不排队
+fn pop_now(
+    db: &Db,
+    keys: Vec<Bytes>,
+    wherefrom: Direction,
+    destination: Option<(Bytes, bool)>,
+) -> Option<crate::Result<(Bytes, Bytes)>> {
+    match db.blocking_pop(keys, wherefrom == Direction::Left, destination, false) {
+        Ok(BlockingPop::Ready(key, element)) => Some(Ok((key, element))),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    }
+}
+
+// BLPOP和BRPOP回复弹出的key和元素，超时回复Null
+fn pop_frame(popped: Option<crate::Result<(Bytes, Bytes)>>) -> Frame {
+    match popped {
+        Some(Ok((key, element))) => Frame::Array(vec![Frame::Bulk(key), Frame::Bulk(element)]),
+        Some(Err(err)) => Frame::Error(err.to_string()),
+        None => Frame::Null,
+    }
+}
+
+// BLMOVE只回复挪过去的元素
+fn move_frame(popped: Option<crate::Result<(Bytes, Bytes)>>) -> Frame {
+    match popped {
+        Some(Ok((_, element))) => Frame::Bulk(element),
+        Some(Err(err)) => Frame::Error(err.to_string()),
+        None => Frame::Null,
+    }
+}
+
+fn make_pop_frame(name: &str, keys: Vec<Bytes>, timeout: Duration) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    for key in keys {
+        frame.push_bulk(key);
+    }
+    frame.push_bulk(format_timeout(timeout));
+    frame
+}
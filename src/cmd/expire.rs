@@ -0,0 +1,481 @@
+use bytes::Bytes;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::convert::TryFrom;
+use tokio::time::Instant;
+use crate::parse::{Parse, ParseError};
+use crate::db::{Db, ExpireOptions};
+use crate::frame::Frame;
+use crate::cmd::make_key_frame;
+
+// EXPIRE key seconds [NX|XX|GT|LT]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expire {
+    key: Bytes,
+    seconds: i64,
+    options: ExpireOptions,
+}
+
+// PEXPIRE key milliseconds [NX|XX|GT|LT]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PExpire {
+    key: Bytes,
+    milliseconds: i64,
+    options: ExpireOptions,
+}
+
+// EXPIREAT key unix-time-seconds [NX|XX|GT|LT]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpireAt {
+    key: Bytes,
+    timestamp: i64,
+    options: ExpireOptions,
+}
+
+// PEXPIREAT key unix-time-milliseconds [NX|XX|GT|LT]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PExpireAt {
+    key: Bytes,
+    timestamp: i64,
+    options: ExpireOptions,
+}
+
+// TTL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ttl {
+    key: Bytes,
+}
+
+// PTTL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct PTtl {
+    key: Bytes,
+}
+
+// PERSIST key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persist {
+    key: Bytes,
+}
+
+// EXPIRETIME key
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpireTime {
+    key: Bytes,
+}
+
+// PEXPIRETIME key
+#[derive(Debug, Clone, PartialEq)]
+pub struct PExpireTime {
+    key: Bytes,
+}
+
+impl Expire {
+    pub fn new(key: impl Into<Bytes>, seconds: i64) -> Self {
+        Self {
+            key: key.into(),
+            seconds,
+            options: ExpireOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ExpireOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    pub fn options(&self) -> &ExpireOptions {
+        &self.options
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Expire> {
+        let key = parse.next_byte()?;
+        let seconds = parse.next_int()?;
+        let options = parse_options(parse)?;
+
+        Ok(Self { key, seconds, options })
+    }
+
+    // 换算成unix时间戳(毫秒)，溢出了就是None
+    fn unix_millis(&self) -> Option<i64> {
+        now_millis().checked_add(self.seconds.checked_mul(1000)?)
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_expire(db, &self.key, self.unix_millis(), self.options, "expire")
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("expire", self.key);
+        frame.push_bulk(Bytes::from(self.seconds.to_string()));
+        push_options(&mut frame, &self.options);
+        frame
+    }
+}
+
+impl PExpire {
+    pub fn new(key: impl Into<Bytes>, milliseconds: i64) -> Self {
+        Self {
+            key: key.into(),
+            milliseconds,
+            options: ExpireOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ExpireOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn milliseconds(&self) -> i64 {
+        self.milliseconds
+    }
+
+    pub fn options(&self) -> &ExpireOptions {
+        &self.options
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PExpire> {
+        let key = parse.next_byte()?;
+        let milliseconds = parse.next_int()?;
+        let options = parse_options(parse)?;
+
+        Ok(Self { key, milliseconds, options })
+    }
+
+    // 换算成unix时间戳(毫秒)，溢出了就是None
+    fn unix_millis(&self) -> Option<i64> {
+        now_millis().checked_add(self.milliseconds)
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_expire(db, &self.key, self.unix_millis(), self.options, "pexpire")
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("pexpire", self.key);
+        frame.push_bulk(Bytes::from(self.milliseconds.to_string()));
+        push_options(&mut frame, &self.options);
+        frame
+    }
+}
+
+impl ExpireAt {
+    pub fn new(key: impl Into<Bytes>, timestamp: i64) -> Self {
+        Self {
+            key: key.into(),
+            timestamp,
+            options: ExpireOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ExpireOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn options(&self) -> &ExpireOptions {
+        &self.options
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ExpireAt> {
+        let key = parse.next_byte()?;
+        let timestamp = parse.next_int()?;
+        let options = parse_options(parse)?;
+
+        Ok(Self { key, timestamp, options })
+    }
+
+    // 换算成unix时间戳(毫秒)，溢出了就是None
+    fn unix_millis(&self) -> Option<i64> {
+        self.timestamp.checked_mul(1000)
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_expire(db, &self.key, self.unix_millis(), self.options, "expireat")
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("expireat", self.key);
+        frame.push_bulk(Bytes::from(self.timestamp.to_string()));
+        push_options(&mut frame, &self.options);
+        frame
+    }
+}
+
+impl PExpireAt {
+    pub fn new(key: impl Into<Bytes>, timestamp: i64) -> Self {
+        Self {
+            key: key.into(),
+            timestamp,
+            options: ExpireOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ExpireOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn options(&self) -> &ExpireOptions {
+        &self.options
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PExpireAt> {
+        let key = parse.next_byte()?;
+        let timestamp = parse.next_int()?;
+        let options = parse_options(parse)?;
+
+        Ok(Self { key, timestamp, options })
+    }
+
+    // 换算成unix时间戳(毫秒)，溢出了就是None
+    fn unix_millis(&self) -> Option<i64> {
+        Some(self.timestamp)
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_expire(db, &self.key, self.unix_millis(), self.options, "pexpireat")
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("pexpireat", self.key);
+        frame.push_bulk(Bytes::from(self.timestamp.to_string()));
+        push_options(&mut frame, &self.options);
+        frame
+    }
+}
+
+impl Ttl {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Ttl> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // 按四舍五入回复剩余的秒数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_ttl(db, self.key, |remaining| ((remaining.as_millis() + 500) / 1000) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("ttl", self.key)
+    }
+}
+
+impl PTtl {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PTtl> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_ttl(db, self.key, |remaining| remaining.as_millis() as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("pttl", self.key)
+    }
+}
+
+impl Persist {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Persist> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // 去掉了过期时间回复1，key不存在或者本来就没有过期时间回复0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.persist(&self.key) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("persist", self.key)
+    }
+}
+
+impl ExpireTime {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ExpireTime> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // 过期时刻是按单调时钟存的，换算回unix时间会差一两毫秒，这里四舍五入到秒
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_ttl(db, self.key, |remaining| unix_millis_after(remaining).saturating_add(500) / 1000)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("expiretime", self.key)
+    }
+}
+
+impl PExpireTime {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PExpireTime> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_ttl(db, self.key, unix_millis_after)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("pexpiretime", self.key)
+    }
+}
+
+// 当前的unix时间戳(毫秒)
+fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+// 过了remaining之后的unix时间戳(毫秒)，PEXPIREAT可以设到i64::MAX，加上去会溢出
+fn unix_millis_after(remaining: Duration) -> i64 {
+    let remaining = i64::try_from(remaining.as_millis()).unwrap_or(i64::MAX);
+    now_millis().saturating_add(remaining)
+}
+
+// NX|XX|GT|LT可以出现多次，NX不能和其他的一起用，GT和LT也不能一起用
+fn parse_options(parse: &mut Parse) -> crate::Result<ExpireOptions> {
+    let mut options = ExpireOptions::default();
+    loop {
+        let option = match parse.next_string() {
+            Ok(option) => option,
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        };
+        match &option.to_ascii_uppercase()[..] {
+            "NX" => options.nx = true,
+            "XX" => options.xx = true,
+            "GT" => options.gt = true,
+            "LT" => options.lt = true,
+            _ => return Err(format!("ERR Unsupported option {}", option).into()),
+        }
+    }
+
+    if options.nx && (options.xx || options.gt || options.lt) {
+        return Err("ERR NX and XX, GT or LT options at the same time are not compatible".into());
+    }
+    if options.gt && options.lt {
+        return Err("ERR GT and LT options at the same time are not compatible".into());
+    }
+    Ok(options)
+}
+
+fn push_options(frame: &mut Frame, options: &ExpireOptions) {
+    let flags = [(options.nx, "NX"), (options.xx, "XX"), (options.gt, "GT"), (options.lt, "LT")];
+    for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+        frame.push_bulk(Bytes::from(flag.as_bytes()));
+    }
+}
+
+// 设置了过期时间回复1，key不存在或者不满足条件回复0。时间已经过了的key直接删掉，也算设置成功
+fn execute_expire(
+    db: &Db,
+    key: &[u8],
+    unix_millis: Option<i64>,
+    options: ExpireOptions,
+    command: &str,
+) -> Frame {
+    let now = Instant::now();
+    let expires_at = unix_millis.and_then(|unix_millis| {
+        let remaining = unix_millis.checked_sub(now_millis())?;
+        now.checked_add(Duration::from_millis(remaining.max(0) as u64))
+    });
+
+    match expires_at {
+        Some(expires_at) => Frame::Integer(db.expire(key, expires_at, &options) as i64),
+        None => Frame::Error(format!("ERR invalid expire time in '{}' command", command)),
+    }
+}
+
+// key不存在回复-2，没有过期时间回复-1，其他的按剩余时间换算
+fn execute_ttl(db: &Db, key: Bytes, convert: impl FnOnce(Duration) -> i64) -> Frame {
+    match db.expiration(&key) {
+        Some(Some(expires_at)) => Frame::Integer(convert(expires_at.saturating_duration_since(Instant::now()))),
+        Some(None) => Frame::Integer(-1),
+        None => Frame::Integer(-2),
+    }
+}
@@ -1,43 +1,46 @@
 use crate::parse::Parse;
 use crate::db::Db;
-use crate::connection::Connection;
 use crate::frame::Frame;
-use tracing::{debug, instrument};
+use bytes::Bytes;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Get {
-    key: String
+    key: Bytes
 }
 
 impl Get {
-    pub fn new(key: impl ToString) -> Self {
+    pub fn new(key: impl Into<Bytes>) -> Self {
         Self {
-            key: key.to_string()
+            key: key.into()
         }
     }
 
-    pub fn key(&self) -> &str {
+    pub fn key(&self) -> &Bytes {
         &self.key
     }
 
     // 解析参数
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Get> {
-        let key = parse.next_string()?;
+        let key = parse.next_byte()?;
 
         Ok(Self {
             key
         })
     }
 
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        let response = if let Some(value) = db.get(&self.key) {
-            Frame::Bulk(value)
-        } else {
-            Frame::Null
-        };
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
 
-        debug!(?response);
-        dst.write_frame(&response).await?;
-        Ok(())
+    // 转换成发送给服务端的frame
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("get".as_bytes()));
+        frame.push_bulk(self.key);
+        frame
     }
 }
\ No newline at end of file
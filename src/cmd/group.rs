@@ -0,0 +1,852 @@
+use bytes::Bytes;
+use std::time::Duration;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::connection::{Connection, Protocol};
+use crate::frame::Frame;
+use crate::shutdown::Shutdown;
+use crate::stream::{StreamId, PendingEntryFields, ClaimOptions, PendingRange};
+use crate::cmd::stream::{INVALID_ID, read_or_block, parse_block, parse_streams, parse_start, parse_end};
+use crate::cmd::stream::{streams_frame, entries_frame, entry_frame};
+use tracing::debug;
+
+// XGROUP 管理消费组和组里的消费者
+#[derive(Debug, Clone, PartialEq)]
+pub enum XGroup {
+    // XGROUP CREATE key group id|$ [MKSTREAM]，id为None表示`$`
+    Create {
+        key: Bytes,
+        group: Bytes,
+        id: Option<StreamId>,
+        mkstream: bool,
+    },
+    // XGROUP DESTROY key group
+    Destroy {
+        key: Bytes,
+        group: Bytes,
+    },
+    // XGROUP CREATECONSUMER key group consumer
+    CreateConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+    // XGROUP DELCONSUMER key group consumer
+    DelConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+    // XGROUP SETID key group id|$
+    SetId {
+        key: Bytes,
+        group: Bytes,
+        id: Option<StreamId>,
+    },
+}
+
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XReadGroup {
+    group: Bytes,
+    consumer: Bytes,
+    keys: Vec<Bytes>,
+    // None表示`>`，读还没有发给组里任何消费者的新消息
+    ids: Vec<Option<StreamId>>,
+    count: Option<u64>,
+    block: Option<Duration>,
+    noack: bool,
+}
+
+// XACK key group id [id ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XAck {
+    key: Bytes,
+    group: Bytes,
+    ids: Vec<StreamId>,
+}
+
+// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPending {
+    key: Bytes,
+    group: Bytes,
+    range: Option<PendingRange>,
+}
+
+// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+//     [RETRYCOUNT count] [FORCE] [JUSTID]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XClaim {
+    key: Bytes,
+    group: Bytes,
+    consumer: Bytes,
+    min_idle: u64,
+    ids: Vec<StreamId>,
+    options: ClaimOptions,
+}
+
+// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XAutoClaim {
+    key: Bytes,
+    group: Bytes,
+    consumer: Bytes,
+    min_idle: u64,
+    start: StreamId,
+    count: Option<u64>,
+    justid: bool,
+}
+
+// XINFO 流、消费组和消费者的状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum XInfo {
+    // XINFO STREAM key
+    Stream(Bytes),
+    // XINFO GROUPS key
+    Groups(Bytes),
+    // XINFO CONSUMERS key group
+    Consumers(Bytes, Bytes),
+}
+
+impl XGroup {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XGroup> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match &subcommand[..] {
+            "create" => {
+                let key = parse.next_byte()?;
+                let group = parse.next_byte()?;
+                let id = parse_group_id(&parse.next_byte()?)?;
+                let mut mkstream = false;
+                loop {
+                    match parse.next_string() {
+                        Ok(arg) if arg.eq_ignore_ascii_case("MKSTREAM") => mkstream = true,
+                        Ok(_) => return Err("ERR syntax error".into()),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(XGroup::Create { key, group, id, mkstream })
+            }
+            "destroy" => Ok(XGroup::Destroy {
+                key: parse.next_byte()?,
+                group: parse.next_byte()?,
+            }),
+            "createconsumer" => Ok(XGroup::CreateConsumer {
+                key: parse.next_byte()?,
+                group: parse.next_byte()?,
+                consumer: parse.next_byte()?,
+            }),
+            "delconsumer" => Ok(XGroup::DelConsumer {
+                key: parse.next_byte()?,
+                group: parse.next_byte()?,
+                consumer: parse.next_byte()?,
+            }),
+            "setid" => Ok(XGroup::SetId {
+                key: parse.next_byte()?,
+                group: parse.next_byte()?,
+                id: parse_group_id(&parse.next_byte()?)?,
+            }),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try XGROUP HELP.", subcommand).into()),
+        }
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let result = match self {
+            XGroup::Create { key, group, id, mkstream } => {
+                db.xgroup_create(key, group, id, mkstream).map(|_| Frame::Simple("OK".to_string()))
+            }
+            XGroup::Destroy { key, group } => {
+                db.xgroup_destroy(&key, &group).map(|destroyed| Frame::Integer(destroyed as i64))
+            }
+            XGroup::CreateConsumer { key, group, consumer } => {
+                db.xgroup_createconsumer(&key, &group, consumer).map(|created| Frame::Integer(created as i64))
+            }
+            // 回复消费者被删掉时还没确认的消息条数
+            XGroup::DelConsumer { key, group, consumer } => {
+                db.xgroup_delconsumer(&key, &group, &consumer).map(|pending| Frame::Integer(pending as i64))
+            }
+            XGroup::SetId { key, group, id } => {
+                db.xgroup_setid(&key, &group, id).map(|_| Frame::Simple("OK".to_string()))
+            }
+        };
+        result.unwrap_or_else(|err| Frame::Error(err.to_string()))
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xgroup".as_bytes()));
+        match self {
+            XGroup::Create { key, group, id, mkstream } => {
+                frame.push_bulk(Bytes::from("create".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+                frame.push_bulk(format_group_id(id));
+                if mkstream {
+                    frame.push_bulk(Bytes::from("MKSTREAM".as_bytes()));
+                }
+            }
+            XGroup::Destroy { key, group } => {
+                frame.push_bulk(Bytes::from("destroy".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+            }
+            XGroup::CreateConsumer { key, group, consumer } => {
+                frame.push_bulk(Bytes::from("createconsumer".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+                frame.push_bulk(consumer);
+            }
+            XGroup::DelConsumer { key, group, consumer } => {
+                frame.push_bulk(Bytes::from("delconsumer".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+                frame.push_bulk(consumer);
+            }
+            XGroup::SetId { key, group, id } => {
+                frame.push_bulk(Bytes::from("setid".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+                frame.push_bulk(format_group_id(id));
+            }
+        }
+        frame
+    }
+}
+
+impl XReadGroup {
+    // keys和ids一一对应
+    pub fn new(group: impl Into<Bytes>, consumer: impl Into<Bytes>, keys: Vec<Bytes>, ids: Vec<Option<StreamId>>) -> Self {
+        Self {
+            group: group.into(),
+            consumer: consumer.into(),
+            keys,
+            ids,
+            count: None,
+            block: None,
+            noack: false,
+        }
+    }
+
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    // 只有全部都是`>`的时候才会阻塞
+    pub fn with_block(mut self, block: Duration) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    // 读到的消息不放进PEL，相当于读完马上确认
+    pub fn with_noack(mut self) -> Self {
+        self.noack = true;
+        self
+    }
+
+    pub fn group(&self) -> &Bytes {
+        &self.group
+    }
+
+    pub fn consumer(&self) -> &Bytes {
+        &self.consumer
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XReadGroup> {
+        let mut group = None;
+        let mut count = None;
+        let mut block = None;
+        let mut noack = false;
+
+        loop {
+            let arg = parse.next_string()?;
+            match &arg.to_ascii_uppercase()[..] {
+                "GROUP" => group = Some((parse.next_byte()?, parse.next_byte()?)),
+                "COUNT" => count = Some(parse.next_uint()?),
+                "BLOCK" => block = Some(parse_block(parse)?),
+                "NOACK" => noack = true,
+                "STREAMS" => break,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        let (group, consumer) = group.ok_or("ERR Missing GROUP option for XREADGROUP")?;
+
+        let (keys, ids) = parse_streams(parse, "xreadgroup")?;
+        let ids = ids
+            .iter()
+            .map(|id| match &id[..] {
+                b">" => Ok(None),
+                b"$" => Err("ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history \
+of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty \
+result set."),
+                id => StreamId::parse(id, 0).map(Some).ok_or(INVALID_ID),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { group, consumer, keys, ids, count, block, noack })
+    }
+
+    // 和XREAD一样的格式，读历史的时候已经被删掉的消息内容是Null
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        let found = read_or_block(db, shutdown, &self.keys, self.block, || self.read(db)).await;
+        // 服务关闭了，直接放掉这个连接
+        if shutdown.is_shutdown() && found.as_ref().is_ok_and(|found| found.is_empty()) {
+            return Ok(());
+        }
+
+        let response = xreadgroup_frame(dst.protocol(), found);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    // 事务和脚本里不阻塞，只读一次
+    pub(crate) fn execute(self, db: &Db, protocol: Protocol) -> Frame {
+        xreadgroup_frame(protocol, self.read(db))
+    }
+
+    fn read(&self, db: &Db) -> crate::Result<Vec<(Bytes, Vec<PendingEntryFields>)>> {
+        // COUNT 0和不带COUNT一样
+        let count = self.count.filter(|count| *count > 0).map(|count| count as usize);
+        db.xreadgroup(&self.group, &self.consumer, &self.keys, &self.ids, count, self.noack)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xreadgroup".as_bytes()));
+        frame.push_bulk(Bytes::from("GROUP".as_bytes()));
+        frame.push_bulk(self.group);
+        frame.push_bulk(self.consumer);
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if let Some(block) = self.block {
+            frame.push_bulk(Bytes::from("BLOCK".as_bytes()));
+            frame.push_bulk(Bytes::from(block.as_millis().to_string()));
+        }
+        if self.noack {
+            frame.push_bulk(Bytes::from("NOACK".as_bytes()));
+        }
+        frame.push_bulk(Bytes::from("STREAMS".as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(key);
+        }
+        for id in self.ids {
+            match id {
+                Some(id) => frame.push_bulk(Bytes::from(id.to_string())),
+                None => frame.push_bulk(Bytes::from(">".as_bytes())),
+            }
+        }
+        frame
+    }
+}
+
+impl XAck {
+    pub fn new(key: impl Into<Bytes>, group: impl Into<Bytes>, ids: Vec<StreamId>) -> Self {
+        Self {
+            key: key.into(),
+            group: group.into(),
+            ids,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn ids(&self) -> &[StreamId] {
+        &self.ids
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XAck> {
+        let key = parse.next_byte()?;
+        let group = parse.next_byte()?;
+        let mut ids = vec![parse_id(&parse.next_byte()?)?];
+        loop {
+            match parse.next_byte() {
+                Ok(id) => ids.push(parse_id(&id)?),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { key, group, ids })
+    }
+
+    // 回复确实在PEL里被确认掉的条数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.xack(&self.key, &self.group, &self.ids) {
+            Ok(acked) => Frame::Integer(acked as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xack".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(self.group);
+        for id in self.ids {
+            frame.push_bulk(Bytes::from(id.to_string()));
+        }
+        frame
+    }
+}
+
+impl XPending {
+    // 不带范围的时候回复汇总信息
+    pub fn new(key: impl Into<Bytes>, group: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            group: group.into(),
+            range: None,
+        }
+    }
+
+    // 列出范围内的每条待确认消息
+    pub fn with_range(mut self, range: PendingRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn group(&self) -> &Bytes {
+        &self.group
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XPending> {
+        let key = parse.next_byte()?;
+        let group = parse.next_byte()?;
+
+        let mut arg = match parse.next_byte() {
+            Ok(arg) => arg,
+            Err(ParseError::EndOfStream) => return Ok(Self { key, group, range: None }),
+            Err(err) => return Err(err.into()),
+        };
+        let mut idle = None;
+        if arg.eq_ignore_ascii_case(b"IDLE") {
+            idle = Some(parse.next_uint()?);
+            arg = parse.next_byte()?;
+        }
+
+        let start = parse_start(&arg)?;
+        let end = parse_end(&parse.next_byte()?)?;
+        let count = parse.next_uint()?;
+        let consumer = match parse.next_byte() {
+            Ok(consumer) => Some(consumer),
+            Err(ParseError::EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        let range = PendingRange { start, end, count, consumer, idle };
+        Ok(Self { key, group, range: Some(range) })
+    }
+
+    // 汇总回复[条数, 最小ID, 最大ID, [[消费者, 条数], ...]]，
+    // 带范围回复[[ID, 消费者, 空闲毫秒数, 投递次数], ...]
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.range {
+            None => match db.xpending(&self.key, &self.group) {
+                Ok(summary) if summary.count == 0 => {
+                    Frame::Array(vec![Frame::Integer(0), Frame::Null, Frame::Null, Frame::Null])
+                }
+                Ok(summary) => {
+                    let format_id = |id: Option<StreamId>| Frame::Bulk(Bytes::from(id.unwrap().to_string()));
+                    let consumers = summary
+                        .consumers
+                        .into_iter()
+                        .map(|(name, count)| Frame::Array(vec![Frame::Bulk(name), Frame::Bulk(Bytes::from(count.to_string()))]))
+                        .collect();
+                    Frame::Array(vec![
+                        Frame::Integer(summary.count as i64),
+                        format_id(summary.first),
+                        format_id(summary.last),
+                        Frame::Array(consumers),
+                    ])
+                }
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            Some(range) => match db.xpending_range(&self.key, &self.group, &range) {
+                Ok(entries) => Frame::Array(
+                    entries
+                        .into_iter()
+                        .map(|entry| {
+                            Frame::Array(vec![
+                                Frame::Bulk(Bytes::from(entry.id.to_string())),
+                                Frame::Bulk(entry.consumer),
+                                Frame::Integer(entry.idle as i64),
+                                Frame::Integer(entry.deliveries as i64),
+                            ])
+                        })
+                        .collect(),
+                ),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xpending".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(self.group);
+        if let Some(range) = self.range {
+            if let Some(idle) = range.idle {
+                frame.push_bulk(Bytes::from("IDLE".as_bytes()));
+                frame.push_bulk(Bytes::from(idle.to_string()));
+            }
+            frame.push_bulk(Bytes::from(range.start.to_string()));
+            frame.push_bulk(Bytes::from(range.end.to_string()));
+            frame.push_bulk(Bytes::from(range.count.to_string()));
+            if let Some(consumer) = range.consumer {
+                frame.push_bulk(consumer);
+            }
+        }
+        frame
+    }
+}
+
+impl XClaim {
+    // 至少要有一个ID
+    pub fn new(
+        key: impl Into<Bytes>,
+        group: impl Into<Bytes>,
+        consumer: impl Into<Bytes>,
+        min_idle: u64,
+        ids: Vec<StreamId>,
+        options: ClaimOptions,
+    ) -> Self {
+        Self {
+            key: key.into(),
+            group: group.into(),
+            consumer: consumer.into(),
+            min_idle,
+            ids,
+            options,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn consumer(&self) -> &Bytes {
+        &self.consumer
+    }
+
+    pub fn ids(&self) -> &[StreamId] {
+        &self.ids
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XClaim> {
+        let key = parse.next_byte()?;
+        let group = parse.next_byte()?;
+        let consumer = parse.next_byte()?;
+        let min_idle = parse.next_uint()?;
+
+        // ID一直读到第一个不是ID的参数，后面的都是选项
+        let mut ids = vec![parse_id(&parse.next_byte()?)?];
+        let mut options = ClaimOptions::default();
+        let mut arg = None;
+        loop {
+            match parse.next_byte() {
+                Ok(next) => match StreamId::parse(&next, 0) {
+                    Some(id) => ids.push(id),
+                    None => {
+                        arg = Some(next);
+                        break;
+                    }
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        while let Some(option) = arg {
+            match &option.to_ascii_uppercase()[..] {
+                b"IDLE" => options.idle = Some(parse.next_uint()?),
+                b"TIME" => options.time = Some(parse.next_uint()?),
+                b"RETRYCOUNT" => options.retry_count = Some(parse.next_uint()?),
+                b"FORCE" => options.force = true,
+                b"JUSTID" => options.justid = true,
+                _ => return Err(format!("ERR Unrecognized XCLAIM option '{}'", String::from_utf8_lossy(&option)).into()),
+            }
+            arg = match parse.next_byte() {
+                Ok(next) => Some(next),
+                Err(ParseError::EndOfStream) => None,
+                Err(err) => return Err(err.into()),
+            };
+        }
+
+        Ok(Self { key, group, consumer, min_idle, ids, options })
+    }
+
+    // 回复认领到的消息，JUSTID的时候只回复ID
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let justid = self.options.justid;
+        match db.xclaim(&self.key, &self.group, &self.consumer, self.min_idle, &self.ids, &self.options) {
+            Ok(claimed) if justid => ids_frame(claimed.into_iter().map(|(id, _)| id)),
+            Ok(claimed) => entries_frame(claimed),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xclaim".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(self.group);
+        frame.push_bulk(self.consumer);
+        frame.push_bulk(Bytes::from(self.min_idle.to_string()));
+        for id in self.ids {
+            frame.push_bulk(Bytes::from(id.to_string()));
+        }
+        if let Some(idle) = self.options.idle {
+            frame.push_bulk(Bytes::from("IDLE".as_bytes()));
+            frame.push_bulk(Bytes::from(idle.to_string()));
+        }
+        if let Some(time) = self.options.time {
+            frame.push_bulk(Bytes::from("TIME".as_bytes()));
+            frame.push_bulk(Bytes::from(time.to_string()));
+        }
+        if let Some(retry_count) = self.options.retry_count {
+            frame.push_bulk(Bytes::from("RETRYCOUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(retry_count.to_string()));
+        }
+        if self.options.force {
+            frame.push_bulk(Bytes::from("FORCE".as_bytes()));
+        }
+        if self.options.justid {
+            frame.push_bulk(Bytes::from("JUSTID".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl XAutoClaim {
+    pub fn new(key: impl Into<Bytes>, group: impl Into<Bytes>, consumer: impl Into<Bytes>, min_idle: u64, start: StreamId) -> Self {
+        Self {
+            key: key.into(),
+            group: group.into(),
+            consumer: consumer.into(),
+            min_idle,
+            start,
+            count: None,
+            justid: false,
+        }
+    }
+
+    // 最多认领多少条，默认100
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn with_justid(mut self) -> Self {
+        self.justid = true;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn consumer(&self) -> &Bytes {
+        &self.consumer
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XAutoClaim> {
+        let key = parse.next_byte()?;
+        let group = parse.next_byte()?;
+        let consumer = parse.next_byte()?;
+        let min_idle = parse.next_uint()?;
+        let start = parse_start(&parse.next_byte()?)?;
+
+        let mut count = None;
+        let mut justid = false;
+        loop {
+            match parse.next_string() {
+                Ok(arg) => match &arg.to_ascii_uppercase()[..] {
+                    "COUNT" => match parse.next_uint()? {
+                        0 => return Err("ERR COUNT must be > 0".into()),
+                        n => count = Some(n),
+                    },
+                    "JUSTID" => justid = true,
+                    _ => return Err("ERR syntax error".into()),
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { key, group, consumer, min_idle, start, count, justid })
+    }
+
+    // 回复[下一次开始的ID, 认领到的消息, 已经被删掉的消息ID]，下一次的ID是0-0表示扫完了
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.unwrap_or(100) as usize;
+        let result = db.xautoclaim(&self.key, &self.group, &self.consumer, self.min_idle, self.start, count, self.justid);
+        match result {
+            Ok((next, claimed, deleted)) => {
+                let claimed = if self.justid {
+                    ids_frame(claimed.into_iter().map(|(id, _)| id))
+                } else {
+                    entries_frame(claimed)
+                };
+                Frame::Array(vec![Frame::Bulk(Bytes::from(next.to_string())), claimed, ids_frame(deleted.into_iter())])
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xautoclaim".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(self.group);
+        frame.push_bulk(self.consumer);
+        frame.push_bulk(Bytes::from(self.min_idle.to_string()));
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if self.justid {
+            frame.push_bulk(Bytes::from("JUSTID".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl XInfo {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XInfo> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match &subcommand[..] {
+            "stream" => Ok(XInfo::Stream(parse.next_byte()?)),
+            "groups" => Ok(XInfo::Groups(parse.next_byte()?)),
+            "consumers" => Ok(XInfo::Consumers(parse.next_byte()?, parse.next_byte()?)),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try XINFO HELP.", subcommand).into()),
+        }
+    }
+
+    // 每一项都回复成Map，RESP2下是平铺的数组
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let field = |name: &'static str, value: Frame| (Frame::Bulk(Bytes::from(name.as_bytes())), value);
+        let format_id = |id: StreamId| Frame::Bulk(Bytes::from(id.to_string()));
+
+        let result = match self {
+            XInfo::Stream(key) => db.xinfo_stream(&key).map(|info| {
+                let entry = |entry: Option<(StreamId, Vec<(Bytes, Bytes)>)>| match entry {
+                    Some((id, fields)) => entry_frame(id, Some(fields)),
+                    None => Frame::Null,
+                };
+                Frame::Map(vec![
+                    field("length", Frame::Integer(info.length as i64)),
+                    field("last-generated-id", format_id(info.last_id)),
+                    field("entries-added", Frame::Integer(info.entries_added as i64)),
+                    field("groups", Frame::Integer(info.groups as i64)),
+                    field("first-entry", entry(info.first)),
+                    field("last-entry", entry(info.last)),
+                ])
+            }),
+            XInfo::Groups(key) => db.xinfo_groups(&key).map(|groups| {
+                Frame::Array(
+                    groups
+                        .into_iter()
+                        .map(|group| {
+                            Frame::Map(vec![
+                                field("name", Frame::Bulk(group.name)),
+                                field("consumers", Frame::Integer(group.consumers as i64)),
+                                field("pending", Frame::Integer(group.pending as i64)),
+                                field("last-delivered-id", format_id(group.last_id)),
+                            ])
+                        })
+                        .collect(),
+                )
+            }),
+            // inactive是最后一次读到消息到现在的毫秒数，从来没读到过是-1
+            XInfo::Consumers(key, group) => db.xinfo_consumers(&key, &group).map(|consumers| {
+                Frame::Array(
+                    consumers
+                        .into_iter()
+                        .map(|consumer| {
+                            Frame::Map(vec![
+                                field("name", Frame::Bulk(consumer.name)),
+                                field("pending", Frame::Integer(consumer.pending as i64)),
+                                field("idle", Frame::Integer(consumer.idle as i64)),
+                                field("inactive", Frame::Integer(consumer.inactive.map_or(-1, |inactive| inactive as i64))),
+                            ])
+                        })
+                        .collect(),
+                )
+            }),
+        };
+        result.unwrap_or_else(|err| Frame::Error(err.to_string()))
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xinfo".as_bytes()));
+        match self {
+            XInfo::Stream(key) => {
+                frame.push_bulk(Bytes::from("stream".as_bytes()));
+                frame.push_bulk(key);
+            }
+            XInfo::Groups(key) => {
+                frame.push_bulk(Bytes::from("groups".as_bytes()));
+                frame.push_bulk(key);
+            }
+            XInfo::Consumers(key, group) => {
+                frame.push_bulk(Bytes::from("consumers".as_bytes()));
+                frame.push_bulk(key);
+                frame.push_bulk(group);
+            }
+        }
+        frame
+    }
+}
+
+fn parse_id(id: &[u8]) -> crate::Result<StreamId> {
+    Ok(StreamId::parse(id, 0).ok_or(INVALID_ID)?)
+}
+
+// `$`表示流里最新的消息
+fn parse_group_id(id: &[u8]) -> crate::Result<Option<StreamId>> {
+    match id {
+        b"$" => Ok(None),
+        id => parse_id(id).map(Some),
+    }
+}
+
+fn format_group_id(id: Option<StreamId>) -> Bytes {
+    match id {
+        Some(id) => Bytes::from(id.to_string()),
+        None => Bytes::from("$".as_bytes()),
+    }
+}
+
+fn xreadgroup_frame(protocol: Protocol, found: crate::Result<Vec<(Bytes, Vec<PendingEntryFields>)>>) -> Frame {
+    match found {
+        Ok(found) => streams_frame(
+            protocol,
+            found.into_iter().map(|(key, entries)| {
+                let entries = entries.into_iter().map(|(id, fields)| entry_frame(id, fields)).collect();
+                (key, Frame::Array(entries))
+            }),
+        ),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+fn ids_frame(ids: impl Iterator<Item = StreamId>) -> Frame {
+    Frame::Array(ids.map(|id| Frame::Bulk(Bytes::from(id.to_string()))).collect())
+}
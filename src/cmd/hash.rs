@@ -0,0 +1,624 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::frame::Frame;
+use crate::cmd::make_key_frame;
+use crate::glob;
+
+// HSET key field value [field value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HSet {
+    key: Bytes,
+    pairs: Vec<(Bytes, Bytes)>,
+}
+
+// HGET key field
+#[derive(Debug, Clone, PartialEq)]
+pub struct HGet {
+    key: Bytes,
+    field: Bytes,
+}
+
+// HMGET key field [field ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HMGet {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+// HDEL key field [field ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HDel {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+// HEXISTS key field
+#[derive(Debug, Clone, PartialEq)]
+pub struct HExists {
+    key: Bytes,
+    field: Bytes,
+}
+
+// HLEN key
+#[derive(Debug, Clone, PartialEq)]
+pub struct HLen {
+    key: Bytes,
+}
+
+// HKEYS key
+#[derive(Debug, Clone, PartialEq)]
+pub struct HKeys {
+    key: Bytes,
+}
+
+// HVALS key
+#[derive(Debug, Clone, PartialEq)]
+pub struct HVals {
+    key: Bytes,
+}
+
+// HGETALL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct HGetAll {
+    key: Bytes,
+}
+
+// HINCRBY key field increment
+#[derive(Debug, Clone, PartialEq)]
+pub struct HIncrBy {
+    key: Bytes,
+    field: Bytes,
+    increment: i64,
+}
+
+// HINCRBYFLOAT key field increment
+#[derive(Debug, Clone, PartialEq)]
+pub struct HIncrByFloat {
+    key: Bytes,
+    field: Bytes,
+    increment: f64,
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HScan {
+    key: Bytes,
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: Option<u64>,
+    novalues: bool,
+}
+
+impl HSet {
+    pub fn new(key: impl Into<Bytes>, pairs: Vec<(Bytes, Bytes)>) -> Self {
+        Self {
+            key: key.into(),
+            pairs,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn pairs(&self) -> &[(Bytes, Bytes)] {
+        &self.pairs
+    }
+
+    // 至少一对字段和值，字段和值要成对出现
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HSet> {
+        let key = parse.next_byte()?;
+        let mut pairs = vec![(parse.next_byte()?, parse.next_byte()?)];
+        loop {
+            match parse.next_byte() {
+                Ok(field) => pairs.push((field, parse.next_byte()?)),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { key, pairs })
+    }
+
+    // 回复新增的字段数，覆盖已有字段的不算
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hset(self.key, self.pairs) {
+            Ok(added) => Frame::Integer(added as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hset", self.key);
+        for (field, value) in self.pairs {
+            frame.push_bulk(field);
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
+
+impl HGet {
+    pub fn new(key: impl Into<Bytes>, field: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            field: field.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn field(&self) -> &Bytes {
+        &self.field
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HGet> {
+        let key = parse.next_byte()?;
+        let field = parse.next_byte()?;
+
+        Ok(Self { key, field })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hget(&self.key, &[self.field]) {
+            Ok(mut values) => values.pop().flatten().map_or(Frame::Null, Frame::Bulk),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hget", self.key);
+        frame.push_bulk(self.field);
+        frame
+    }
+}
+
+impl HMGet {
+    pub fn new(key: impl Into<Bytes>, fields: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            fields,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn fields(&self) -> &[Bytes] {
+        &self.fields
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HMGet> {
+        let (key, fields) = parse_fields(parse)?;
+        Ok(Self { key, fields })
+    }
+
+    // 按请求的顺序回复，不存在的字段回复Null
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hget(&self.key, &self.fields) {
+            Ok(values) => Frame::Array(values.into_iter().map(|value| value.map_or(Frame::Null, Frame::Bulk)).collect()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_fields_frame("hmget", self.key, self.fields)
+    }
+}
+
+impl HDel {
+    pub fn new(key: impl Into<Bytes>, fields: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            fields,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn fields(&self) -> &[Bytes] {
+        &self.fields
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HDel> {
+        let (key, fields) = parse_fields(parse)?;
+        Ok(Self { key, fields })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hdel(&self.key, &self.fields) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_fields_frame("hdel", self.key, self.fields)
+    }
+}
+
+impl HExists {
+    pub fn new(key: impl Into<Bytes>, field: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            field: field.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn field(&self) -> &Bytes {
+        &self.field
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HExists> {
+        let key = parse.next_byte()?;
+        let field = parse.next_byte()?;
+
+        Ok(Self { key, field })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hget(&self.key, &[self.field]) {
+            Ok(values) => Frame::Integer(values[0].is_some() as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hexists", self.key);
+        frame.push_bulk(self.field);
+        frame
+    }
+}
+
+impl HLen {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HLen> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hlen(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("hlen", self.key)
+    }
+}
+
+impl HKeys {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HKeys> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hgetall(&self.key) {
+            Ok(pairs) => Frame::Array(pairs.into_iter().map(|(field, _)| Frame::Bulk(field)).collect()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("hkeys", self.key)
+    }
+}
+
+impl HVals {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HVals> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hgetall(&self.key) {
+            Ok(pairs) => Frame::Array(pairs.into_iter().map(|(_, value)| Frame::Bulk(value)).collect()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("hvals", self.key)
+    }
+}
+
+impl HGetAll {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HGetAll> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // RESP3下回复Map，RESP2下会展开成字段和值交替的数组
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hgetall(&self.key) {
+            Ok(pairs) => Frame::Map(
+                pairs.into_iter().map(|(field, value)| (Frame::Bulk(field), Frame::Bulk(value))).collect()
+            ),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("hgetall", self.key)
+    }
+}
+
+impl HIncrBy {
+    pub fn new(key: impl Into<Bytes>, field: impl Into<Bytes>, increment: i64) -> Self {
+        Self {
+            key: key.into(),
+            field: field.into(),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn field(&self) -> &Bytes {
+        &self.field
+    }
+
+    pub fn increment(&self) -> i64 {
+        self.increment
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HIncrBy> {
+        let key = parse.next_byte()?;
+        let field = parse.next_byte()?;
+        let increment = parse.next_int()?;
+
+        Ok(Self { key, field, increment })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hincrby(self.key, self.field, self.increment) {
+            Ok(value) => Frame::Integer(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hincrby", self.key);
+        frame.push_bulk(self.field);
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame
+    }
+}
+
+impl HIncrByFloat {
+    pub fn new(key: impl Into<Bytes>, field: impl Into<Bytes>, increment: f64) -> Self {
+        Self {
+            key: key.into(),
+            field: field.into(),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn field(&self) -> &Bytes {
+        &self.field
+    }
+
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HIncrByFloat> {
+        let key = parse.next_byte()?;
+        let field = parse.next_byte()?;
+        let increment = std::str::from_utf8(&parse.next_byte()?)
+            .ok()
+            .and_then(|increment| increment.parse::<f64>().ok())
+            .filter(|increment| !increment.is_nan())
+            .ok_or("ERR value is not a valid float")?;
+
+        Ok(Self { key, field, increment })
+    }
+
+    // 浮点数按字符串回复
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hincrbyfloat(self.key, self.field, self.increment) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hincrbyfloat", self.key);
+        frame.push_bulk(self.field);
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame
+    }
+}
+
+impl HScan {
+    pub fn new(key: impl Into<Bytes>, cursor: u64) -> Self {
+        Self {
+            key: key.into(),
+            cursor,
+            pattern: None,
+            count: None,
+            novalues: false,
+        }
+    }
+
+    // 只返回匹配glob模式的字段
+    pub fn with_pattern(mut self, pattern: impl Into<Bytes>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    // 每次大概遍历多少个字段，默认10个
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    // 只返回字段，不返回值
+    pub fn with_novalues(mut self) -> Self {
+        self.novalues = true;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HScan> {
+        let key = parse.next_byte()?;
+        // 游标是完整的u64，可能会超过i64的范围，不能走next_int
+        let cursor = parse.next_string()?.parse::<u64>().map_err(|_| "ERR invalid cursor")?;
+        let mut hscan = HScan::new(key, cursor);
+
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.eq_ignore_ascii_case("MATCH") => {
+                    hscan.pattern = Some(parse.next_byte()?);
+                }
+                Ok(s) if s.eq_ignore_ascii_case("COUNT") => {
+                    let count = parse.next_uint()?;
+                    if count == 0 {
+                        return Err("ERR syntax error".into());
+                    }
+                    hscan.count = Some(count);
+                }
+                Ok(s) if s.eq_ignore_ascii_case("NOVALUES") => {
+                    hscan.novalues = true;
+                }
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(hscan)
+    }
+
+    // 回复下一次的游标和这次遍历到的字段，游标为0表示遍历完了
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.unwrap_or(10) as usize;
+        match db.hscan(&self.key, self.cursor, count) {
+            Ok((cursor, pairs)) => {
+                let mut items = Frame::array();
+                for (field, value) in pairs {
+                    // MATCH是在遍历之后过滤的，所以一次可能一个都不返回
+                    if let Some(pattern) = &self.pattern {
+                        if !glob::matches(pattern, &field) {
+                            continue;
+                        }
+                    }
+                    items.push_bulk(field);
+                    if !self.novalues {
+                        items.push_bulk(value);
+                    }
+                }
+                Frame::Array(vec![Frame::Bulk(Bytes::from(cursor.to_string())), items])
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("hscan", self.key);
+        frame.push_bulk(Bytes::from(self.cursor.to_string()));
+        if let Some(pattern) = self.pattern {
+            frame.push_bulk(Bytes::from("MATCH".as_bytes()));
+            frame.push_bulk(pattern);
+        }
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if self.novalues {
+            frame.push_bulk(Bytes::from("NOVALUES".as_bytes()));
+        }
+        frame
+    }
+}
+
+// 至少要有一个字段
+fn parse_fields(parse: &mut Parse) -> crate::Result<(Bytes, Vec<Bytes>)> {
+    let key = parse.next_byte()?;
+    let mut fields = vec![parse.next_byte()?];
+    loop {
+        match parse.next_byte() {
+            Ok(field) => fields.push(field),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok((key, fields))
+}
+
+fn make_fields_frame(name: &str, key: Bytes, fields: Vec<Bytes>) -> Frame {
+    let mut frame = make_key_frame(name, key);
+    for field in fields {
+        frame.push_bulk(field);
+    }
+    frame
+}
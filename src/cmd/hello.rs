@@ -0,0 +1,101 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::connection::{Connection, Protocol};
+use crate::frame::Frame;
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    protover: Option<u64>,
+    auth: Option<(String, Bytes)>,
+    setname: Option<String>,
+}
+
+impl Hello {
+    // 只有指定了协议版本才能带AUTH和SETNAME
+    pub fn new(protover: Option<u64>, auth: Option<(String, Bytes)>, setname: Option<impl ToString>) -> Self {
+        Self {
+            protover,
+            auth: protover.and(auth),
+            setname: protover.and(setname.map(|name| name.to_string())),
+        }
+    }
+
+    pub fn protover(&self) -> Option<u64> {
+        self.protover
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hello> {
+        let mut hello = Hello::new(None, None, None::<String>);
+
+        hello.protover = match parse.next_uint() {
+            Ok(protover) => Some(protover),
+            Err(ParseError::EndOfStream) => return Ok(hello),
+            Err(_) => return Err("ERR Protocol version is not an integer or out of range".into()),
+        };
+
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.eq_ignore_ascii_case("AUTH") => {
+                    let username = parse.next_string()?;
+                    let password = parse.next_byte()?;
+                    hello.auth = Some((username, password));
+                }
+                Ok(s) if s.eq_ignore_ascii_case("SETNAME") => {
+                    hello.setname = Some(parse.next_string()?);
+                }
+                Ok(s) => return Err(format!("ERR Syntax error in HELLO option '{}'", s).into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(hello)
+    }
+
+    // 切换当前连接的协议版本，然后用新的协议回复服务端的信息
+    pub(crate) fn execute(self, dst: &mut Connection) -> Frame {
+        let protocol = match self.protover {
+            None => dst.protocol(),
+            Some(2) => Protocol::Resp2,
+            Some(3) => Protocol::Resp3,
+            Some(_) => return Frame::Error("NOPROTO unsupported protocol version".to_string()),
+        };
+        dst.set_protocol(protocol);
+
+        let proto = match protocol {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        };
+        Frame::Map(vec![
+            (bulk("server"), bulk("redis")),
+            (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+            (bulk("proto"), Frame::Integer(proto)),
+            (bulk("mode"), bulk("standalone")),
+            (bulk("role"), bulk("master")),
+            (bulk("modules"), Frame::Array(vec![])),
+        ])
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hello".as_bytes()));
+        if let Some(protover) = self.protover {
+            frame.push_bulk(Bytes::from(protover.to_string()));
+            if let Some((username, password)) = self.auth {
+                frame.push_bulk(Bytes::from("AUTH".as_bytes()));
+                frame.push_bulk(Bytes::from(username.into_bytes()));
+                frame.push_bulk(password);
+            }
+            if let Some(setname) = self.setname {
+                frame.push_bulk(Bytes::from("SETNAME".as_bytes()));
+                frame.push_bulk(Bytes::from(setname.into_bytes()));
+            }
+        }
+        frame
+    }
+}
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
@@ -0,0 +1,484 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::frame::Frame;
+use crate::glob;
+use crate::cmd::select::parse_db_index;
+use crate::cmd::make_keys_frame;
+
+// DEL key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Del {
+    keys: Vec<Bytes>,
+}
+
+// UNLINK key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unlink {
+    keys: Vec<Bytes>,
+}
+
+// EXISTS key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exists {
+    keys: Vec<Bytes>,
+}
+
+// TOUCH key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Touch {
+    keys: Vec<Bytes>,
+}
+
+// TYPE key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {
+    key: Bytes,
+}
+
+// RENAME key newkey
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    key: Bytes,
+    destination: Bytes,
+}
+
+// RENAMENX key newkey
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenameNx {
+    key: Bytes,
+    destination: Bytes,
+}
+
+// COPY source destination [DB destination-db] [REPLACE]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Copy {
+    source: Bytes,
+    destination: Bytes,
+    db: Option<usize>,
+    replace: bool,
+}
+
+// KEYS pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keys {
+    pattern: Bytes,
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: Option<u64>,
+    key_type: Option<String>,
+}
+
+impl Del {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Del> {
+        Ok(Self { keys: parse_keys(parse)? })
+    }
+
+    // 回复删掉的key的个数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.del(&self.keys) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("del", self.keys)
+    }
+}
+
+impl Unlink {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Unlink> {
+        Ok(Self { keys: parse_keys(parse)? })
+    }
+
+    // 回复删掉的key的个数，值在后台释放
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.unlink(&self.keys) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("unlink", self.keys)
+    }
+}
+
+impl Exists {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Exists> {
+        Ok(Self { keys: parse_keys(parse)? })
+    }
+
+    // 回复存在的key的个数，同一个key出现多次就算多次
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.exists(&self.keys) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("exists", self.keys)
+    }
+}
+
+impl Touch {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Touch> {
+        Ok(Self { keys: parse_keys(parse)? })
+    }
+
+    // 没有记录访问时间，和EXISTS一样回复存在的key的个数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.exists(&self.keys) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("touch", self.keys)
+    }
+}
+
+impl Type {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Type> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Simple(db.key_type(&self.key).to_string())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("type", vec![self.key])
+    }
+}
+
+impl Rename {
+    pub fn new(key: impl Into<Bytes>, destination: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            destination: destination.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Rename> {
+        let key = parse.next_byte()?;
+        let destination = parse.next_byte()?;
+
+        Ok(Self { key, destination })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.rename(&self.key, self.destination, false) {
+            Ok(_) => Frame::Simple("OK".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("rename", vec![self.key, self.destination])
+    }
+}
+
+impl RenameNx {
+    pub fn new(key: impl Into<Bytes>, destination: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            destination: destination.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<RenameNx> {
+        let key = parse.next_byte()?;
+        let destination = parse.next_byte()?;
+
+        Ok(Self { key, destination })
+    }
+
+    // 改名了回复1，newkey已经存在回复0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.rename(&self.key, self.destination, true) {
+            Ok(renamed) => Frame::Integer(renamed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("renamenx", vec![self.key, self.destination])
+    }
+}
+
+impl Copy {
+    pub fn new(source: impl Into<Bytes>, destination: impl Into<Bytes>) -> Self {
+        Self {
+            source: source.into(),
+            destination: destination.into(),
+            db: None,
+            replace: false,
+        }
+    }
+
+    // 复制到另一个库里，默认是当前选中的库
+    pub fn with_db(mut self, db: usize) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    // destination已经存在的时候覆盖掉
+    pub fn with_replace(mut self) -> Self {
+        self.replace = true;
+        self
+    }
+
+    pub fn source(&self) -> &Bytes {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub fn db(&self) -> Option<usize> {
+        self.db
+    }
+
+    pub fn replace(&self) -> bool {
+        self.replace
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Copy> {
+        let source = parse.next_byte()?;
+        let destination = parse.next_byte()?;
+        let mut copy = Copy::new(source, destination);
+
+        loop {
+            match parse.next_string() {
+                Ok(option) if option.eq_ignore_ascii_case("REPLACE") => copy.replace = true,
+                Ok(option) if option.eq_ignore_ascii_case("DB") => copy.db = Some(parse_db_index(parse)?),
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(copy)
+    }
+
+    // 复制了回复1，source不存在或者destination已经存在回复0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.copy(&self.source, self.destination, self.db, self.replace) {
+            Ok(copied) => Frame::Integer(copied as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_keys_frame("copy", vec![self.source, self.destination]);
+        if let Some(db) = self.db {
+            frame.push_bulk(Bytes::from("DB".as_bytes()));
+            frame.push_bulk(Bytes::from(db.to_string()));
+        }
+        if self.replace {
+            frame.push_bulk(Bytes::from("REPLACE".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl Keys {
+    pub fn new(pattern: impl Into<Bytes>) -> Self {
+        Self {
+            pattern: pattern.into(),
+        }
+    }
+
+    pub fn pattern(&self) -> &Bytes {
+        &self.pattern
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Keys> {
+        let pattern = parse.next_byte()?;
+
+        Ok(Self { pattern })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let mut response = Frame::array();
+        for key in db.keys(&self.pattern) {
+            response.push_bulk(key);
+        }
+
+        response
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("keys", vec![self.pattern])
+    }
+}
+
+impl Scan {
+    pub fn new(cursor: u64) -> Self {
+        Self {
+            cursor,
+            pattern: None,
+            count: None,
+            key_type: None,
+        }
+    }
+
+    // 只返回匹配glob模式的key
+    pub fn with_pattern(mut self, pattern: impl Into<Bytes>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    // 每次大概遍历多少个key，默认10个
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    // 只返回这个类型的key，类型名和TYPE命令回复的一样
+    pub fn with_type(mut self, key_type: impl Into<String>) -> Self {
+        self.key_type = Some(key_type.into());
+        self
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Scan> {
+        // 游标是完整的u64，可能会超过i64的范围，不能走next_int
+        let cursor = parse.next_string()?.parse::<u64>().map_err(|_| "ERR invalid cursor")?;
+        let mut scan = Scan::new(cursor);
+
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.eq_ignore_ascii_case("MATCH") => {
+                    scan.pattern = Some(parse.next_byte()?);
+                }
+                Ok(s) if s.eq_ignore_ascii_case("COUNT") => {
+                    let count = parse.next_uint()?;
+                    if count == 0 {
+                        return Err("ERR syntax error".into());
+                    }
+                    scan.count = Some(count);
+                }
+                Ok(s) if s.eq_ignore_ascii_case("TYPE") => {
+                    scan.key_type = Some(parse.next_string()?);
+                }
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(scan)
+    }
+
+    // 回复下一次的游标和这次遍历到的key，游标为0表示遍历完了。
+    // 整个遍历过程中一直存在的key至少会返回一次，中间有增删的key可能返回也可能不返回
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.unwrap_or(10) as usize;
+        let (cursor, found) = db.scan(self.cursor, count);
+
+        let mut keys = Frame::array();
+        for (key, key_type) in found {
+            // MATCH和TYPE都是在遍历之后过滤的，所以一次可能一个都不返回
+            if self.pattern.as_ref().is_some_and(|pattern| !glob::matches(pattern, &key)) {
+                continue;
+            }
+            if self.key_type.as_ref().is_some_and(|expected| !expected.eq_ignore_ascii_case(key_type)) {
+                continue;
+            }
+            keys.push_bulk(key);
+        }
+        Frame::Array(vec![Frame::Bulk(Bytes::from(cursor.to_string())), keys])
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_keys_frame("scan", vec![Bytes::from(self.cursor.to_string())]);
+        if let Some(pattern) = self.pattern {
+            frame.push_bulk(Bytes::from("MATCH".as_bytes()));
+            frame.push_bulk(pattern);
+        }
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if let Some(key_type) = self.key_type {
+            frame.push_bulk(Bytes::from("TYPE".as_bytes()));
+            frame.push_bulk(Bytes::from(key_type));
+        }
+        frame
+    }
+}
+
+// 至少要有一个key
+pub(crate) fn parse_keys(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    let mut keys = vec![parse.next_byte()?];
+    loop {
+        match parse.next_byte() {
+            Ok(key) => keys.push(key),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(keys)
+}
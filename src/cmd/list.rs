@@ -0,0 +1,302 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::frame::Frame;
+
+// LPUSH key element [element ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LPush {
+    key: Bytes,
+    elements: Vec<Bytes>,
+}
+
+// RPUSH key element [element ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPush {
+    key: Bytes,
+    elements: Vec<Bytes>,
+}
+
+// LPOP key [count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LPop {
+    key: Bytes,
+    count: Option<u64>,
+}
+
+// RPOP key [count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPop {
+    key: Bytes,
+    count: Option<u64>,
+}
+
+// LRANGE key start stop
+#[derive(Debug, Clone, PartialEq)]
+pub struct LRange {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+}
+
+// LLEN key
+#[derive(Debug, Clone, PartialEq)]
+pub struct LLen {
+    key: Bytes,
+}
+
+impl LPush {
+    pub fn new(key: impl Into<Bytes>, elements: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            elements,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn elements(&self) -> &[Bytes] {
+        &self.elements
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LPush> {
+        let (key, elements) = parse_push(parse)?;
+        Ok(Self { key, elements })
+    }
+
+    // 依次插到头部，所以最后一个元素会在最前面
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_push(db, self.key, self.elements, true)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_push_frame("lpush", self.key, self.elements)
+    }
+}
+
+impl RPush {
+    pub fn new(key: impl Into<Bytes>, elements: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            elements,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn elements(&self) -> &[Bytes] {
+        &self.elements
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<RPush> {
+        let (key, elements) = parse_push(parse)?;
+        Ok(Self { key, elements })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_push(db, self.key, self.elements, false)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_push_frame("rpush", self.key, self.elements)
+    }
+}
+
+impl LPop {
+    pub fn new(key: impl Into<Bytes>, count: Option<u64>) -> Self {
+        Self {
+            key: key.into(),
+            count,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LPop> {
+        let (key, count) = parse_pop(parse)?;
+        Ok(Self { key, count })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_pop(db, self.key, self.count, true)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pop_frame("lpop", self.key, self.count)
+    }
+}
+
+impl RPop {
+    pub fn new(key: impl Into<Bytes>, count: Option<u64>) -> Self {
+        Self {
+            key: key.into(),
+            count,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<RPop> {
+        let (key, count) = parse_pop(parse)?;
+        Ok(Self { key, count })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_pop(db, self.key, self.count, false)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pop_frame("rpop", self.key, self.count)
+    }
+}
+
+impl LRange {
+    pub fn new(key: impl Into<Bytes>, start: i64, stop: i64) -> Self {
+        Self {
+            key: key.into(),
+            start,
+            stop,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LRange> {
+        let key = parse.next_byte()?;
+        let start = parse.next_int()?;
+        let stop = parse.next_int()?;
+
+        Ok(Self { key, start, stop })
+    }
+
+    // 下标越界不会报错，只会返回空列表或者截断
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.lrange(&self.key, self.start, self.stop) {
+            Ok(elements) => Frame::Array(elements.into_iter().map(Frame::Bulk).collect()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("lrange".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.stop.to_string()));
+        frame
+    }
+}
+
+impl LLen {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LLen> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.llen(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("llen".as_bytes()));
+        frame.push_bulk(self.key);
+        frame
+    }
+}
+
+// 至少要有一个元素
+fn parse_push(parse: &mut Parse) -> crate::Result<(Bytes, Vec<Bytes>)> {
+    let key = parse.next_byte()?;
+    let mut elements = vec![parse.next_byte()?];
+    loop {
+        match parse.next_byte() {
+            Ok(element) => elements.push(element),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok((key, elements))
+}
+
+fn parse_pop(parse: &mut Parse) -> crate::Result<(Bytes, Option<u64>)> {
+    let key = parse.next_byte()?;
+    let count = match parse.next_uint() {
+        Ok(count) => Some(count),
+        Err(ParseError::EndOfStream) => None,
+        Err(_) => return Err("ERR value is out of range, must be positive".into()),
+    };
+    Ok((key, count))
+}
+
+// 回复插入之后列表的长度
+fn execute_push(db: &Db, key: Bytes, elements: Vec<Bytes>, left: bool) -> Frame {
+    match db.push(key, elements, left) {
+        Ok(len) => Frame::Integer(len as i64),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+// 不带count回复单个元素，带了count回复数组，key不存在都回复Null
+fn execute_pop(db: &Db, key: Bytes, count: Option<u64>, left: bool) -> Frame {
+    match db.pop(&key, count.unwrap_or(1) as usize, left) {
+        Ok(Some(mut elements)) => match count {
+            Some(_) => Frame::Array(elements.into_iter().map(Frame::Bulk).collect()),
+            None => elements.pop().map_or(Frame::Null, Frame::Bulk),
+        },
+        Ok(None) => Frame::Null,
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+fn make_push_frame(name: &str, key: Bytes, elements: Vec<Bytes>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    frame.push_bulk(key);
+    for element in elements {
+        frame.push_bulk(element);
+    }
+    frame
+}
+
+fn make_pop_frame(name: &str, key: Bytes, count: Option<u64>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    frame.push_bulk(key);
+    if let Some(count) = count {
+        frame.push_bulk(Bytes::from(count.to_string()));
+    }
+    frame
+}
@@ -2,47 +2,725 @@ mod get;
 
 pub use get::Get;
 
-mod unknown;
 mod set;
 
+pub use set::{Set, SetCondition};
+
+mod publish;
+
+pub use publish::Publish;
+
+mod subscribe;
+
+pub use subscribe::{Subscribe, Unsubscribe, PSubscribe, PUnsubscribe};
+
+mod pubsub;
+
+pub use pubsub::PubSub;
+
+mod hello;
+
+pub use hello::Hello;
+
+mod list;
+
+pub use list::{LPush, RPush, LPop, RPop, LRange, LLen};
+
+mod blocking;
+
+pub use blocking::{BLPop, BRPop, BLMove, Direction};
+
+mod hash;
+
+pub use hash::{HSet, HGet, HMGet, HDel, HExists, HLen, HKeys, HVals, HGetAll, HIncrBy, HIncrByFloat, HScan};
+
+mod sets;
+
+pub use sets::{SAdd, SRem, SIsMember, SMembers, SCard, SInter, SUnion, SDiff, SInterStore, SUnionStore, SDiffStore};
+
+mod zset;
+
+pub use zset::{ZAdd, ZIncrBy, ZRem, ZScore, ZCard, ZRank, ZRange, ZRangeByScore};
+
+pub use crate::zset::{ScoreBound, LexBound, RangeBy, ZAddOptions};
+
+mod stream;
+
+pub use stream::{XAdd, XRange, XRead, XLen, XTrim};
+
+mod group;
+
+pub use group::{XGroup, XReadGroup, XAck, XPending, XClaim, XAutoClaim, XInfo};
+
+pub use crate::stream::{StreamId, XAddId, TrimStrategy, TrimOptions, ClaimOptions, PendingRange};
+
+mod string;
+
+pub use string::{Append, GetRange, SetRange, StrLen, GetDel, GetEx, MGet, MSet, MSetNx, Incr, Decr, IncrBy, DecrBy, IncrByFloat, Expiry};
+
+mod expire;
+
+pub use expire::{Expire, PExpire, ExpireAt, PExpireAt, Ttl, PTtl, Persist, ExpireTime, PExpireTime};
+
+pub use crate::db::ExpireOptions;
+
+mod keys;
+
+pub use keys::{Del, Unlink, Exists, Touch, Type, Rename, RenameNx, Copy, Keys, Scan};
+
+mod select;
+
+pub use select::{Select, SwapDb, Move, FlushDb, FlushAll};
+
+mod multi;
+
+pub use multi::{Multi, Exec, Discard, Watch, Unwatch};
+
+mod script;
+
+pub use script::{Eval, EvalSha, Script};
+
+mod unknown;
+
 pub use unknown::Unknown;
 
+use bytes::Bytes;
 use crate::frame::Frame;
 use crate::db::Db;
 use crate::connection::Connection;
 use crate::shutdown::Shutdown;
-use crate::parse::Parse;
-use crate::cmd::set::Set;
+use crate::parse::{Parse, ParseError};
+use tracing::debug;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     Get(Get),
     Set(Set),
+    Publish(Publish),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    PubSub(PubSub),
+    Hello(Hello),
+    LPush(LPush),
+    RPush(RPush),
+    LPop(LPop),
+    RPop(RPop),
+    LRange(LRange),
+    LLen(LLen),
+    BLPop(BLPop),
+    BRPop(BRPop),
+    BLMove(BLMove),
+    HSet(HSet),
+    HGet(HGet),
+    HMGet(HMGet),
+    HDel(HDel),
+    HExists(HExists),
+    HLen(HLen),
+    HKeys(HKeys),
+    HVals(HVals),
+    HGetAll(HGetAll),
+    HIncrBy(HIncrBy),
+    HIncrByFloat(HIncrByFloat),
+    HScan(HScan),
+    SAdd(SAdd),
+    SRem(SRem),
+    SIsMember(SIsMember),
+    SMembers(SMembers),
+    SCard(SCard),
+    SInter(SInter),
+    SUnion(SUnion),
+    SDiff(SDiff),
+    SInterStore(SInterStore),
+    SUnionStore(SUnionStore),
+    SDiffStore(SDiffStore),
+    ZAdd(ZAdd),
+    ZIncrBy(ZIncrBy),
+    ZRem(ZRem),
+    ZScore(ZScore),
+    ZCard(ZCard),
+    ZRank(ZRank),
+    ZRange(ZRange),
+    ZRangeByScore(ZRangeByScore),
+    XAdd(XAdd),
+    XRange(XRange),
+    XRead(XRead),
+    XLen(XLen),
+    XTrim(XTrim),
+    XGroup(XGroup),
+    XReadGroup(XReadGroup),
+    XAck(XAck),
+    XPending(XPending),
+    XClaim(XClaim),
+    XAutoClaim(XAutoClaim),
+    XInfo(XInfo),
+    Append(Append),
+    GetRange(GetRange),
+    SetRange(SetRange),
+    StrLen(StrLen),
+    GetDel(GetDel),
+    GetEx(GetEx),
+    MGet(MGet),
+    MSet(MSet),
+    MSetNx(MSetNx),
+    Incr(Incr),
+    Decr(Decr),
+    IncrBy(IncrBy),
+    DecrBy(DecrBy),
+    IncrByFloat(IncrByFloat),
+    Expire(Expire),
+    PExpire(PExpire),
+    ExpireAt(ExpireAt),
+    PExpireAt(PExpireAt),
+    Ttl(Ttl),
+    PTtl(PTtl),
+    Persist(Persist),
+    ExpireTime(ExpireTime),
+    PExpireTime(PExpireTime),
+    Del(Del),
+    Unlink(Unlink),
+    Exists(Exists),
+    Touch(Touch),
+    Type(Type),
+    Rename(Rename),
+    RenameNx(RenameNx),
+    Copy(Copy),
+    Keys(Keys),
+    Scan(Scan),
+    Select(Select),
+    SwapDb(SwapDb),
+    Move(Move),
+    FlushDb(FlushDb),
+    FlushAll(FlushAll),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Eval(Eval),
+    EvalSha(EvalSha),
+    Script(Script),
     Unknown(Unknown),
 }
 
 impl Command {
+    // 解析失败返回的错误都是可以直接回给客户端的错误信息
     pub fn from_frame(frame: Frame) -> crate::Result<Command> {
         let mut parse = Parse::new(frame)?; // 这里一定传Frame::Array
-        let command_name = parse.next_string()?.to_lowercase();// 转成小写
-        let command = match &command_name[..] {
-            "get" => Command::Get(Get::parse_frames(&mut parse)?),
-            "set" => Command::Set(Set::parse_frames(&mut parse)?),
+        let command_name = parse.next_string()?;
+        Command::parse_command(&command_name, &mut parse)
+            .map_err(|err| command_error(&command_name, err))
+    }
+
+    fn parse_command(command_name: &str, parse: &mut Parse) -> crate::Result<Command> {
+        let command = match &command_name.to_lowercase()[..] { // 转成小写
+            "get" => Command::Get(Get::parse_frames(parse)?),
+            "set" => Command::Set(Set::parse_frames(parse)?),
+            "publish" => Command::Publish(Publish::parse_frames(parse)?),
+            "subscribe" => Command::Subscribe(Subscribe::parse_frames(parse)?),
+            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(parse)?),
+            "psubscribe" => Command::PSubscribe(PSubscribe::parse_frames(parse)?),
+            "punsubscribe" => Command::PUnsubscribe(PUnsubscribe::parse_frames(parse)?),
+            "pubsub" => Command::PubSub(PubSub::parse_frames(parse)?),
+            "hello" => Command::Hello(Hello::parse_frames(parse)?),
+            "lpush" => Command::LPush(LPush::parse_frames(parse)?),
+            "rpush" => Command::RPush(RPush::parse_frames(parse)?),
+            "lpop" => Command::LPop(LPop::parse_frames(parse)?),
+            "rpop" => Command::RPop(RPop::parse_frames(parse)?),
+            "lrange" => Command::LRange(LRange::parse_frames(parse)?),
+            "llen" => Command::LLen(LLen::parse_frames(parse)?),
+            "blpop" => Command::BLPop(BLPop::parse_frames(parse)?),
+            "brpop" => Command::BRPop(BRPop::parse_frames(parse)?),
+            "blmove" => Command::BLMove(BLMove::parse_frames(parse)?),
+            "hset" => Command::HSet(HSet::parse_frames(parse)?),
+            "hget" => Command::HGet(HGet::parse_frames(parse)?),
+            "hmget" => Command::HMGet(HMGet::parse_frames(parse)?),
+            "hdel" => Command::HDel(HDel::parse_frames(parse)?),
+            "hexists" => Command::HExists(HExists::parse_frames(parse)?),
+            "hlen" => Command::HLen(HLen::parse_frames(parse)?),
+            "hkeys" => Command::HKeys(HKeys::parse_frames(parse)?),
+            "hvals" => Command::HVals(HVals::parse_frames(parse)?),
+            "hgetall" => Command::HGetAll(HGetAll::parse_frames(parse)?),
+            "hincrby" => Command::HIncrBy(HIncrBy::parse_frames(parse)?),
+            "hincrbyfloat" => Command::HIncrByFloat(HIncrByFloat::parse_frames(parse)?),
+            "hscan" => Command::HScan(HScan::parse_frames(parse)?),
+            "sadd" => Command::SAdd(SAdd::parse_frames(parse)?),
+            "srem" => Command::SRem(SRem::parse_frames(parse)?),
+            "sismember" => Command::SIsMember(SIsMember::parse_frames(parse)?),
+            "smembers" => Command::SMembers(SMembers::parse_frames(parse)?),
+            "scard" => Command::SCard(SCard::parse_frames(parse)?),
+            "sinter" => Command::SInter(SInter::parse_frames(parse)?),
+            "sunion" => Command::SUnion(SUnion::parse_frames(parse)?),
+            "sdiff" => Command::SDiff(SDiff::parse_frames(parse)?),
+            "sinterstore" => Command::SInterStore(SInterStore::parse_frames(parse)?),
+            "sunionstore" => Command::SUnionStore(SUnionStore::parse_frames(parse)?),
+            "sdiffstore" => Command::SDiffStore(SDiffStore::parse_frames(parse)?),
+            "zadd" => Command::ZAdd(ZAdd::parse_frames(parse)?),
+            "zincrby" => Command::ZIncrBy(ZIncrBy::parse_frames(parse)?),
+            "zrem" => Command::ZRem(ZRem::parse_frames(parse)?),
+            "zscore" => Command::ZScore(ZScore::parse_frames(parse)?),
+            "zcard" => Command::ZCard(ZCard::parse_frames(parse)?),
+            "zrank" => Command::ZRank(ZRank::parse_frames(parse)?),
+            "zrange" => Command::ZRange(ZRange::parse_frames(parse)?),
+            "zrangebyscore" => Command::ZRangeByScore(ZRangeByScore::parse_frames(parse)?),
+            "xadd" => Command::XAdd(XAdd::parse_frames(parse)?),
+            "xrange" => Command::XRange(XRange::parse_frames(parse)?),
+            "xread" => Command::XRead(XRead::parse_frames(parse)?),
+            "xlen" => Command::XLen(XLen::parse_frames(parse)?),
+            "xtrim" => Command::XTrim(XTrim::parse_frames(parse)?),
+            "xgroup" => Command::XGroup(XGroup::parse_frames(parse)?),
+            "xreadgroup" => Command::XReadGroup(XReadGroup::parse_frames(parse)?),
+            "xack" => Command::XAck(XAck::parse_frames(parse)?),
+            "xpending" => Command::XPending(XPending::parse_frames(parse)?),
+            "xclaim" => Command::XClaim(XClaim::parse_frames(parse)?),
+            "xautoclaim" => Command::XAutoClaim(XAutoClaim::parse_frames(parse)?),
+            "xinfo" => Command::XInfo(XInfo::parse_frames(parse)?),
+            "append" => Command::Append(Append::parse_frames(parse)?),
+            "getrange" => Command::GetRange(GetRange::parse_frames(parse)?),
+            "setrange" => Command::SetRange(SetRange::parse_frames(parse)?),
+            "strlen" => Command::StrLen(StrLen::parse_frames(parse)?),
+            "getdel" => Command::GetDel(GetDel::parse_frames(parse)?),
+            "getex" => Command::GetEx(GetEx::parse_frames(parse)?),
+            "mget" => Command::MGet(MGet::parse_frames(parse)?),
+            "mset" => Command::MSet(MSet::parse_frames(parse)?),
+            "msetnx" => Command::MSetNx(MSetNx::parse_frames(parse)?),
+            "incr" => Command::Incr(Incr::parse_frames(parse)?),
+            "decr" => Command::Decr(Decr::parse_frames(parse)?),
+            "incrby" => Command::IncrBy(IncrBy::parse_frames(parse)?),
+            "decrby" => Command::DecrBy(DecrBy::parse_frames(parse)?),
+            "incrbyfloat" => Command::IncrByFloat(IncrByFloat::parse_frames(parse)?),
+            "expire" => Command::Expire(Expire::parse_frames(parse)?),
+            "pexpire" => Command::PExpire(PExpire::parse_frames(parse)?),
+            "expireat" => Command::ExpireAt(ExpireAt::parse_frames(parse)?),
+            "pexpireat" => Command::PExpireAt(PExpireAt::parse_frames(parse)?),
+            "ttl" => Command::Ttl(Ttl::parse_frames(parse)?),
+            "pttl" => Command::PTtl(PTtl::parse_frames(parse)?),
+            "persist" => Command::Persist(Persist::parse_frames(parse)?),
+            "expiretime" => Command::ExpireTime(ExpireTime::parse_frames(parse)?),
+            "pexpiretime" => Command::PExpireTime(PExpireTime::parse_frames(parse)?),
+            "del" => Command::Del(Del::parse_frames(parse)?),
+            "unlink" => Command::Unlink(Unlink::parse_frames(parse)?),
+            "exists" => Command::Exists(Exists::parse_frames(parse)?),
+            "touch" => Command::Touch(Touch::parse_frames(parse)?),
+            "type" => Command::Type(Type::parse_frames(parse)?),
+            "rename" => Command::Rename(Rename::parse_frames(parse)?),
+            "renamenx" => Command::RenameNx(RenameNx::parse_frames(parse)?),
+            "copy" => Command::Copy(Copy::parse_frames(parse)?),
+            "keys" => Command::Keys(Keys::parse_frames(parse)?),
+            "scan" => Command::Scan(Scan::parse_frames(parse)?),
+            "select" => Command::Select(Select::parse_frames(parse)?),
+            "swapdb" => Command::SwapDb(SwapDb::parse_frames(parse)?),
+            "move" => Command::Move(Move::parse_frames(parse)?),
+            "flushdb" => Command::FlushDb(FlushDb::parse_frames(parse)?),
+            "flushall" => Command::FlushAll(FlushAll::parse_frames(parse)?),
+            "multi" => Command::Multi(Multi::parse_frames(parse)?),
+            "exec" => Command::Exec(Exec::parse_frames(parse)?),
+            "discard" => Command::Discard(Discard::parse_frames(parse)?),
+            "watch" => Command::Watch(Watch::parse_frames(parse)?),
+            "unwatch" => Command::Unwatch(Unwatch::parse_frames(parse)?),
+            "eval" => Command::Eval(Eval::parse_frames(parse)?),
+            "evalsha" => Command::EvalSha(EvalSha::parse_frames(parse)?),
+            "script" => Command::Script(Script::parse_frames(parse)?),
             _ => {
                 return Ok(Command::Unknown(Unknown::new(command_name)));
             }
         };
-        parse.finish()?; // 解析结束了
+        // 解析结束了还有多余的参数
+        if parse.finish().is_err() {
+            return Err(wrong_arity(command_name));
+        }
         Ok(command)
     }
 
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
-        use Command::*;
+    // 和from_frame相反，把命令转回成frame
+    pub fn into_frame(self) -> Frame {
         match self {
-            Get(cmd) => cmd.apply(db, dst).await?,
-            Set(cmd) => cmd.apply(db, dst).await?,
-            _ => {}
+            Command::Get(cmd) => cmd.into_frame(),
+            Command::Set(cmd) => cmd.into_frame(),
+            Command::Publish(cmd) => cmd.into_frame(),
+            Command::Subscribe(cmd) => cmd.into_frame(),
+            Command::Unsubscribe(cmd) => cmd.into_frame(),
+            Command::PSubscribe(cmd) => cmd.into_frame(),
+            Command::PUnsubscribe(cmd) => cmd.into_frame(),
+            Command::PubSub(cmd) => cmd.into_frame(),
+            Command::Hello(cmd) => cmd.into_frame(),
+            Command::LPush(cmd) => cmd.into_frame(),
+            Command::RPush(cmd) => cmd.into_frame(),
+            Command::LPop(cmd) => cmd.into_frame(),
+            Command::RPop(cmd) => cmd.into_frame(),
+            Command::LRange(cmd) => cmd.into_frame(),
+            Command::LLen(cmd) => cmd.into_frame(),
+            Command::BLPop(cmd) => cmd.into_frame(),
+            Command::BRPop(cmd) => cmd.into_frame(),
+            Command::BLMove(cmd) => cmd.into_frame(),
+            Command::HSet(cmd) => cmd.into_frame(),
+            Command::HGet(cmd) => cmd.into_frame(),
+            Command::HMGet(cmd) => cmd.into_frame(),
+            Command::HDel(cmd) => cmd.into_frame(),
+            Command::HExists(cmd) => cmd.into_frame(),
+            Command::HLen(cmd) => cmd.into_frame(),
+            Command::HKeys(cmd) => cmd.into_frame(),
+            Command::HVals(cmd) => cmd.into_frame(),
+            Command::HGetAll(cmd) => cmd.into_frame(),
+            Command::HIncrBy(cmd) => cmd.into_frame(),
+            Command::HIncrByFloat(cmd) => cmd.into_frame(),
+            Command::HScan(cmd) => cmd.into_frame(),
+            Command::SAdd(cmd) => cmd.into_frame(),
+            Command::SRem(cmd) => cmd.into_frame(),
+            Command::SIsMember(cmd) => cmd.into_frame(),
+            Command::SMembers(cmd) => cmd.into_frame(),
+            Command::SCard(cmd) => cmd.into_frame(),
+            Command::SInter(cmd) => cmd.into_frame(),
+            Command::SUnion(cmd) => cmd.into_frame(),
+            Command::SDiff(cmd) => cmd.into_frame(),
+            Command::SInterStore(cmd) => cmd.into_frame(),
+            Command::SUnionStore(cmd) => cmd.into_frame(),
+            Command::SDiffStore(cmd) => cmd.into_frame(),
+            Command::ZAdd(cmd) => cmd.into_frame(),
+            Command::ZIncrBy(cmd) => cmd.into_frame(),
+            Command::ZRem(cmd) => cmd.into_frame(),
+            Command::ZScore(cmd) => cmd.into_frame(),
+            Command::ZCard(cmd) => cmd.into_frame(),
+            Command::ZRank(cmd) => cmd.into_frame(),
+            Command::ZRange(cmd) => cmd.into_frame(),
+            Command::ZRangeByScore(cmd) => cmd.into_frame(),
+            Command::XAdd(cmd) => cmd.into_frame(),
+            Command::XRange(cmd) => cmd.into_frame(),
+            Command::XRead(cmd) => cmd.into_frame(),
+            Command::XLen(cmd) => cmd.into_frame(),
+            Command::XTrim(cmd) => cmd.into_frame(),
+            Command::XGroup(cmd) => cmd.into_frame(),
+            Command::XReadGroup(cmd) => cmd.into_frame(),
+            Command::XAck(cmd) => cmd.into_frame(),
+            Command::XPending(cmd) => cmd.into_frame(),
+            Command::XClaim(cmd) => cmd.into_frame(),
+            Command::XAutoClaim(cmd) => cmd.into_frame(),
+            Command::XInfo(cmd) => cmd.into_frame(),
+            Command::Append(cmd) => cmd.into_frame(),
+            Command::GetRange(cmd) => cmd.into_frame(),
+            Command::SetRange(cmd) => cmd.into_frame(),
+            Command::StrLen(cmd) => cmd.into_frame(),
+            Command::GetDel(cmd) => cmd.into_frame(),
+            Command::GetEx(cmd) => cmd.into_frame(),
+            Command::MGet(cmd) => cmd.into_frame(),
+            Command::MSet(cmd) => cmd.into_frame(),
+            Command::MSetNx(cmd) => cmd.into_frame(),
+            Command::Incr(cmd) => cmd.into_frame(),
+            Command::Decr(cmd) => cmd.into_frame(),
+            Command::IncrBy(cmd) => cmd.into_frame(),
+            Command::DecrBy(cmd) => cmd.into_frame(),
+            Command::IncrByFloat(cmd) => cmd.into_frame(),
+            Command::Expire(cmd) => cmd.into_frame(),
+            Command::PExpire(cmd) => cmd.into_frame(),
+            Command::ExpireAt(cmd) => cmd.into_frame(),
+            Command::PExpireAt(cmd) => cmd.into_frame(),
+            Command::Ttl(cmd) => cmd.into_frame(),
+            Command::PTtl(cmd) => cmd.into_frame(),
+            Command::Persist(cmd) => cmd.into_frame(),
+            Command::ExpireTime(cmd) => cmd.into_frame(),
+            Command::PExpireTime(cmd) => cmd.into_frame(),
+            Command::Del(cmd) => cmd.into_frame(),
+            Command::Unlink(cmd) => cmd.into_frame(),
+            Command::Exists(cmd) => cmd.into_frame(),
+            Command::Touch(cmd) => cmd.into_frame(),
+            Command::Type(cmd) => cmd.into_frame(),
+            Command::Rename(cmd) => cmd.into_frame(),
+            Command::RenameNx(cmd) => cmd.into_frame(),
+            Command::Copy(cmd) => cmd.into_frame(),
+            Command::Keys(cmd) => cmd.into_frame(),
+            Command::Scan(cmd) => cmd.into_frame(),
+            Command::Select(cmd) => cmd.into_frame(),
+            Command::SwapDb(cmd) => cmd.into_frame(),
+            Command::Move(cmd) => cmd.into_frame(),
+            Command::FlushDb(cmd) => cmd.into_frame(),
+            Command::FlushAll(cmd) => cmd.into_frame(),
+            Command::Multi(cmd) => cmd.into_frame(),
+            Command::Exec(cmd) => cmd.into_frame(),
+            Command::Discard(cmd) => cmd.into_frame(),
+            Command::Watch(cmd) => cmd.into_frame(),
+            Command::Unwatch(cmd) => cmd.into_frame(),
+            Command::Eval(cmd) => cmd.into_frame(),
+            Command::EvalSha(cmd) => cmd.into_frame(),
+            Command::Script(cmd) => cmd.into_frame(),
+            Command::Unknown(cmd) => cmd.into_frame(),
         }
+    }
+
+    // 订阅、阻塞的命令和脚本要等待，其他命令同步执行完再把回复写给客户端
+    pub(crate) async fn apply(self, db: &mut Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        use Command::*;
+        let response = match self {
+            Subscribe(cmd) => return cmd.apply(db, dst, shutdown).await,
+            Unsubscribe(cmd) => return cmd.apply(dst).await,
+            PSubscribe(cmd) => return cmd.apply(db, dst, shutdown).await,
+            PUnsubscribe(cmd) => return cmd.apply(dst).await,
+            BLPop(cmd) => return cmd.apply(db, dst, shutdown).await,
+            BRPop(cmd) => return cmd.apply(db, dst, shutdown).await,
+            BLMove(cmd) => return cmd.apply(db, dst, shutdown).await,
+            XRead(cmd) => return cmd.apply(db, dst, shutdown).await,
+            XReadGroup(cmd) => return cmd.apply(db, dst, shutdown).await,
+            Eval(cmd) => cmd.execute(db, dst).await,
+            EvalSha(cmd) => cmd.execute(db, dst).await,
+            cmd => cmd.execute(db, dst),
+        };
+
+        debug!(?response);
+        dst.write_frame(&response).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // 同步执行命令，直接返回回复。事务和脚本里的命令都走这里，阻塞的命令不会等待。
+    // SELECT会把db换成选中的库，所以这里要可变引用
+    pub(crate) fn execute(self, db: &mut Db, dst: &mut Connection) -> Frame {
+        use Command::*;
+        match self {
+            Get(cmd) => cmd.execute(db),
+            Set(cmd) => cmd.execute(db),
+            Publish(cmd) => cmd.execute(db),
+            PubSub(cmd) => cmd.execute(db),
+            Hello(cmd) => cmd.execute(dst),
+            LPush(cmd) => cmd.execute(db),
+            RPush(cmd) => cmd.execute(db),
+            LPop(cmd) => cmd.execute(db),
+            RPop(cmd) => cmd.execute(db),
+            LRange(cmd) => cmd.execute(db),
+            LLen(cmd) => cmd.execute(db),
+            BLPop(cmd) => cmd.execute(db),
+            BRPop(cmd) => cmd.execute(db),
+            BLMove(cmd) => cmd.execute(db),
+            HSet(cmd) => cmd.execute(db),
+            HGet(cmd) => cmd.execute(db),
+            HMGet(cmd) => cmd.execute(db),
+            HDel(cmd) => cmd.execute(db),
+            HExists(cmd) => cmd.execute(db),
+            HLen(cmd) => cmd.execute(db),
+            HKeys(cmd) => cmd.execute(db),
+            HVals(cmd) => cmd.execute(db),
+            HGetAll(cmd) => cmd.execute(db),
+            HIncrBy(cmd) => cmd.execute(db),
+            HIncrByFloat(cmd) => cmd.execute(db),
+            HScan(cmd) => cmd.execute(db),
+            SAdd(cmd) => cmd.execute(db),
+            SRem(cmd) => cmd.execute(db),
+            SIsMember(cmd) => cmd.execute(db),
+            SMembers(cmd) => cmd.execute(db),
+            SCard(cmd) => cmd.execute(db),
+            SInter(cmd) => cmd.execute(db),
+            SUnion(cmd) => cmd.execute(db),
+            SDiff(cmd) => cmd.execute(db),
+            SInterStore(cmd) => cmd.execute(db),
+            SUnionStore(cmd) => cmd.execute(db),
+            SDiffStore(cmd) => cmd.execute(db),
+            ZAdd(cmd) => cmd.execute(db),
+            ZIncrBy(cmd) => cmd.execute(db),
+            ZRem(cmd) => cmd.execute(db),
+            ZScore(cmd) => cmd.execute(db),
+            ZCard(cmd) => cmd.execute(db),
+            ZRank(cmd) => cmd.execute(db),
+            ZRange(cmd) => cmd.execute(db, dst.protocol()),
+            ZRangeByScore(cmd) => cmd.execute(db, dst.protocol()),
+            XAdd(cmd) => cmd.execute(db),
+            XRange(cmd) => cmd.execute(db),
+            XRead(cmd) => cmd.execute(db, dst.protocol()),
+            XLen(cmd) => cmd.execute(db),
+            XTrim(cmd) => cmd.execute(db),
+            XGroup(cmd) => cmd.execute(db),
+            XReadGroup(cmd) => cmd.execute(db, dst.protocol()),
+            XAck(cmd) => cmd.execute(db),
+            XPending(cmd) => cmd.execute(db),
+            XClaim(cmd) => cmd.execute(db),
+            XAutoClaim(cmd) => cmd.execute(db),
+            XInfo(cmd) => cmd.execute(db),
+            Append(cmd) => cmd.execute(db),
+            GetRange(cmd) => cmd.execute(db),
+            SetRange(cmd) => cmd.execute(db),
+            StrLen(cmd) => cmd.execute(db),
+            GetDel(cmd) => cmd.execute(db),
+            GetEx(cmd) => cmd.execute(db),
+            MGet(cmd) => cmd.execute(db),
+            MSet(cmd) => cmd.execute(db),
+            MSetNx(cmd) => cmd.execute(db),
+            Incr(cmd) => cmd.execute(db),
+            Decr(cmd) => cmd.execute(db),
+            IncrBy(cmd) => cmd.execute(db),
+            DecrBy(cmd) => cmd.execute(db),
+            IncrByFloat(cmd) => cmd.execute(db),
+            Expire(cmd) => cmd.execute(db),
+            PExpire(cmd) => cmd.execute(db),
+            ExpireAt(cmd) => cmd.execute(db),
+            PExpireAt(cmd) => cmd.execute(db),
+            Ttl(cmd) => cmd.execute(db),
+            PTtl(cmd) => cmd.execute(db),
+            Persist(cmd) => cmd.execute(db),
+            ExpireTime(cmd) => cmd.execute(db),
+            PExpireTime(cmd) => cmd.execute(db),
+            Del(cmd) => cmd.execute(db),
+            Unlink(cmd) => cmd.execute(db),
+            Exists(cmd) => cmd.execute(db),
+            Touch(cmd) => cmd.execute(db),
+            Type(cmd) => cmd.execute(db),
+            Rename(cmd) => cmd.execute(db),
+            RenameNx(cmd) => cmd.execute(db),
+            Copy(cmd) => cmd.execute(db),
+            Keys(cmd) => cmd.execute(db),
+            Scan(cmd) => cmd.execute(db),
+            Select(cmd) => cmd.execute(db),
+            SwapDb(cmd) => cmd.execute(db),
+            Move(cmd) => cmd.execute(db),
+            FlushDb(cmd) => cmd.execute(db),
+            FlushAll(cmd) => cmd.execute(db),
+            Unwatch(cmd) => cmd.execute(),
+            Script(cmd) => cmd.execute(db),
+            Unknown(cmd) => cmd.execute(),
+            // 订阅之后要一直等消息，事务的命令要用到连接上排队的命令，都在server::Handler里处理；
+            // 脚本要等它在另一个线程上执行完。这些命令不会排进事务里，脚本里也不能执行
+            Subscribe(_) | Unsubscribe(_) | PSubscribe(_) | PUnsubscribe(_) | Multi(_) | Exec(_) | Discard(_)
+            | Watch(_) | Eval(_) | EvalSha(_) => unreachable!("'{}' command can not be executed synchronously", self.get_name()),
+        }
+    }
+
+    // 命令名，用于日志和错误提示
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            Command::Get(_) => "get",
+            Command::Set(_) => "set",
+            Command::Publish(_) => "publish",
+            Command::Subscribe(_) => "subscribe",
+            Command::Unsubscribe(_) => "unsubscribe",
+            Command::PSubscribe(_) => "psubscribe",
+            Command::PUnsubscribe(_) => "punsubscribe",
+            Command::PubSub(_) => "pubsub",
+            Command::Hello(_) => "hello",
+            Command::LPush(_) => "lpush",
+            Command::RPush(_) => "rpush",
+            Command::LPop(_) => "lpop",
+            Command::RPop(_) => "rpop",
+            Command::LRange(_) => "lrange",
+            Command::LLen(_) => "llen",
+            Command::BLPop(_) => "blpop",
+            Command::BRPop(_) => "brpop",
+            Command::BLMove(_) => "blmove",
+            Command::HSet(_) => "hset",
+            Command::HGet(_) => "hget",
+            Command::HMGet(_) => "hmget",
+            Command::HDel(_) => "hdel",
+            Command::HExists(_) => "hexists",
+            Command::HLen(_) => "hlen",
+            Command::HKeys(_) => "hkeys",
+            Command::HVals(_) => "hvals",
+            Command::HGetAll(_) => "hgetall",
+            Command::HIncrBy(_) => "hincrby",
+            Command::HIncrByFloat(_) => "hincrbyfloat",
+            Command::HScan(_) => "hscan",
+            Command::SAdd(_) => "sadd",
+            Command::SRem(_) => "srem",
+            Command::SIsMember(_) => "sismember",
+            Command::SMembers(_) => "smembers",
+            Command::SCard(_) => "scard",
+            Command::SInter(_) => "sinter",
+            Command::SUnion(_) => "sunion",
+            Command::SDiff(_) => "sdiff",
+            Command::SInterStore(_) => "sinterstore",
+            Command::SUnionStore(_) => "sunionstore",
+            Command::SDiffStore(_) => "sdiffstore",
+            Command::ZAdd(_) => "zadd",
+            Command::ZIncrBy(_) => "zincrby",
+            Command::ZRem(_) => "zrem",
+            Command::ZScore(_) => "zscore",
+            Command::ZCard(_) => "zcard",
+            Command::ZRank(_) => "zrank",
+            Command::ZRange(_) => "zrange",
+            Command::ZRangeByScore(_) => "zrangebyscore",
+            Command::XAdd(_) => "xadd",
+            Command::XRange(_) => "xrange",
+            Command::XRead(_) => "xread",
+            Command::XLen(_) => "xlen",
+            Command::XTrim(_) => "xtrim",
+            Command::XGroup(_) => "xgroup",
+            Command::XReadGroup(_) => "xreadgroup",
+            Command::XAck(_) => "xack",
+            Command::XPending(_) => "xpending",
+            Command::XClaim(_) => "xclaim",
+            Command::XAutoClaim(_) => "xautoclaim",
+            Command::XInfo(_) => "xinfo",
+            Command::Append(_) => "append",
+            Command::GetRange(_) => "getrange",
+            Command::SetRange(_) => "setrange",
+            Command::StrLen(_) => "strlen",
+            Command::GetDel(_) => "getdel",
+            Command::GetEx(_) => "getex",
+            Command::MGet(_) => "mget",
+            Command::MSet(_) => "mset",
+            Command::MSetNx(_) => "msetnx",
+            Command::Incr(_) => "incr",
+            Command::Decr(_) => "decr",
+            Command::IncrBy(_) => "incrby",
+            Command::DecrBy(_) => "decrby",
+            Command::IncrByFloat(_) => "incrbyfloat",
+            Command::Expire(_) => "expire",
+            Command::PExpire(_) => "pexpire",
+            Command::ExpireAt(_) => "expireat",
+            Command::PExpireAt(_) => "pexpireat",
+            Command::Ttl(_) => "ttl",
+            Command::PTtl(_) => "pttl",
+            Command::Persist(_) => "persist",
+            Command::ExpireTime(_) => "expiretime",
+            Command::PExpireTime(_) => "pexpiretime",
+            Command::Del(_) => "del",
+            Command::Unlink(_) => "unlink",
+            Command::Exists(_) => "exists",
+            Command::Touch(_) => "touch",
+            Command::Type(_) => "type",
+            Command::Rename(_) => "rename",
+            Command::RenameNx(_) => "renamenx",
+            Command::Copy(_) => "copy",
+            Command::Keys(_) => "keys",
+            Command::Scan(_) => "scan",
+            Command::Select(_) => "select",
+            Command::SwapDb(_) => "swapdb",
+            Command::Move(_) => "move",
+            Command::FlushDb(_) => "flushdb",
+            Command::FlushAll(_) => "flushall",
+            Command::Multi(_) => "multi",
+            Command::Exec(_) => "exec",
+            Command::Discard(_) => "discard",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
+            Command::Eval(_) => "eval",
+            Command::EvalSha(_) => "evalsha",
+            Command::Script(_) => "script",
+            Command::Unknown(cmd) => cmd.get_name(),
+        }
+    }
+}
+
+// 参数不够也算参数个数不对
+fn command_error(command_name: &str, err: crate::Error) -> crate::Error {
+    match err.downcast_ref::<ParseError>() {
+        Some(ParseError::EndOfStream) => wrong_arity(command_name),
+        _ => err,
+    }
+}
+
+fn wrong_arity(command_name: &str) -> crate::Error {
+    format!("ERR wrong number of arguments for '{}' command", command_name.to_lowercase()).into()
+}
+
+// 只带一个key的命令转回成frame，其他参数由调用者接着往后加
+pub(crate) fn make_key_frame(name: &str, key: Bytes) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    frame.push_bulk(key);
+    frame
+}
+
+// 命令名后面跟着一串key
+pub(crate) fn make_keys_frame(name: &str, keys: Vec<Bytes>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    for key in keys {
+        frame.push_bulk(key);
+    }
+    frame
+}
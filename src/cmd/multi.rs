@@ -0,0 +1,194 @@
+use bytes::Bytes;
+use crate::parse::Parse;
+use crate::db::{Db, WatchedKey};
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::cmd::{Command, make_keys_frame};
+use crate::cmd::keys::parse_keys;
+use tracing::debug;
+
+// MULTI
+#[derive(Debug, Clone, PartialEq)]
+pub struct Multi {}
+
+// EXEC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exec {}
+
+// DISCARD
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discard {}
+
+// WATCH key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    keys: Vec<Bytes>,
+}
+
+// UNWATCH
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unwatch {}
+
+// 这几个命令要用到连接上排队的命令和WATCH的key，由server::Handler处理，这里只管回复
+impl Multi {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Multi> {
+        Ok(Self {})
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple("OK".to_string());
+
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("multi", vec![])
+    }
+}
+
+impl Default for Multi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Exec {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Exec> {
+        Ok(Self {})
+    }
+
+    // 所有的命令在一次加锁里执行完，回复按顺序放到一个数组里。WATCH的key被改过的话一个都不执行，回复nil
+    pub(crate) async fn apply(
+        self,
+        db: &mut Db,
+        dst: &mut Connection,
+        commands: Vec<Command>,
+        watched: Vec<WatchedKey>,
+    ) -> crate::Result<()> {
+        let response = match db.transaction(&watched).await {
+            Some(mut transaction) => {
+                let mut replies = Vec::with_capacity(commands.len());
+                for cmd in commands {
+                    // 出错的命令在数组里对应一个错误，后面的命令照样执行。脚本要等它在另一个线程上执行完，
+                    // 其他命令都是同步执行的，阻塞的命令也不会等待
+                    let reply = match cmd {
+                        Command::Eval(cmd) => cmd.execute(transaction.db(), dst).await,
+                        Command::EvalSha(cmd) => cmd.execute(transaction.db(), dst).await,
+                        cmd => cmd.execute(transaction.db(), dst),
+                    };
+                    replies.push(reply);
+                }
+                *db = transaction.finish();
+                Frame::Array(replies)
+            }
+            None => Frame::Null,
+        };
+
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("exec", vec![])
+    }
+}
+
+impl Default for Exec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discard {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Discard> {
+        Ok(Self {})
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple("OK".to_string());
+
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("discard", vec![])
+    }
+}
+
+impl Default for Discard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watch {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Watch> {
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { keys })
+    }
+
+    // 记下这些key现在的版本，已经WATCH过的key再来一次不影响之前记下的
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, watched: &mut Vec<WatchedKey>) -> crate::Result<()> {
+        watched.extend(db.watch(&self.keys));
+        let response = Frame::Simple("OK".to_string());
+
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("watch", self.keys)
+    }
+}
+
+impl Unwatch {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Unwatch> {
+        Ok(Self {})
+    }
+
+    // EXEC和DISCARD之后本来就不WATCH了，在事务里执行什么都不用做
+    pub(crate) fn execute(self) -> Frame {
+        Frame::Simple("OK".to_string())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("unwatch", vec![])
+    }
+}
+
+impl Default for Unwatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,48 @@
+use bytes::Bytes;
+use crate::parse::Parse;
+use crate::db::Db;
+use crate::frame::Frame;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Publish {
+    channel: String,
+    message: Bytes,
+}
+
+impl Publish {
+    pub fn new(channel: impl ToString, message: Bytes) -> Self {
+        Self {
+            channel: channel.to_string(),
+            message,
+        }
+    }
+
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    pub fn message(&self) -> &Bytes {
+        &self.message
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Publish> {
+        let channel = parse.next_string()?;
+        let message = parse.next_byte()?;
+
+        Ok(Self { channel, message })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        // 返回收到消息的订阅者数量
+        let num_subscribers = db.publish(&self.channel, self.message);
+        Frame::Integer(num_subscribers as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("publish".as_bytes()));
+        frame.push_bulk(Bytes::from(self.channel.into_bytes()));
+        frame.push_bulk(self.message);
+        frame
+    }
+}
@@ -0,0 +1,86 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::frame::Frame;
+
+// PUBSUB 自省命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum PubSub {
+    // 有订阅者的频道，可以带一个glob模式过滤
+    Channels(Option<String>),
+    // 指定频道的订阅者数量
+    NumSub(Vec<String>),
+    // 模式订阅的数量
+    NumPat,
+}
+
+impl PubSub {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PubSub> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match &subcommand[..] {
+            "channels" => match parse.next_string() {
+                Ok(pattern) => Ok(PubSub::Channels(Some(pattern))),
+                Err(ParseError::EndOfStream) => Ok(PubSub::Channels(None)),
+                Err(err) => Err(err.into()),
+            },
+            "numsub" => {
+                let mut channels = vec![];
+                loop {
+                    match parse.next_string() {
+                        Ok(channel) => channels.push(channel),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(PubSub::NumSub(channels))
+            }
+            "numpat" => Ok(PubSub::NumPat),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try PUBSUB HELP.", subcommand).into()),
+        }
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self {
+            PubSub::Channels(pattern) => {
+                let mut response = Frame::array();
+                for channel in db.pubsub_channels(pattern.as_deref()) {
+                    response.push_bulk(Bytes::from(channel));
+                }
+                response
+            }
+            PubSub::NumSub(channels) => {
+                let nums = db.pubsub_numsub(&channels);
+                let mut response = Frame::array();
+                for (channel, num) in channels.into_iter().zip(nums) {
+                    response.push_bulk(Bytes::from(channel));
+                    response.push_int(num as i64);
+                }
+                response
+            }
+            PubSub::NumPat => Frame::Integer(db.pubsub_numpat() as i64),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("pubsub".as_bytes()));
+        match self {
+            PubSub::Channels(pattern) => {
+                frame.push_bulk(Bytes::from("channels".as_bytes()));
+                if let Some(pattern) = pattern {
+                    frame.push_bulk(Bytes::from(pattern.into_bytes()));
+                }
+            }
+            PubSub::NumSub(channels) => {
+                frame.push_bulk(Bytes::from("numsub".as_bytes()));
+                for channel in channels {
+                    frame.push_bulk(Bytes::from(channel.into_bytes()));
+                }
+            }
+            PubSub::NumPat => {
+                frame.push_bulk(Bytes::from("numpat".as_bytes()));
+            }
+        }
+        frame
+    }
+}
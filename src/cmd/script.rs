@@ -0,0 +1,240 @@
+use bytes::Bytes;
+use std::convert::TryFrom;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::cmd::select::parse_flush_mode;
+use crate::lua;
+
+// EVAL script numkeys [key ...] [arg ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eval {
+    script: Bytes,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+// EVALSHA sha1 numkeys [key ...] [arg ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalSha {
+    sha1: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+// SCRIPT 管理缓存的脚本
+#[derive(Debug, Clone, PartialEq)]
+pub enum Script {
+    // SCRIPT LOAD script，只缓存不执行
+    Load(Bytes),
+    // SCRIPT EXISTS sha1 [sha1 ...]
+    Exists(Vec<String>),
+    // SCRIPT FLUSH [ASYNC|SYNC]，缓存很小，两种都是直接清空
+    Flush,
+    // SCRIPT KILL 停掉正在执行的脚本
+    Kill,
+}
+
+impl Eval {
+    pub fn new(script: impl Into<Bytes>, keys: Vec<Bytes>, args: Vec<Bytes>) -> Self {
+        Self {
+            script: script.into(),
+            keys,
+            args,
+        }
+    }
+
+    pub fn script(&self) -> &Bytes {
+        &self.script
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub fn args(&self) -> &[Bytes] {
+        &self.args
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Eval> {
+        let script = parse.next_byte()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(Self { script, keys, args })
+    }
+
+    // 执行过的脚本也会缓存起来，之后可以用EVALSHA。脚本在另一个线程上执行，要等它执行完拿到回复
+    pub(crate) async fn execute(self, db: &Db, dst: &mut Connection) -> Frame {
+        match lua::compile(&self.script) {
+            Ok(()) => {
+                db.script_load(self.script.clone());
+                run_script(db, dst, self.script, self.keys, self.args).await
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_script_frame("eval", self.script, self.keys, self.args)
+    }
+}
+
+impl EvalSha {
+    pub fn new(sha1: impl ToString, keys: Vec<Bytes>, args: Vec<Bytes>) -> Self {
+        Self {
+            sha1: sha1.to_string(),
+            keys,
+            args,
+        }
+    }
+
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub fn args(&self) -> &[Bytes] {
+        &self.args
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<EvalSha> {
+        let sha1 = parse.next_string()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(Self { sha1, keys, args })
+    }
+
+    pub(crate) async fn execute(self, db: &Db, dst: &mut Connection) -> Frame {
+        match db.script_body(&self.sha1) {
+            Some(script) => run_script(db, dst, script, self.keys, self.args).await,
+            None => Frame::Error("NOSCRIPT No matching script. Please use EVAL.".to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_script_frame("evalsha", Bytes::from(self.sha1.into_bytes()), self.keys, self.args)
+    }
+}
+
+impl Script {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Script> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match &subcommand[..] {
+            "load" => Ok(Script::Load(parse.next_byte()?)),
+            "exists" => {
+                let mut sha1s = vec![parse.next_string()?];
+                loop {
+                    match parse.next_string() {
+                        Ok(sha1) => sha1s.push(sha1),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(Script::Exists(sha1s))
+            }
+            "flush" => {
+                parse_flush_mode(parse)?;
+                Ok(Script::Flush)
+            }
+            "kill" => Ok(Script::Kill),
+            _ => Err(format!("ERR unknown subcommand '{}'. Try SCRIPT HELP.", subcommand).into()),
+        }
+    }
+
+    // KILL不用拿锁，脚本执行期间也能马上执行
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self {
+            Script::Load(script) => match lua::compile(&script) {
+                Ok(()) => Frame::Bulk(Bytes::from(db.script_load(script))),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            Script::Exists(sha1s) => {
+                let mut response = Frame::array();
+                for exists in db.script_exists(&sha1s) {
+                    response.push_int(exists as i64);
+                }
+                response
+            }
+            Script::Flush => {
+                db.script_flush();
+                Frame::Simple("OK".to_string())
+            }
+            Script::Kill => match db.kill_script() {
+                Ok(()) => Frame::Simple("OK".to_string()),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("script".as_bytes()));
+        match self {
+            Script::Load(script) => {
+                frame.push_bulk(Bytes::from("load".as_bytes()));
+                frame.push_bulk(script);
+            }
+            Script::Exists(sha1s) => {
+                frame.push_bulk(Bytes::from("exists".as_bytes()));
+                for sha1 in sha1s {
+                    frame.push_bulk(Bytes::from(sha1.into_bytes()));
+                }
+            }
+            Script::Flush => {
+                frame.push_bulk(Bytes::from("flush".as_bytes()));
+            }
+            Script::Kill => {
+                frame.push_bulk(Bytes::from("kill".as_bytes()));
+            }
+        }
+        frame
+    }
+}
+
+// 脚本执行期间独占所有的库，在EXEC里执行的时候已经独占了。脚本里SELECT换库不影响这个连接
+async fn run_script(db: &Db, dst: &mut Connection, script: Bytes, keys: Vec<Bytes>, args: Vec<Bytes>) -> Frame {
+    let mut transaction = if db.in_transaction() { None } else { Some(db.exclusive().await) };
+    let mut script_db = match &mut transaction {
+        Some(transaction) => transaction.db().clone(),
+        None => db.clone(),
+    };
+
+    let guard = db.start_script();
+    lua::eval(&mut script_db, dst, script, keys, args, &guard).await
+}
+
+// numkeys个key，剩下的都是参数
+fn parse_keys_and_args(parse: &mut Parse) -> crate::Result<(Vec<Bytes>, Vec<Bytes>)> {
+    let numkeys = parse.next_int()?;
+    let numkeys = usize::try_from(numkeys).map_err(|_| "ERR Number of keys can't be negative")?;
+
+    let mut keys = vec![];
+    loop {
+        match parse.next_byte() {
+            Ok(arg) => keys.push(arg),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if numkeys > keys.len() {
+        return Err("ERR Number of keys can't be greater than number of args".into());
+    }
+
+    let args = keys.split_off(numkeys);
+    Ok((keys, args))
+}
+
+fn make_script_frame(name: &str, script: Bytes, keys: Vec<Bytes>, args: Vec<Bytes>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    frame.push_bulk(script);
+    frame.push_bulk(Bytes::from(keys.len().to_string()));
+    for arg in keys.into_iter().chain(args) {
+        frame.push_bulk(arg);
+    }
+    frame
+}
@@ -0,0 +1,250 @@
+use bytes::Bytes;
+use std::convert::TryFrom;
+use crate::parse::{Parse, ParseError};
+use crate::db::{Db, OUT_OF_RANGE};
+use crate::frame::Frame;
+
+// SELECT index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    index: usize,
+}
+
+// SWAPDB index1 index2
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapDb {
+    first: usize,
+    second: usize,
+}
+
+// MOVE key db
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    key: Bytes,
+    db: usize,
+}
+
+// FLUSHDB [ASYNC|SYNC]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlushDb {
+    lazy: bool,
+}
+
+// FLUSHALL [ASYNC|SYNC]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlushAll {
+    lazy: bool,
+}
+
+impl Select {
+    pub fn new(index: usize) -> Self {
+        Self { index }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Select> {
+        let index = parse_db_index(parse)?;
+
+        Ok(Self { index })
+    }
+
+    // 选中的库是跟着连接走的，换掉这个连接持有的Db就行了
+    pub(crate) fn execute(self, db: &mut Db) -> Frame {
+        match db.select(self.index) {
+            Ok(selected) => {
+                *db = selected;
+                Frame::Simple("OK".to_string())
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_frame("select", &[self.index.to_string()])
+    }
+}
+
+impl SwapDb {
+    pub fn new(first: usize, second: usize) -> Self {
+        Self { first, second }
+    }
+
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    pub fn second(&self) -> usize {
+        self.second
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SwapDb> {
+        let first = parse.next_int().map_err(|_| "ERR invalid first DB index")?;
+        let second = parse.next_int().map_err(|_| "ERR invalid second DB index")?;
+        let first = usize::try_from(first).map_err(|_| OUT_OF_RANGE)?;
+        let second = usize::try_from(second).map_err(|_| OUT_OF_RANGE)?;
+
+        Ok(Self { first, second })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.swapdb(self.first, self.second) {
+            Ok(()) => Frame::Simple("OK".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_frame("swapdb", &[self.first.to_string(), self.second.to_string()])
+    }
+}
+
+impl Move {
+    pub fn new(key: impl Into<Bytes>, db: usize) -> Self {
+        Self {
+            key: key.into(),
+            db,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn db(&self) -> usize {
+        self.db
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Move> {
+        let key = parse.next_byte()?;
+        let db = parse_db_index(parse)?;
+
+        Ok(Self { key, db })
+    }
+
+    // 移过去了回复1，key不存在或者目标库里已经有了回复0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.move_key(&self.key, self.db) {
+            Ok(moved) => Frame::Integer(moved as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_frame("move", &[]);
+        frame.push_bulk(self.key);
+        frame.push_bulk(Bytes::from(self.db.to_string()));
+        frame
+    }
+}
+
+impl FlushDb {
+    pub fn new() -> Self {
+        Self { lazy: false }
+    }
+
+    // 数据放到后台线程去释放
+    pub fn with_async(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+
+    pub fn is_async(&self) -> bool {
+        self.lazy
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<FlushDb> {
+        let lazy = parse_flush_mode(parse)?;
+
+        Ok(Self { lazy })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        db.flush(false, self.lazy);
+        Frame::Simple("OK".to_string())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_frame("flushdb", &[]);
+        if self.lazy {
+            frame.push_bulk(Bytes::from("ASYNC".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl Default for FlushDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlushAll {
+    pub fn new() -> Self {
+        Self { lazy: false }
+    }
+
+    // 数据放到后台线程去释放
+    pub fn with_async(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+
+    pub fn is_async(&self) -> bool {
+        self.lazy
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<FlushAll> {
+        let lazy = parse_flush_mode(parse)?;
+
+        Ok(Self { lazy })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        db.flush(true, self.lazy);
+        Frame::Simple("OK".to_string())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_frame("flushall", &[]);
+        if self.lazy {
+            frame.push_bulk(Bytes::from("ASYNC".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl Default for FlushAll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 库的下标，负数当成超出范围
+pub(crate) fn parse_db_index(parse: &mut Parse) -> crate::Result<usize> {
+    let index = parse.next_int()?;
+    Ok(usize::try_from(index).map_err(|_| OUT_OF_RANGE)?)
+}
+
+// 可选的ASYNC|SYNC，返回是不是ASYNC
+pub(crate) fn parse_flush_mode(parse: &mut Parse) -> crate::Result<bool> {
+    let lazy = match parse.next_string() {
+        Ok(mode) if mode.eq_ignore_ascii_case("ASYNC") => true,
+        Ok(mode) if mode.eq_ignore_ascii_case("SYNC") => false,
+        Ok(_) => return Err("ERR syntax error".into()),
+        Err(ParseError::EndOfStream) => false,
+        Err(err) => return Err(err.into()),
+    };
+    Ok(lazy)
+}
+
+fn make_frame(name: &str, args: &[String]) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    for arg in args {
+        frame.push_bulk(Bytes::from(arg.clone()));
+    }
+    frame
+}
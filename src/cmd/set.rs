@@ -2,28 +2,63 @@ use bytes::Bytes;
 use std::time::Duration;
 use crate::parse::{ParseError, Parse};
 use crate::db::Db;
-use crate::connection::Connection;
 use crate::frame::Frame;
-use tracing::{debug, instrument};
+use crate::cmd::string::{parse_expiry, Expiry};
 
-#[derive(Debug)]
+// SET key value [NX|XX] [GET] [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|KEEPTTL]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Set {
-    key: String,
+    key: Bytes,
     value: Bytes,
-    expire: Option<Duration>,
+    expiry: Option<Expiry>,
+    condition: Option<SetCondition>,
+    get: bool,
+    keep_ttl: bool,
+}
+
+// NX只在key不存在的时候设置，XX只在key存在的时候设置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    Nx,
+    Xx,
 }
 
 impl Set {
-    pub fn new(key: impl ToString, value: Bytes, expire: Option<Duration>) -> Self {
+    pub fn new(key: impl Into<Bytes>, value: Bytes, expire: Option<Duration>) -> Self {
         Set {
-            key: key.to_string(),
+            key: key.into(),
             value,
-            expire,
+            expiry: expire.map(Expiry::In),
+            condition: None,
+            get: false,
+            keep_ttl: false,
         }
     }
 
+    // PERSIST对SET没有意义，和不带过期时间是一样的
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self.keep_ttl = false;
+        self
+    }
+
+    pub fn with_condition(mut self, condition: SetCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn with_get(mut self) -> Self {
+        self.get = true;
+        self
+    }
 
-    pub fn key(&self) -> &str {
+    pub fn with_keep_ttl(mut self) -> Self {
+        self.keep_ttl = true;
+        self.expiry = None;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
         &self.key
     }
 
@@ -31,39 +66,95 @@ impl Set {
         &self.value
     }
 
-    pub fn expire(&self) -> Option<Duration> {
-        self.expire
+    pub fn expiry(&self) -> Option<Expiry> {
+        self.expiry
     }
 
-    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
-        use ParseError::EndOfStream;
+    pub fn condition(&self) -> Option<SetCondition> {
+        self.condition
+    }
+
+    pub fn get(&self) -> bool {
+        self.get
+    }
 
-        let key = parse.next_string()?;
+    pub fn keep_ttl(&self) -> bool {
+        self.keep_ttl
+    }
+
+    // 选项可以按任意顺序出现，不区分大小写。NX和XX不能同时出现，过期时间和KEEPTTL只能有一个
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
+        let key = parse.next_byte()?;
         let value = parse.next_byte()?;
-        let mut expire = None;
+        let mut set = Set::new(key, value, None);
 
-        match parse.next_string() {
-            Ok(s) if s == "EX" => {
-                let sec = parse.next_int()?;
-                expire = Some(Duration::from_secs(sec));
-            }
-            Ok(s) if s == "PX" => {
-                let ms = parse.next_int()?;
-                expire = Some(Duration::from_millis(ms));
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_ascii_uppercase(),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            match &option[..] {
+                "NX" | "XX" => {
+                    let condition = if option == "NX" { SetCondition::Nx } else { SetCondition::Xx };
+                    if set.condition.is_some_and(|prev| prev != condition) {
+                        return Err("ERR syntax error".into());
+                    }
+                    set.condition = Some(condition);
+                }
+                "GET" => set.get = true,
+                "KEEPTTL" => {
+                    if set.expiry.is_some() {
+                        return Err("ERR syntax error".into());
+                    }
+                    set.keep_ttl = true;
+                }
+                option => {
+                    if set.expiry.is_some() || set.keep_ttl {
+                        return Err("ERR syntax error".into());
+                    }
+                    set.expiry = Some(parse_expiry(parse, option, "set")?.ok_or("ERR syntax error")?);
+                }
             }
-            Ok(_) => return Err("currently `SET` only supports the expiration option".into()),
-            Err(EndOfStream) => {}
-            Err(err) => return Err(err.into()),
         }
 
-        Ok(Set { key, value, expire })
+        Ok(set)
     }
 
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        db.set(self.key, self.value, self.expire);
-        let response = Frame::Simple("OK".to_string());
-        debug!(?response);
-        dst.write_frame(&response).await?;
-        Ok(())
+    // 没有写入的时候回复nil，带GET的时候回复旧值
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let must_exist = self.condition.map(|condition| condition == SetCondition::Xx);
+        let expires_at = if self.keep_ttl { None } else { Some(self.expiry.and_then(Expiry::deadline)) };
+
+        match db.set(self.key, self.value, must_exist, expires_at, self.get) {
+            Ok((_, Some(old))) if self.get => Frame::Bulk(old),
+            Ok((true, _)) if !self.get => Frame::Simple("OK".to_string()),
+            Ok(_) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
     }
-}
\ No newline at end of file
+
+    // 转换成发送给服务端的frame，过期时间统一用毫秒(PX/PXAT)传过去
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("set".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(self.value);
+        match self.condition {
+            Some(SetCondition::Nx) => frame.push_bulk(Bytes::from("NX".as_bytes())),
+            Some(SetCondition::Xx) => frame.push_bulk(Bytes::from("XX".as_bytes())),
+            None => {}
+        }
+        if self.get {
+            frame.push_bulk(Bytes::from("GET".as_bytes()));
+        }
+        if self.keep_ttl {
+            frame.push_bulk(Bytes::from("KEEPTTL".as_bytes()));
+        }
+        match self.expiry {
+            Some(Expiry::Persist) | None => {}
+            Some(expiry) => expiry.push_frame(&mut frame),
+        }
+        frame
+    }
+}
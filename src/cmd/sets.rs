@@ -0,0 +1,451 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::{Db, SetOp};
+use crate::frame::Frame;
+use crate::cmd::make_keys_frame;
+
+// SADD key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SAdd {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+// SREM key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+// SISMEMBER key member
+#[derive(Debug, Clone, PartialEq)]
+pub struct SIsMember {
+    key: Bytes,
+    member: Bytes,
+}
+
+// SMEMBERS key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SMembers {
+    key: Bytes,
+}
+
+// SCARD key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SCard {
+    key: Bytes,
+}
+
+// SINTER key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SInter {
+    keys: Vec<Bytes>,
+}
+
+// SUNION key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SUnion {
+    keys: Vec<Bytes>,
+}
+
+// SDIFF key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SDiff {
+    keys: Vec<Bytes>,
+}
+
+// SINTERSTORE destination key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SInterStore {
+    destination: Bytes,
+    keys: Vec<Bytes>,
+}
+
+// SUNIONSTORE destination key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SUnionStore {
+    destination: Bytes,
+    keys: Vec<Bytes>,
+}
+
+// SDIFFSTORE destination key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SDiffStore {
+    destination: Bytes,
+    keys: Vec<Bytes>,
+}
+
+impl SAdd {
+    pub fn new(key: impl Into<Bytes>, members: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            members,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn members(&self) -> &[Bytes] {
+        &self.members
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SAdd> {
+        let key = parse.next_byte()?;
+        let members = parse_keys(parse)?;
+
+        Ok(Self { key, members })
+    }
+
+    // 回复新加进去的元素个数，已经在集合里的不算
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.sadd(self.key, self.members) {
+            Ok(num) => Frame::Integer(num as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_keys_frame("sadd", vec![self.key]);
+        for member in self.members {
+            frame.push_bulk(member);
+        }
+        frame
+    }
+}
+
+impl SRem {
+    pub fn new(key: impl Into<Bytes>, members: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            members,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn members(&self) -> &[Bytes] {
+        &self.members
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SRem> {
+        let key = parse.next_byte()?;
+        let members = parse_keys(parse)?;
+
+        Ok(Self { key, members })
+    }
+
+    // 回复实际删掉的元素个数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.srem(&self.key, &self.members) {
+            Ok(num) => Frame::Integer(num as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_keys_frame("srem", vec![self.key]);
+        for member in self.members {
+            frame.push_bulk(member);
+        }
+        frame
+    }
+}
+
+impl SIsMember {
+    pub fn new(key: impl Into<Bytes>, member: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            member: member.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn member(&self) -> &Bytes {
+        &self.member
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SIsMember> {
+        let key = parse.next_byte()?;
+        let member = parse.next_byte()?;
+
+        Ok(Self { key, member })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.sismember(&self.key, &self.member) {
+            Ok(is_member) => Frame::Integer(is_member as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("sismember", vec![self.key, self.member])
+    }
+}
+
+impl SMembers {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SMembers> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // 只有一个集合的并集就是它自己
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_op(db, SetOp::Union, &[self.key])
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("smembers", vec![self.key])
+    }
+}
+
+impl SCard {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SCard> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.scard(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("scard", vec![self.key])
+    }
+}
+
+impl SInter {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SInter> {
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_op(db, SetOp::Inter, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("sinter", self.keys)
+    }
+}
+
+impl SUnion {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SUnion> {
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_op(db, SetOp::Union, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("sunion", self.keys)
+    }
+}
+
+impl SDiff {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SDiff> {
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_op(db, SetOp::Diff, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_keys_frame("sdiff", self.keys)
+    }
+}
+
+impl SInterStore {
+    pub fn new(destination: impl Into<Bytes>, keys: Vec<Bytes>) -> Self {
+        Self {
+            destination: destination.into(),
+            keys,
+        }
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SInterStore> {
+        let destination = parse.next_byte()?;
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { destination, keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_store(db, SetOp::Inter, self.destination, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut keys = vec![self.destination];
+        keys.extend(self.keys);
+        make_keys_frame("sinterstore", keys)
+    }
+}
+
+impl SUnionStore {
+    pub fn new(destination: impl Into<Bytes>, keys: Vec<Bytes>) -> Self {
+        Self {
+            destination: destination.into(),
+            keys,
+        }
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SUnionStore> {
+        let destination = parse.next_byte()?;
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { destination, keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_store(db, SetOp::Union, self.destination, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut keys = vec![self.destination];
+        keys.extend(self.keys);
+        make_keys_frame("sunionstore", keys)
+    }
+}
+
+impl SDiffStore {
+    pub fn new(destination: impl Into<Bytes>, keys: Vec<Bytes>) -> Self {
+        Self {
+            destination: destination.into(),
+            keys,
+        }
+    }
+
+    pub fn destination(&self) -> &Bytes {
+        &self.destination
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SDiffStore> {
+        let destination = parse.next_byte()?;
+        let keys = parse_keys(parse)?;
+
+        Ok(Self { destination, keys })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_store(db, SetOp::Diff, self.destination, &self.keys)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut keys = vec![self.destination];
+        keys.extend(self.keys);
+        make_keys_frame("sdiffstore", keys)
+    }
+}
+
+// 至少要有一个
+fn parse_keys(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    let mut keys = vec![parse.next_byte()?];
+    loop {
+        match parse.next_byte() {
+            Ok(key) => keys.push(key),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(keys)
+}
+
+// RESP3下回复Set，RESP2下会降级成数组
+fn execute_op(db: &Db, op: SetOp, keys: &[Bytes]) -> Frame {
+    match db.sop(op, keys) {
+        Ok(members) => Frame::Set(members.into_iter().map(Frame::Bulk).collect()),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+// 回复结果集合的元素个数
+fn execute_store(db: &Db, op: SetOp, destination: Bytes, keys: &[Bytes]) -> Frame {
+    match db.sopstore(op, destination, keys) {
+        Ok(len) => Frame::Integer(len as i64),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
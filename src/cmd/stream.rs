@@ -0,0 +1,643 @@
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::connection::{Connection, Protocol};
+use crate::frame::Frame;
+use crate::shutdown::Shutdown;
+use crate::cmd::blocking::sleep_timeout;
+use crate::stream::{StreamId, StreamEntry, XAddId, TrimStrategy, TrimOptions};
+use tracing::debug;
+
+pub(crate) const INVALID_ID: &str = "ERR Invalid stream ID specified as stream command argument";
+
+// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] *|id field value [field value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XAdd {
+    key: Bytes,
+    id: XAddId,
+    fields: Vec<(Bytes, Bytes)>,
+    nomkstream: bool,
+    trim: Option<TrimOptions>,
+}
+
+// XRANGE key start end [COUNT count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XRange {
+    key: Bytes,
+    start: StreamId,
+    end: StreamId,
+    count: Option<u64>,
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XRead {
+    keys: Vec<Bytes>,
+    // None表示`$`，只读开始执行之后新加的消息
+    ids: Vec<Option<StreamId>>,
+    count: Option<u64>,
+    block: Option<Duration>,
+}
+
+// XLEN key
+#[derive(Debug, Clone, PartialEq)]
+pub struct XLen {
+    key: Bytes,
+}
+
+// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XTrim {
+    key: Bytes,
+    options: TrimOptions,
+}
+
+impl XAdd {
+    pub fn new(key: impl Into<Bytes>, id: XAddId, fields: Vec<(Bytes, Bytes)>) -> Self {
+        Self {
+            key: key.into(),
+            id,
+            fields,
+            nomkstream: false,
+            trim: None,
+        }
+    }
+
+    // key不存在的时候不创建流
+    pub fn with_nomkstream(mut self) -> Self {
+        self.nomkstream = true;
+        self
+    }
+
+    // 加完之后顺便裁剪
+    pub fn with_trim(mut self, trim: TrimOptions) -> Self {
+        self.trim = Some(trim);
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn id(&self) -> XAddId {
+        self.id
+    }
+
+    pub fn fields(&self) -> &[(Bytes, Bytes)] {
+        &self.fields
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XAdd> {
+        let key = parse.next_byte()?;
+        let mut nomkstream = false;
+        let mut trim = None;
+        let mut limit = None;
+
+        let id = loop {
+            let arg = parse.next_byte()?;
+            match &arg.to_ascii_uppercase()[..] {
+                b"NOMKSTREAM" => nomkstream = true,
+                b"MAXLEN" => trim = Some(parse_threshold(parse, false)?),
+                b"MINID" => trim = Some(parse_threshold(parse, true)?),
+                b"LIMIT" => limit = Some(parse.next_uint()?),
+                _ => break XAddId::parse(&arg).ok_or(INVALID_ID)?,
+            }
+        };
+
+        let mut fields = vec![];
+        loop {
+            match parse.next_byte() {
+                Ok(field) => match parse.next_byte() {
+                    Ok(value) => fields.push((field, value)),
+                    Err(ParseError::EndOfStream) => return Err("ERR wrong number of arguments for 'xadd' command".into()),
+                    Err(err) => return Err(err.into()),
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if fields.is_empty() {
+            return Err("ERR wrong number of arguments for 'xadd' command".into());
+        }
+
+        let trim = match (trim, limit) {
+            (Some((strategy, approx)), limit) => Some(trim_options(strategy, approx, limit)?),
+            (None, Some(_)) => return Err("ERR syntax error".into()),
+            (None, None) => None,
+        };
+
+        Ok(Self { key, id, fields, nomkstream, trim })
+    }
+
+    // 回复新消息的ID，NOMKSTREAM并且key不存在回复Null
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.xadd(self.key, self.id, self.fields, self.nomkstream, self.trim.as_ref()) {
+            Ok(Some(id)) => Frame::Bulk(Bytes::from(id.to_string())),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xadd".as_bytes()));
+        frame.push_bulk(self.key);
+        if self.nomkstream {
+            frame.push_bulk(Bytes::from("NOMKSTREAM".as_bytes()));
+        }
+        if let Some(trim) = self.trim {
+            push_trim(&mut frame, &trim);
+        }
+        frame.push_bulk(Bytes::from(self.id.to_string()));
+        for (field, value) in self.fields {
+            frame.push_bulk(field);
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
+
+impl XRange {
+    // start和end都是闭区间
+    pub fn new(key: impl Into<Bytes>, start: StreamId, end: StreamId) -> Self {
+        Self {
+            key: key.into(),
+            start,
+            end,
+            count: None,
+        }
+    }
+
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn start(&self) -> StreamId {
+        self.start
+    }
+
+    pub fn end(&self) -> StreamId {
+        self.end
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XRange> {
+        let key = parse.next_byte()?;
+
+        let start = parse_start(&parse.next_byte()?)?;
+        let end = parse_end(&parse.next_byte()?)?;
+
+        let mut count = None;
+        loop {
+            match parse.next_string() {
+                Ok(arg) if arg.eq_ignore_ascii_case("COUNT") => count = Some(parse.next_uint()?),
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { key, start, end, count })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.count {
+            Some(0) => Frame::Array(vec![]),
+            count => match db.xrange(&self.key, self.start, self.end, count.map(|count| count as usize)) {
+                Ok(entries) => entries_frame(entries),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xrange".as_bytes()));
+        frame.push_bulk(self.key);
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        frame
+    }
+}
+
+impl XRead {
+    // keys和ids一一对应
+    pub fn new(keys: Vec<Bytes>, ids: Vec<Option<StreamId>>) -> Self {
+        Self {
+            keys,
+            ids,
+            count: None,
+            block: None,
+        }
+    }
+
+    // 每个流最多返回多少条
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    // 没有新消息的时候最多等多久，0表示一直等
+    pub fn with_block(mut self, block: Duration) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub fn ids(&self) -> &[Option<StreamId>] {
+        &self.ids
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XRead> {
+        let mut count = None;
+        let mut block = None;
+
+        loop {
+            let arg = parse.next_string()?;
+            match &arg.to_ascii_uppercase()[..] {
+                "COUNT" => count = Some(parse.next_uint()?),
+                "BLOCK" => block = Some(parse_block(parse)?),
+                "STREAMS" => break,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        let (keys, ids) = parse_streams(parse, "xread")?;
+        let ids = ids
+            .iter()
+            .map(|id| match &id[..] {
+                b"$" => Ok(None),
+                id => StreamId::parse(id, 0).map(Some).ok_or(INVALID_ID),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { keys, ids, count, block })
+    }
+
+    // 回复每个有新消息的流，都没有新消息回复Null
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        let found = self.read(db, shutdown).await;
+        // 服务关闭了，直接放掉这个连接
+        if shutdown.is_shutdown() && found.as_ref().is_ok_and(|found| found.is_empty()) {
+            return Ok(());
+        }
+
+        let response = xread_frame(dst.protocol(), found);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    // 事务和脚本里不阻塞，只读一次
+    pub(crate) fn execute(self, db: &Db, protocol: Protocol) -> Frame {
+        let found = self.resolve_ids(db).and_then(|ids| db.xread(&self.keys, &ids, self.read_count()));
+        xread_frame(protocol, found)
+    }
+
+    async fn read(&self, db: &Db, shutdown: &mut Shutdown) -> crate::Result<Vec<(Bytes, Vec<StreamEntry>)>> {
+        let ids = self.resolve_ids(db)?;
+        read_or_block(db, shutdown, &self.keys, self.block, || db.xread(&self.keys, &ids, self.read_count())).await
+    }
+
+    // `$`在开始等待之前就确定下来，等待期间加的消息都算新的
+    fn resolve_ids(&self, db: &Db) -> crate::Result<Vec<StreamId>> {
+        self.keys
+            .iter()
+            .zip(&self.ids)
+            .map(|(key, id)| match id {
+                Some(id) => Ok(*id),
+                None => db.xlast_id(key),
+            })
+            .collect()
+    }
+
+    // COUNT 0和不带COUNT一样
+    fn read_count(&self) -> Option<usize> {
+        self.count.filter(|count| *count > 0).map(|count| count as usize)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xread".as_bytes()));
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if let Some(block) = self.block {
+            frame.push_bulk(Bytes::from("BLOCK".as_bytes()));
+            frame.push_bulk(Bytes::from(block.as_millis().to_string()));
+        }
+        frame.push_bulk(Bytes::from("STREAMS".as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(key);
+        }
+        for id in self.ids {
+            match id {
+                Some(id) => frame.push_bulk(Bytes::from(id.to_string())),
+                None => frame.push_bulk(Bytes::from("$".as_bytes())),
+            }
+        }
+        frame
+    }
+}
+
+impl XLen {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XLen> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.xlen(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xlen".as_bytes()));
+        frame.push_bulk(self.key);
+        frame
+    }
+}
+
+impl XTrim {
+    pub fn new(key: impl Into<Bytes>, options: TrimOptions) -> Self {
+        Self {
+            key: key.into(),
+            options,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn options(&self) -> &TrimOptions {
+        &self.options
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XTrim> {
+        let key = parse.next_byte()?;
+        let (strategy, approx) = match &parse.next_byte()?.to_ascii_uppercase()[..] {
+            b"MAXLEN" => parse_threshold(parse, false)?,
+            b"MINID" => parse_threshold(parse, true)?,
+            _ => return Err("ERR syntax error".into()),
+        };
+
+        let mut limit = None;
+        loop {
+            match parse.next_string() {
+                Ok(arg) if arg.eq_ignore_ascii_case("LIMIT") => limit = Some(parse.next_uint()?),
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let options = trim_options(strategy, approx, limit)?;
+        Ok(Self { key, options })
+    }
+
+    // 回复删掉的消息条数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.xtrim(&self.key, &self.options) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xtrim".as_bytes()));
+        frame.push_bulk(self.key);
+        push_trim(&mut frame, &self.options);
+        frame
+    }
+}
+
+// 先读一次，读不到并且要阻塞的时候在keys上登记一个Notify，等到有新消息、超时或者服务关闭，
+// 后两种返回空
+pub(crate) async fn read_or_block<T>(
+    db: &Db,
+    shutdown: &mut Shutdown,
+    keys: &[Bytes],
+    block: Option<Duration>,
+    mut read: impl FnMut() -> crate::Result<Vec<T>>,
+) -> crate::Result<Vec<T>> {
+    let found = read()?;
+    let block = match block {
+        Some(block) if found.is_empty() => block,
+        _ => return Ok(found),
+    };
+
+    // 先登记再检查，登记之后加的消息一定会留下唤醒的许可
+    let notify = Arc::new(Notify::new());
+    let id = db.xwatch(keys, notify.clone());
+    let found = wait(db, shutdown, &notify, block, read).await;
+    db.xunwatch(keys, id);
+    found
+}
+
+async fn wait<T>(
+    db: &Db,
+    shutdown: &mut Shutdown,
+    notify: &Notify,
+    block: Duration,
+    mut read: impl FnMut() -> crate::Result<Vec<T>>,
+) -> crate::Result<Vec<T>> {
+    let sleep = sleep_timeout(block);
+    tokio::pin!(sleep);
+
+    loop {
+        let found = read()?;
+        if !found.is_empty() {
+            return Ok(found);
+        }
+
+        // 等待期间不占着许可，事务和脚本可以先执行
+        let notified = db.unlocked(async {
+            tokio::select! {
+                _ = notify.notified() => true,
+                _ = &mut sleep => false,
+                _ = shutdown.recv() => false,
+            }
+        }).await;
+        if !notified {
+            return Ok(vec![]);
+        }
+    }
+}
+
+// BLOCK的参数，单位是毫秒
+pub(crate) fn parse_block(parse: &mut Parse) -> crate::Result<Duration> {
+    let ms = parse.next_int().map_err(|_| "ERR timeout is not an integer or out of range")?;
+    if ms < 0 {
+        return Err("ERR timeout is negative".into());
+    }
+    Ok(Duration::from_millis(ms as u64))
+}
+
+// STREAMS后面的参数，前一半是key，后一半是对应的ID
+pub(crate) fn parse_streams(parse: &mut Parse, name: &str) -> crate::Result<(Vec<Bytes>, Vec<Bytes>)> {
+    let mut keys = vec![];
+    loop {
+        match parse.next_byte() {
+            Ok(arg) => keys.push(arg),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if keys.is_empty() || keys.len() % 2 != 0 {
+        return Err(format!(
+            "ERR Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be specified.",
+            name,
+            if name == "xread" { "$" } else { ">" },
+        ).into());
+    }
+
+    let ids = keys.split_off(keys.len() / 2);
+    Ok((keys, ids))
+}
+
+// `-`是最小的ID，`(`开头的是开区间，省略序号当成0
+pub(crate) fn parse_start(start: &[u8]) -> crate::Result<StreamId> {
+    match start {
+        b"-" => Ok(StreamId::MIN),
+        [b'(', id @ ..] => Ok(StreamId::parse(id, 0)
+            .ok_or(INVALID_ID)?
+            .next()
+            .ok_or("ERR invalid start ID for the interval")?),
+        id => Ok(StreamId::parse(id, 0).ok_or(INVALID_ID)?),
+    }
+}
+
+// `+`是最大的ID，省略序号当成最大
+pub(crate) fn parse_end(end: &[u8]) -> crate::Result<StreamId> {
+    match end {
+        b"+" => Ok(StreamId::MAX),
+        [b'(', id @ ..] => Ok(StreamId::parse(id, u64::MAX)
+            .ok_or(INVALID_ID)?
+            .prev()
+            .ok_or("ERR invalid end ID for the interval")?),
+        id => Ok(StreamId::parse(id, u64::MAX).ok_or(INVALID_ID)?),
+    }
+}
+
+// MAXLEN|MINID后面可以跟一个`=`或者`~`，然后是阈值
+fn parse_threshold(parse: &mut Parse, minid: bool) -> crate::Result<(TrimStrategy, bool)> {
+    let mut threshold = parse.next_byte()?;
+    let mut approx = false;
+    if &threshold[..] == b"~" || &threshold[..] == b"=" {
+        approx = &threshold[..] == b"~";
+        threshold = parse.next_byte()?;
+    }
+
+    let strategy = if minid {
+        TrimStrategy::MinId(StreamId::parse(&threshold, 0).ok_or(INVALID_ID)?)
+    } else {
+        let max = std::str::from_utf8(&threshold)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or("ERR value is not an integer or out of range")?;
+        if max < 0 {
+            return Err("ERR The MAXLEN argument must be >= 0.".into());
+        }
+        TrimStrategy::MaxLen(max as u64)
+    };
+    Ok((strategy, approx))
+}
+
+fn trim_options(strategy: TrimStrategy, approx: bool, limit: Option<u64>) -> crate::Result<TrimOptions> {
+    if limit.is_some() && !approx {
+        return Err("ERR syntax error, LIMIT cannot be used without the special ~ option".into());
+    }
+    Ok(TrimOptions { strategy, approx, limit })
+}
+
+fn push_trim(frame: &mut Frame, trim: &TrimOptions) {
+    match trim.strategy {
+        TrimStrategy::MaxLen(max) => {
+            frame.push_bulk(Bytes::from("MAXLEN".as_bytes()));
+            if trim.approx {
+                frame.push_bulk(Bytes::from("~".as_bytes()));
+            }
+            frame.push_bulk(Bytes::from(max.to_string()));
+        }
+        TrimStrategy::MinId(id) => {
+            frame.push_bulk(Bytes::from("MINID".as_bytes()));
+            if trim.approx {
+                frame.push_bulk(Bytes::from("~".as_bytes()));
+            }
+            frame.push_bulk(Bytes::from(id.to_string()));
+        }
+    }
+    if let Some(limit) = trim.limit {
+        frame.push_bulk(Bytes::from("LIMIT".as_bytes()));
+        frame.push_bulk(Bytes::from(limit.to_string()));
+    }
+}
+
+// XREAD和XREADGROUP的回复，RESP3下是key到消息的Map，没有流回复Null
+pub(crate) fn streams_frame(protocol: Protocol, found: impl ExactSizeIterator<Item = (Bytes, Frame)>) -> Frame {
+    if found.len() == 0 {
+        return Frame::Null;
+    }
+    match protocol {
+        Protocol::Resp3 => Frame::Map(found.map(|(key, entries)| (Frame::Bulk(key), entries)).collect()),
+        Protocol::Resp2 => Frame::Array(found.map(|(key, entries)| Frame::Array(vec![Frame::Bulk(key), entries])).collect()),
+    }
+}
+
+fn xread_frame(protocol: Protocol, found: crate::Result<Vec<(Bytes, Vec<StreamEntry>)>>) -> Frame {
+    match found {
+        Ok(found) => streams_frame(protocol, found.into_iter().map(|(key, entries)| (key, entries_frame(entries)))),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+pub(crate) fn entries_frame(entries: Vec<StreamEntry>) -> Frame {
+    Frame::Array(entries.into_iter().map(|(id, fields)| entry_frame(id, Some(fields))).collect())
+}
+
+// 每条消息回复成[id, [field, value, ...]]，消息已经被删掉的话是[id, Null]
+pub(crate) fn entry_frame(id: StreamId, fields: Option<Vec<(Bytes, Bytes)>>) -> Frame {
+    let fields = match fields {
+        Some(fields) => {
+            let mut pairs = Frame::array();
+            for (field, value) in fields {
+                pairs.push_bulk(field);
+                pairs.push_bulk(value);
+            }
+            pairs
+        }
+        None => Frame::Null,
+    };
+    Frame::Array(vec![Frame::Bulk(Bytes::from(id.to_string())), fields])
+}
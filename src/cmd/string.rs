@@ -0,0 +1,686 @@
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::frame::Frame;
+use crate::cmd::make_key_frame;
+
+// APPEND key value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Append {
+    key: Bytes,
+    value: Bytes,
+}
+
+// GETRANGE key start end
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetRange {
+    key: Bytes,
+    start: i64,
+    end: i64,
+}
+
+// SETRANGE key offset value
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetRange {
+    key: Bytes,
+    offset: u64,
+    value: Bytes,
+}
+
+// STRLEN key
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrLen {
+    key: Bytes,
+}
+
+// GETDEL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetDel {
+    key: Bytes,
+}
+
+// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetEx {
+    key: Bytes,
+    expiry: Option<Expiry>,
+}
+
+// MGET key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MGet {
+    keys: Vec<Bytes>,
+}
+
+// MSET key value [key value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSet {
+    pairs: Vec<(Bytes, Bytes)>,
+}
+
+// MSETNX key value [key value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSetNx {
+    pairs: Vec<(Bytes, Bytes)>,
+}
+
+// INCR key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incr {
+    key: Bytes,
+}
+
+// DECR key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decr {
+    key: Bytes,
+}
+
+// INCRBY key increment
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncrBy {
+    key: Bytes,
+    increment: i64,
+}
+
+// DECRBY key decrement
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecrBy {
+    key: Bytes,
+    decrement: i64,
+}
+
+// INCRBYFLOAT key increment
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncrByFloat {
+    key: Bytes,
+    increment: f64,
+}
+
+// 怎么设置过期时间，EX和EXAT解析的时候就换算成毫秒
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiry {
+    // EX/PX，从现在开始多久之后过期
+    In(Duration),
+    // EXAT/PXAT，unix时间戳(毫秒)
+    At(u64),
+    // PERSIST，去掉过期时间
+    Persist,
+}
+
+impl Expiry {
+    // 换算成过期的时刻，已经过去的时间戳就是现在
+    pub(crate) fn deadline(self) -> Option<Instant> {
+        match self {
+            Expiry::In(duration) => Some(Instant::now() + duration),
+            Expiry::At(ms) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Some(Instant::now() + Duration::from_millis(ms).saturating_sub(now))
+            }
+            Expiry::Persist => None,
+        }
+    }
+
+    pub(crate) fn push_frame(self, frame: &mut Frame) {
+        match self {
+            Expiry::In(duration) => {
+                frame.push_bulk(Bytes::from("PX".as_bytes()));
+                frame.push_bulk(Bytes::from(duration.as_millis().to_string()));
+            }
+            Expiry::At(ms) => {
+                frame.push_bulk(Bytes::from("PXAT".as_bytes()));
+                frame.push_bulk(Bytes::from(ms.to_string()));
+            }
+            Expiry::Persist => frame.push_bulk(Bytes::from("PERSIST".as_bytes())),
+        }
+    }
+}
+
+// 解析EX/PX/EXAT/PXAT选项，option不是这几个的时候返回None。
+// 时间必须大于0，换算成毫秒之后不能超过i64的范围
+pub(crate) fn parse_expiry(parse: &mut Parse, option: &str, command: &str) -> crate::Result<Option<Expiry>> {
+    let (seconds, at) = match option {
+        "EX" => (true, false),
+        "PX" => (false, false),
+        "EXAT" => (true, true),
+        "PXAT" => (false, true),
+        _ => return Ok(None),
+    };
+
+    let value = parse.next_int()?;
+    let ms = if seconds { value.checked_mul(1000) } else { Some(value) }
+        .filter(|ms| *ms > 0)
+        .ok_or_else(|| format!("ERR invalid expire time in '{}' command", command))? as u64;
+
+    if at {
+        Ok(Some(Expiry::At(ms)))
+    } else {
+        Ok(Some(Expiry::In(Duration::from_millis(ms))))
+    }
+}
+
+impl Append {
+    pub fn new(key: impl Into<Bytes>, value: Bytes) -> Self {
+        Self {
+            key: key.into(),
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Append> {
+        let key = parse.next_byte()?;
+        let value = parse.next_byte()?;
+
+        Ok(Self { key, value })
+    }
+
+    // 回复追加之后的长度
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.append(self.key, self.value) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("append", self.key);
+        frame.push_bulk(self.value);
+        frame
+    }
+}
+
+impl Incr {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Incr> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_incr(db, self.key, 1)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("incr", self.key)
+    }
+}
+
+impl Decr {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Decr> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_incr(db, self.key, -1)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("decr", self.key)
+    }
+}
+
+impl IncrBy {
+    pub fn new(key: impl Into<Bytes>, increment: i64) -> Self {
+        Self {
+            key: key.into(),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn increment(&self) -> i64 {
+        self.increment
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<IncrBy> {
+        let key = parse.next_byte()?;
+        let increment = parse.next_int()?;
+
+        Ok(Self { key, increment })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        execute_incr(db, self.key, self.increment)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("incrby", self.key);
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame
+    }
+}
+
+impl DecrBy {
+    pub fn new(key: impl Into<Bytes>, decrement: i64) -> Self {
+        Self {
+            key: key.into(),
+            decrement,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn decrement(&self) -> i64 {
+        self.decrement
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<DecrBy> {
+        let key = parse.next_byte()?;
+        let decrement = parse.next_int()?;
+
+        Ok(Self { key, decrement })
+    }
+
+    // i64::MIN取反会溢出
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.decrement.checked_neg() {
+            Some(increment) => execute_incr(db, self.key, increment),
+            None => Frame::Error("ERR decrement would overflow".to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("decrby", self.key);
+        frame.push_bulk(Bytes::from(self.decrement.to_string()));
+        frame
+    }
+}
+
+impl IncrByFloat {
+    pub fn new(key: impl Into<Bytes>, increment: f64) -> Self {
+        Self {
+            key: key.into(),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<IncrByFloat> {
+        let key = parse.next_byte()?;
+        let increment = std::str::from_utf8(&parse.next_byte()?)
+            .ok()
+            .and_then(|increment| increment.parse::<f64>().ok())
+            .filter(|increment| !increment.is_nan())
+            .ok_or("ERR value is not a valid float")?;
+
+        Ok(Self { key, increment })
+    }
+
+    // 浮点数按字符串回复
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.incrbyfloat(self.key, self.increment) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("incrbyfloat", self.key);
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame
+    }
+}
+
+impl GetRange {
+    pub fn new(key: impl Into<Bytes>, start: i64, end: i64) -> Self {
+        Self {
+            key: key.into(),
+            start,
+            end,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetRange> {
+        let key = parse.next_byte()?;
+        let start = parse.next_int()?;
+        let end = parse.next_int()?;
+
+        Ok(Self { key, start, end })
+    }
+
+    // key不存在或者范围是空的都回复空串
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.getrange(&self.key, self.start, self.end) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("getrange", self.key);
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+        frame
+    }
+}
+
+impl SetRange {
+    pub fn new(key: impl Into<Bytes>, offset: u64, value: Bytes) -> Self {
+        Self {
+            key: key.into(),
+            offset,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SetRange> {
+        let key = parse.next_byte()?;
+        let offset = u64::try_from(parse.next_int()?).map_err(|_| "ERR offset is out of range")?;
+        let value = parse.next_byte()?;
+
+        Ok(Self { key, offset, value })
+    }
+
+    // 回复修改之后的长度
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let offset = usize::try_from(self.offset).unwrap_or(usize::MAX);
+        match db.setrange(self.key, offset, self.value) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("setrange", self.key);
+        frame.push_bulk(Bytes::from(self.offset.to_string()));
+        frame.push_bulk(self.value);
+        frame
+    }
+}
+
+impl StrLen {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<StrLen> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.strlen(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("strlen", self.key)
+    }
+}
+
+impl GetDel {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetDel> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    // 不是字符串的key不会被删掉
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.getdel(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("getdel", self.key)
+    }
+}
+
+impl GetEx {
+    // 不带选项的时候和GET一样
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            expiry: None,
+        }
+    }
+
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn expiry(&self) -> Option<Expiry> {
+        self.expiry
+    }
+
+    // 最多只能有一个选项
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetEx> {
+        let key = parse.next_byte()?;
+
+        let mut expiry = None;
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_ascii_uppercase(),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            if expiry.is_some() {
+                return Err("ERR syntax error".into());
+            }
+            expiry = match &option[..] {
+                "PERSIST" => Some(Expiry::Persist),
+                option => Some(parse_expiry(parse, option, "getex")?.ok_or("ERR syntax error")?),
+            };
+        }
+
+        Ok(Self { key, expiry })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.getex(&self.key, self.expiry.map(Expiry::deadline)) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("getex", self.key);
+        if let Some(expiry) = self.expiry {
+            expiry.push_frame(&mut frame);
+        }
+        frame
+    }
+}
+
+impl MGet {
+    pub fn new(keys: Vec<Bytes>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<MGet> {
+        let mut keys = vec![parse.next_byte()?];
+        loop {
+            match parse.next_byte() {
+                Ok(key) => keys.push(key),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    // 不存在的key和不是字符串的key都回复Null，不会报WRONGTYPE
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let values = db.mget(&self.keys);
+        Frame::Array(values.into_iter().map(|value| value.map_or(Frame::Null, Frame::Bulk)).collect())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("mget".as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(key);
+        }
+        frame
+    }
+}
+
+impl MSet {
+    pub fn new(pairs: Vec<(Bytes, Bytes)>) -> Self {
+        Self { pairs }
+    }
+
+    pub fn pairs(&self) -> &[(Bytes, Bytes)] {
+        &self.pairs
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<MSet> {
+        let pairs = parse_pairs(parse)?;
+        Ok(Self { pairs })
+    }
+
+    // 同一个key出现多次的话后面的值生效
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        db.mset(self.pairs, false);
+        Frame::Simple("OK".to_string())
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pairs_frame("mset", self.pairs)
+    }
+}
+
+impl MSetNx {
+    pub fn new(pairs: Vec<(Bytes, Bytes)>) -> Self {
+        Self { pairs }
+    }
+
+    pub fn pairs(&self) -> &[(Bytes, Bytes)] {
+        &self.pairs
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<MSetNx> {
+        let pairs = parse_pairs(parse)?;
+        Ok(Self { pairs })
+    }
+
+    // 所有的key都不存在才设置，回复1，否则什么都不做回复0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.mset(self.pairs, true) as i64)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_pairs_frame("msetnx", self.pairs)
+    }
+}
+
+// INCR/DECR/INCRBY/DECRBY都是加上一个有符号的数，回复加完之后的值
+fn execute_incr(db: &Db, key: Bytes, increment: i64) -> Frame {
+    match db.incrby(key, increment) {
+        Ok(value) => Frame::Integer(value),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+// 至少一对key和值，key和值要成对出现
+fn parse_pairs(parse: &mut Parse) -> crate::Result<Vec<(Bytes, Bytes)>> {
+    let mut pairs = vec![(parse.next_byte()?, parse.next_byte()?)];
+    loop {
+        match parse.next_byte() {
+            Ok(key) => pairs.push((key, parse.next_byte()?)),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(pairs)
+}
+
+fn make_pairs_frame(name: &str, pairs: Vec<(Bytes, Bytes)>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    for (key, value) in pairs {
+        frame.push_bulk(key);
+        frame.push_bulk(value);
+    }
+    frame
+}
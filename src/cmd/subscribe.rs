@@ -0,0 +1,372 @@
+use bytes::Bytes;
+use std::pin::Pin;
+use tokio::stream::{Stream, StreamExt, StreamMap};
+use tokio::sync::broadcast;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::shutdown::Shutdown;
+use crate::cmd::Command;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscribe {
+    channels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsubscribe {
+    channels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PSubscribe {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PUnsubscribe {
+    patterns: Vec<String>,
+}
+
+// 每个频道的消息流
+type Messages = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+
+// 每个模式的消息流，带上实际的频道名
+type PMessages = Pin<Box<dyn Stream<Item = (String, Bytes)> + Send>>;
+
+// 订阅模式下当前连接的所有订阅
+struct Subscriptions {
+    channels: StreamMap<String, Messages>,
+    patterns: StreamMap<String, PMessages>,
+}
+
+impl Subscribe {
+    pub fn new(channels: &[String]) -> Self {
+        Self {
+            channels: channels.to_vec()
+        }
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    // 至少要订阅一个频道
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Subscribe> {
+        let mut channels = vec![parse.next_string()?];
+        channels.extend(parse_names(parse)?);
+
+        Ok(Self { channels })
+    }
+
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        Subscriptions::new().run(db, dst, shutdown, self.channels, vec![]).await
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_command_frame("subscribe", self.channels)
+    }
+}
+
+impl Unsubscribe {
+    pub fn new(channels: &[String]) -> Self {
+        Self {
+            channels: channels.to_vec()
+        }
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    // 不带频道表示取消所有订阅
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Unsubscribe> {
+        let channels = parse_names(parse)?;
+
+        Ok(Self { channels })
+    }
+
+    // 没在订阅模式下，直接告诉客户端没有订阅任何频道
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        not_subscribed(b"unsubscribe", self.channels, dst).await
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_command_frame("unsubscribe", self.channels)
+    }
+}
+
+impl PSubscribe {
+    pub fn new(patterns: &[String]) -> Self {
+        Self {
+            patterns: patterns.to_vec()
+        }
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    // 至少要订阅一个模式
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PSubscribe> {
+        let mut patterns = vec![parse.next_string()?];
+        patterns.extend(parse_names(parse)?);
+
+        Ok(Self { patterns })
+    }
+
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()> {
+        Subscriptions::new().run(db, dst, shutdown, vec![], self.patterns).await
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_command_frame("psubscribe", self.patterns)
+    }
+}
+
+impl PUnsubscribe {
+    pub fn new(patterns: &[String]) -> Self {
+        Self {
+            patterns: patterns.to_vec()
+        }
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    // 不带模式表示取消所有模式订阅
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PUnsubscribe> {
+        let patterns = parse_names(parse)?;
+
+        Ok(Self { patterns })
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        not_subscribed(b"punsubscribe", self.patterns, dst).await
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_command_frame("punsubscribe", self.patterns)
+    }
+}
+
+impl Subscriptions {
+    fn new() -> Self {
+        Self {
+            channels: StreamMap::new(),
+            patterns: StreamMap::new(),
+        }
+    }
+
+    // 频道和模式加起来的订阅数
+    fn len(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    // 进入订阅模式，直到取消了所有订阅、客户端断开或者服务关闭才返回
+    async fn run(
+        mut self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+        mut subscribe_to: Vec<String>,
+        mut psubscribe_to: Vec<String>,
+    ) -> crate::Result<()> {
+        loop {
+            for channel_name in subscribe_to.drain(..) {
+                self.subscribe(channel_name, db, dst).await?;
+            }
+            for pattern in psubscribe_to.drain(..) {
+                self.psubscribe(pattern, db, dst).await?;
+            }
+
+            tokio::select! {
+                Some((channel_name, msg)) = self.channels.next() => {
+                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
+                }
+                Some((pattern, (channel_name, msg))) = self.patterns.next() => {
+                    dst.write_frame(&make_pmessage_frame(pattern, channel_name, msg)).await?;
+                }
+                res = dst.read_frame() => {
+                    let frame = match res? {
+                        Some(frame) => frame,
+                        None => return Ok(())
+                    };
+                    self.handle_command(frame, &mut subscribe_to, &mut psubscribe_to, dst).await?;
+                }
+                _ = shutdown.recv() => {
+                    return Ok(());
+                }
+            }
+
+            // 全部取消订阅之后退出订阅模式
+            if self.len() == 0 && subscribe_to.is_empty() && psubscribe_to.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, channel_name: String, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let mut rx = db.subscribe(channel_name.clone());
+
+        // 把broadcast的receiver包装成stream，消费太慢丢掉的消息直接跳过
+        let rx = Box::pin(async_stream::stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(msg) => yield msg,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+
+        self.channels.insert(channel_name.clone(), rx);
+
+        let response = make_reply_frame(b"subscribe", channel_name, self.len());
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    async fn psubscribe(&mut self, pattern: String, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let mut rx = db.psubscribe(pattern.clone());
+
+        let rx = Box::pin(async_stream::stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(msg) => yield msg,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+
+        self.patterns.insert(pattern.clone(), rx);
+
+        let response = make_reply_frame(b"psubscribe", pattern, self.len());
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    // 订阅模式下只能继续订阅或者取消订阅
+    async fn handle_command(
+        &mut self,
+        frame: Frame,
+        subscribe_to: &mut Vec<String>,
+        psubscribe_to: &mut Vec<String>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let command = match Command::from_frame(frame) {
+            Ok(command) => command,
+            Err(err) => {
+                dst.write_frame(&Frame::Error(err.to_string())).await?;
+                return Ok(());
+            }
+        };
+
+        match command {
+            Command::Subscribe(subscribe) => {
+                subscribe_to.extend(subscribe.channels);
+            }
+            Command::PSubscribe(psubscribe) => {
+                psubscribe_to.extend(psubscribe.patterns);
+            }
+            Command::Unsubscribe(mut unsubscribe) => {
+                if unsubscribe.channels.is_empty() {
+                    unsubscribe.channels = self.channels.keys().map(|k| k.to_string()).collect();
+                }
+
+                for channel_name in unsubscribe.channels {
+                    self.channels.remove(&channel_name);
+                    let response = make_reply_frame(b"unsubscribe", channel_name, self.len());
+                    dst.write_frame(&response).await?;
+                }
+            }
+            Command::PUnsubscribe(mut punsubscribe) => {
+                if punsubscribe.patterns.is_empty() {
+                    punsubscribe.patterns = self.patterns.keys().map(|k| k.to_string()).collect();
+                }
+
+                for pattern in punsubscribe.patterns {
+                    self.patterns.remove(&pattern);
+                    let response = make_reply_frame(b"punsubscribe", pattern, self.len());
+                    dst.write_frame(&response).await?;
+                }
+            }
+            command => {
+                let response = Frame::Error(format!(
+                    "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE are allowed in this context",
+                    command.get_name()
+                ));
+                dst.write_frame(&response).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 把剩下的参数都当成频道名或者模式
+fn parse_names(parse: &mut Parse) -> crate::Result<Vec<String>> {
+    let mut names = vec![];
+    loop {
+        match parse.next_string() {
+            Ok(s) => names.push(s),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(names)
+}
+
+// 不在订阅模式下取消订阅，每个名字回一条订阅数为0的确认
+async fn not_subscribed(kind: &'static [u8], names: Vec<String>, dst: &mut Connection) -> crate::Result<()> {
+    if names.is_empty() {
+        let response = Frame::Push(vec![
+            Frame::Bulk(Bytes::from_static(kind)),
+            Frame::Null,
+            Frame::Integer(0),
+        ]);
+        dst.write_frame(&response).await?;
+    }
+
+    for name in names {
+        dst.write_frame(&make_reply_frame(kind, name, 0)).await?;
+    }
+    Ok(())
+}
+
+fn make_command_frame(name: &str, args: Vec<String>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes().to_vec()));
+    for arg in args {
+        frame.push_bulk(Bytes::from(arg.into_bytes()));
+    }
+    frame
+}
+
+// (p)subscribe 和 (p)unsubscribe 的确认
+fn make_reply_frame(kind: &'static [u8], name: String, num_subs: usize) -> Frame {
+    let mut response = Frame::push();
+    response.push_bulk(Bytes::from_static(kind));
+    response.push_bulk(Bytes::from(name));
+    response.push_int(num_subs as i64);
+    response
+}
+
+fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
+    let mut response = Frame::push();
+    response.push_bulk(Bytes::from_static(b"message"));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(msg);
+    response
+}
+
+fn make_pmessage_frame(pattern: String, channel_name: String, msg: Bytes) -> Frame {
+    let mut response = Frame::push();
+    response.push_bulk(Bytes::from_static(b"pmessage"));
+    response.push_bulk(Bytes::from(pattern));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(msg);
+    response
+}
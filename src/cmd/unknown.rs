@@ -1,17 +1,31 @@
-#[derive(Debug)]
+use bytes::Bytes;
+use crate::frame::Frame;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Unknown {
     command_name: String
 }
 
 impl Unknown {
-    pub(crate) fn new(key: impl ToString) -> Self {
+    pub fn new(key: impl ToString) -> Self {
         Self {
             command_name: key.to_string()
         }
     }
 
-    pub(crate) fn get_name(&self) -> &str {
+    pub fn get_name(&self) -> &str {
         &self.command_name
     }
-}
 
+    // 不认识的命令直接回一个错误
+    pub(crate) fn execute(self) -> Frame {
+        Frame::Error(format!("ERR unknown command '{}'", self.command_name))
+    }
+
+    // 参数在解析的时候就丢掉了，这里只能还原出命令名
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.command_name.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,648 @@
+use bytes::Bytes;
+use crate::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::connection::Protocol;
+use crate::frame::Frame;
+use crate::cmd::make_key_frame;
+use crate::zset::{ScoreBound, LexBound, RangeBy, ZAddOptions, ZAddResult};
+
+// ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZAdd {
+    key: Bytes,
+    options: ZAddOptions,
+    pairs: Vec<(f64, Bytes)>,
+}
+
+// ZINCRBY key increment member
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZIncrBy {
+    key: Bytes,
+    increment: f64,
+    member: Bytes,
+}
+
+// ZREM key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+// ZSCORE key member
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZScore {
+    key: Bytes,
+    member: Bytes,
+}
+
+// ZCARD key
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZCard {
+    key: Bytes,
+}
+
+// ZRANK key member
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRank {
+    key: Bytes,
+    member: Bytes,
+}
+
+// ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRange {
+    key: Bytes,
+    by: RangeBy,
+    rev: bool,
+    limit: Option<(i64, i64)>,
+    withscores: bool,
+}
+
+// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRangeByScore {
+    key: Bytes,
+    min: ScoreBound,
+    max: ScoreBound,
+    withscores: bool,
+    limit: Option<(i64, i64)>,
+}
+
+impl ZAdd {
+    pub fn new(key: impl Into<Bytes>, options: ZAddOptions, pairs: Vec<(f64, Bytes)>) -> Self {
+        Self {
+            key: key.into(),
+            options,
+            pairs,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn options(&self) -> &ZAddOptions {
+        &self.options
+    }
+
+    pub fn pairs(&self) -> &[(f64, Bytes)] {
+        &self.pairs
+    }
+
+    // 选项都在分数前面，第一个不是选项的参数就是分数了
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZAdd> {
+        let key = parse.next_byte()?;
+        let mut options = ZAddOptions::default();
+
+        let score = loop {
+            let arg = parse.next_byte()?;
+            match &arg.to_ascii_uppercase()[..] {
+                b"NX" => options.nx = true,
+                b"XX" => options.xx = true,
+                b"GT" => options.gt = true,
+                b"LT" => options.lt = true,
+                b"CH" => options.ch = true,
+                b"INCR" => options.incr = true,
+                _ => break parse_score(&arg)?,
+            }
+        };
+
+        let mut pairs = vec![(score, parse.next_byte()?)];
+        loop {
+            match parse.next_byte() {
+                Ok(score) => pairs.push((parse_score(&score)?, parse.next_byte()?)),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if options.nx && options.xx {
+            return Err("ERR XX and NX options at the same time are not compatible".into());
+        }
+        if (options.gt && options.lt) || (options.nx && (options.gt || options.lt)) {
+            return Err("ERR GT, LT, and/or NX options at the same time are not compatible".into());
+        }
+        if options.incr && pairs.len() > 1 {
+            return Err("ERR INCR option supports a single increment-element pair".into());
+        }
+
+        Ok(Self { key, options, pairs })
+    }
+
+    // 回复新加的成员个数(CH的时候加上分数变了的)，INCR的时候和ZINCRBY一样回复新的分数
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let options = self.options;
+        match db.zadd(self.key, &options, self.pairs) {
+            Ok(results) if options.incr => match results[0] {
+                ZAddResult::Added(score) | ZAddResult::Updated(score) | ZAddResult::Unchanged(score) => {
+                    Frame::Double(score)
+                }
+                ZAddResult::Skipped => Frame::Null,
+            },
+            Ok(results) => {
+                let num = results
+                    .iter()
+                    .filter(|result| match result {
+                        ZAddResult::Added(_) => true,
+                        ZAddResult::Updated(_) => options.ch,
+                        _ => false,
+                    })
+                    .count();
+                Frame::Integer(num as i64)
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zadd", self.key);
+        let flags = [
+            (self.options.nx, "NX"),
+            (self.options.xx, "XX"),
+            (self.options.gt, "GT"),
+            (self.options.lt, "LT"),
+            (self.options.ch, "CH"),
+            (self.options.incr, "INCR"),
+        ];
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            frame.push_bulk(Bytes::from(flag.as_bytes()));
+        }
+        for (score, member) in self.pairs {
+            frame.push_bulk(Bytes::from(score.to_string()));
+            frame.push_bulk(member);
+        }
+        frame
+    }
+}
+
+impl ZIncrBy {
+    pub fn new(key: impl Into<Bytes>, increment: f64, member: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            increment,
+            member: member.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    pub fn member(&self) -> &Bytes {
+        &self.member
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZIncrBy> {
+        let key = parse.next_byte()?;
+        let increment = parse_score(&parse.next_byte()?)?;
+        let member = parse.next_byte()?;
+
+        Ok(Self { key, increment, member })
+    }
+
+    // 回复加完之后的分数，成员不存在的时候当成0
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let options = ZAddOptions { incr: true, ..ZAddOptions::default() };
+        match db.zadd(self.key, &options, vec![(self.increment, self.member)]) {
+            Ok(results) => match results[0] {
+                ZAddResult::Added(score) | ZAddResult::Updated(score) | ZAddResult::Unchanged(score) => {
+                    Frame::Double(score)
+                }
+                ZAddResult::Skipped => Frame::Null,
+            },
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zincrby", self.key);
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame.push_bulk(self.member);
+        frame
+    }
+}
+
+impl ZRem {
+    pub fn new(key: impl Into<Bytes>, members: Vec<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            members,
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn members(&self) -> &[Bytes] {
+        &self.members
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZRem> {
+        let key = parse.next_byte()?;
+        let mut members = vec![parse.next_byte()?];
+        loop {
+            match parse.next_byte() {
+                Ok(member) => members.push(member),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self { key, members })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zrem(&self.key, &self.members) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zrem", self.key);
+        for member in self.members {
+            frame.push_bulk(member);
+        }
+        frame
+    }
+}
+
+impl ZScore {
+    pub fn new(key: impl Into<Bytes>, member: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            member: member.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn member(&self) -> &Bytes {
+        &self.member
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZScore> {
+        let key = parse.next_byte()?;
+        let member = parse.next_byte()?;
+
+        Ok(Self { key, member })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zscore(&self.key, &self.member) {
+            Ok(score) => score.map_or(Frame::Null, Frame::Double),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zscore", self.key);
+        frame.push_bulk(self.member);
+        frame
+    }
+}
+
+impl ZCard {
+    pub fn new(key: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZCard> {
+        let key = parse.next_byte()?;
+
+        Ok(Self { key })
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zcard(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        make_key_frame("zcard", self.key)
+    }
+}
+
+impl ZRank {
+    pub fn new(key: impl Into<Bytes>, member: impl Into<Bytes>) -> Self {
+        Self {
+            key: key.into(),
+            member: member.into(),
+        }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn member(&self) -> &Bytes {
+        &self.member
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZRank> {
+        let key = parse.next_byte()?;
+        let member = parse.next_byte()?;
+
+        Ok(Self { key, member })
+    }
+
+    // 按分数从小到大的排名，从0开始，成员不存在回复Null
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zrank(&self.key, &self.member) {
+            Ok(rank) => rank.map_or(Frame::Null, |rank| Frame::Integer(rank as i64)),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zrank", self.key);
+        frame.push_bulk(self.member);
+        frame
+    }
+}
+
+impl ZRange {
+    pub fn new(key: impl Into<Bytes>, by: RangeBy) -> Self {
+        Self {
+            key: key.into(),
+            by,
+            rev: false,
+            limit: None,
+            withscores: false,
+        }
+    }
+
+    // 从大到小，BYSCORE和BYLEX的时候参数要先传max再传min
+    pub fn with_rev(mut self) -> Self {
+        self.rev = true;
+        self
+    }
+
+    // 只能和BYSCORE或者BYLEX一起用，count为负数表示不限制
+    pub fn with_limit(mut self, offset: i64, count: i64) -> Self {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    // 不能和BYLEX一起用
+    pub fn with_scores(mut self) -> Self {
+        self.withscores = true;
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn by(&self) -> &RangeBy {
+        &self.by
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZRange> {
+        let key = parse.next_byte()?;
+        let start = parse.next_byte()?;
+        let stop = parse.next_byte()?;
+
+        let (mut byscore, mut bylex, mut rev, mut limit, mut withscores) = (false, false, false, None, false);
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.eq_ignore_ascii_case("BYSCORE") => byscore = true,
+                Ok(s) if s.eq_ignore_ascii_case("BYLEX") => bylex = true,
+                Ok(s) if s.eq_ignore_ascii_case("REV") => rev = true,
+                Ok(s) if s.eq_ignore_ascii_case("LIMIT") => limit = Some(parse_limit(parse)?),
+                Ok(s) if s.eq_ignore_ascii_case("WITHSCORES") => withscores = true,
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if byscore && bylex {
+            return Err("ERR syntax error".into());
+        }
+        if limit.is_some() && !byscore && !bylex {
+            return Err("ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX".into());
+        }
+        if withscores && bylex {
+            return Err("ERR syntax error, WITHSCORES not supported in combination with BYLEX".into());
+        }
+
+        // 倒序的时候先传的是上界
+        let (min, max) = if rev && (byscore || bylex) { (stop, start) } else { (start, stop) };
+        let by = if byscore {
+            RangeBy::Score(parse_score_bound(&min)?, parse_score_bound(&max)?)
+        } else if bylex {
+            RangeBy::Lex(parse_lex_bound(&min)?, parse_lex_bound(&max)?)
+        } else {
+            RangeBy::Rank(parse_index(&min)?, parse_index(&max)?)
+        };
+
+        Ok(Self { key, by, rev, limit, withscores })
+    }
+
+    pub(crate) fn execute(self, db: &Db, protocol: Protocol) -> Frame {
+        execute_range(db, protocol, &self.key, &self.by, self.rev, self.limit, self.withscores)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zrange", self.key);
+        let (first, second, by) = match self.by {
+            RangeBy::Rank(start, stop) => (Bytes::from(start.to_string()), Bytes::from(stop.to_string()), None),
+            RangeBy::Score(min, max) => (format_score_bound(min), format_score_bound(max), Some("BYSCORE")),
+            RangeBy::Lex(min, max) => (format_lex_bound(min), format_lex_bound(max), Some("BYLEX")),
+        };
+        if self.rev && by.is_some() {
+            frame.push_bulk(second);
+            frame.push_bulk(first);
+        } else {
+            frame.push_bulk(first);
+            frame.push_bulk(second);
+        }
+        if let Some(by) = by {
+            frame.push_bulk(Bytes::from(by.as_bytes()));
+        }
+        if self.rev {
+            frame.push_bulk(Bytes::from("REV".as_bytes()));
+        }
+        if let Some((offset, count)) = self.limit {
+            push_limit(&mut frame, offset, count);
+        }
+        if self.withscores {
+            frame.push_bulk(Bytes::from("WITHSCORES".as_bytes()));
+        }
+        frame
+    }
+}
+
+impl ZRangeByScore {
+    pub fn new(key: impl Into<Bytes>, min: ScoreBound, max: ScoreBound) -> Self {
+        Self {
+            key: key.into(),
+            min,
+            max,
+            withscores: false,
+            limit: None,
+        }
+    }
+
+    pub fn with_scores(mut self) -> Self {
+        self.withscores = true;
+        self
+    }
+
+    pub fn with_limit(mut self, offset: i64, count: i64) -> Self {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ZRangeByScore> {
+        let key = parse.next_byte()?;
+        let min = parse_score_bound(&parse.next_byte()?)?;
+        let max = parse_score_bound(&parse.next_byte()?)?;
+        let mut cmd = ZRangeByScore::new(key, min, max);
+
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.eq_ignore_ascii_case("WITHSCORES") => cmd.withscores = true,
+                Ok(s) if s.eq_ignore_ascii_case("LIMIT") => cmd.limit = Some(parse_limit(parse)?),
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    pub(crate) fn execute(self, db: &Db, protocol: Protocol) -> Frame {
+        let by = RangeBy::Score(self.min, self.max);
+        execute_range(db, protocol, &self.key, &by, false, self.limit, self.withscores)
+    }
+
+    pub fn into_frame(self) -> Frame {
+        let mut frame = make_key_frame("zrangebyscore", self.key);
+        frame.push_bulk(format_score_bound(self.min));
+        frame.push_bulk(format_score_bound(self.max));
+        if self.withscores {
+            frame.push_bulk(Bytes::from("WITHSCORES".as_bytes()));
+        }
+        if let Some((offset, count)) = self.limit {
+            push_limit(&mut frame, offset, count);
+        }
+        frame
+    }
+}
+
+fn parse_score(score: &[u8]) -> crate::Result<f64> {
+    std::str::from_utf8(score)
+        .ok()
+        .and_then(|score| score.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| "ERR value is not a valid float".into())
+}
+
+fn parse_index(index: &[u8]) -> crate::Result<i64> {
+    std::str::from_utf8(index)
+        .ok()
+        .and_then(|index| index.parse::<i64>().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".into())
+}
+
+// `(`开头表示不包含，可以是`-inf`和`+inf`
+fn parse_score_bound(bound: &[u8]) -> crate::Result<ScoreBound> {
+    let (exclusive, score) = match bound.first() {
+        Some(b'(') => (true, &bound[1..]),
+        _ => (false, bound),
+    };
+    let score = parse_score(score).map_err(|_| "ERR min or max is not a float")?;
+
+    Ok(if exclusive { ScoreBound::Exclusive(score) } else { ScoreBound::Inclusive(score) })
+}
+
+fn format_score_bound(bound: ScoreBound) -> Bytes {
+    match bound {
+        ScoreBound::Inclusive(score) => Bytes::from(score.to_string()),
+        ScoreBound::Exclusive(score) => Bytes::from(format!("({}", score)),
+    }
+}
+
+// 必须是`-`、`+`或者`[`、`(`开头
+fn parse_lex_bound(bound: &Bytes) -> crate::Result<LexBound> {
+    match bound.first() {
+        Some(b'-') if bound.len() == 1 => Ok(LexBound::Min),
+        Some(b'+') if bound.len() == 1 => Ok(LexBound::Max),
+        Some(b'[') => Ok(LexBound::Inclusive(bound.slice(1..))),
+        Some(b'(') => Ok(LexBound::Exclusive(bound.slice(1..))),
+        _ => Err("ERR min or max not valid string range item".into()),
+    }
+}
+
+fn format_lex_bound(bound: LexBound) -> Bytes {
+    match bound {
+        LexBound::Min => Bytes::from_static(b"-"),
+        LexBound::Max => Bytes::from_static(b"+"),
+        LexBound::Inclusive(member) => Bytes::from([&b"["[..], &member[..]].concat()),
+        LexBound::Exclusive(member) => Bytes::from([&b"("[..], &member[..]].concat()),
+    }
+}
+
+fn parse_limit(parse: &mut Parse) -> crate::Result<(i64, i64)> {
+    let offset = parse.next_int()?;
+    let count = parse.next_int()?;
+    Ok((offset, count))
+}
+
+fn push_limit(frame: &mut Frame, offset: i64, count: i64) {
+    frame.push_bulk(Bytes::from("LIMIT".as_bytes()));
+    frame.push_bulk(Bytes::from(offset.to_string()));
+    frame.push_bulk(Bytes::from(count.to_string()));
+}
+
+// 带分数的时候，RESP3下每个成员和分数是一个数组，RESP2下是平铺的
+fn execute_range(
+    db: &Db,
+    protocol: Protocol,
+    key: &[u8],
+    by: &RangeBy,
+    rev: bool,
+    limit: Option<(i64, i64)>,
+    withscores: bool,
+) -> Frame {
+    match db.zrange(key, by, rev, limit) {
+        Ok(members) if withscores => {
+            let mut items = Vec::with_capacity(members.len() * 2);
+            for (member, score) in members {
+                match protocol {
+                    Protocol::Resp3 => items.push(Frame::Array(vec![Frame::Bulk(member), Frame::Double(score)])),
+                    Protocol::Resp2 => {
+                        items.push(Frame::Bulk(member));
+                        items.push(Frame::Double(score));
+                    }
+                }
+            }
+            Frame::Array(items)
+        }
+        Ok(members) => Frame::Array(members.into_iter().map(|(member, _)| Frame::Bulk(member)).collect()),
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
@@ -9,6 +9,16 @@ use std::io::Cursor;
 pub struct Connection {
     stream: BufWriter<TcpStream>,
     buffer: BytesMut,
+    protocol: Protocol,
+    // EXEC和脚本执行期间的回复先攒在这里，脚本可能在EXEC里执行，所以是一层层的
+    captures: Vec<Vec<Frame>>,
+}
+
+// 协议版本，决定响应怎么序列化，新连接默认是RESP2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Resp2,
+    Resp3,
 }
 
 impl Connection {
@@ -16,6 +26,8 @@ impl Connection {
         Self {
             stream: BufWriter::new(stream),
             buffer: BytesMut::with_capacity(1024 * 4),
+            protocol: Protocol::Resp2,
+            captures: vec![],
         }
     }
 
@@ -33,9 +45,6 @@ impl Connection {
                 }
             }
         }
-
-
-        Ok(Some(Frame::Null))
     }
 
     fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
@@ -57,60 +66,160 @@ impl Connection {
         }
     }
 
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    // HELLO 协商之后切换序列化的协议版本
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+    }
+
+    // 之后写的frame都不发出去，等finish_capture的时候一起拿走
+    pub(crate) fn start_capture(&mut self) {
+        self.captures.push(vec![]);
+    }
+
+    pub(crate) fn finish_capture(&mut self) -> Vec<Frame> {
+        self.captures.pop().unwrap_or_default()
+    }
+
     pub async fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
-        match frame {
-            Frame::Array(val) => {
-                self.stream.write_u8(b'*').await?;
-                self.write_decimal(val.len() as u64).await?;
-                for entry in &**val {
-                    self.write_value(entry).await?;
-                }
-            }
-            _ => self.write_value(frame).await?
+        if let Some(replies) = self.captures.last_mut() {
+            replies.push(frame.clone());
+            return Ok(());
         }
+
+        // 先整个编码到内存里再一次性写出去，嵌套的frame处理起来简单
+        let mut buf = Vec::new();
+        self.write_value(&mut buf, frame)?;
+        self.stream.write_all(&buf).await?;
         self.stream.flush().await
     }
 
-    async fn write_value(&mut self, frame: &Frame) -> std::io::Result<()> {
+    fn write_value(&self, buf: &mut Vec<u8>, frame: &Frame) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let resp3 = self.protocol == Protocol::Resp3;
         match frame {
             Frame::Simple(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
+                write!(buf, "+{}\r\n", val)?;
             }
             Frame::Error(val) => {
-                self.stream.write_u8(b'-').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
+                write!(buf, "-{}\r\n", val)?;
             }
             Frame::Integer(val) => {
-                self.stream.write_u8(b':').await?;
-                self.write_decimal(*val).await?;
+                write!(buf, ":{}\r\n", val)?;
+            }
+            Frame::Null if resp3 => {
+                buf.extend_from_slice(b"_\r\n");
             }
             Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
+                buf.extend_from_slice(b"$-1\r\n");
             }
             Frame::Bulk(val) => {
-                let len = val.len();
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as u64).await?;
-                self.stream.write_all(val).await?;
-                self.stream.write_all(b"\r\n").await?;
+                write_blob(buf, b'$', val)?;
+            }
+            Frame::Array(val) => {
+                self.write_aggregate(buf, b'*', val)?;
+            }
+            Frame::Boolean(val) if resp3 => {
+                buf.extend_from_slice(if *val { b"#t\r\n" } else { b"#f\r\n" });
+            }
+            // RESP2 里面布尔值用0和1表示
+            Frame::Boolean(val) => {
+                write!(buf, ":{}\r\n", *val as u8)?;
+            }
+            Frame::Double(val) if resp3 => {
+                write!(buf, ",{}\r\n", format_double(*val))?;
+            }
+            Frame::Double(val) => {
+                write_blob(buf, b'$', format_double(*val).as_bytes())?;
+            }
+            Frame::BigNumber(val) if resp3 => {
+                write!(buf, "({}\r\n", val)?;
+            }
+            Frame::BigNumber(val) => {
+                write_blob(buf, b'$', val.as_bytes())?;
+            }
+            Frame::Verbatim(format, val) if resp3 => {
+                write!(buf, "={}\r\n{}:", val.len() + 4, format)?;
+                buf.extend_from_slice(val);
+                buf.extend_from_slice(b"\r\n");
+            }
+            Frame::Verbatim(_, val) => {
+                write_blob(buf, b'$', val)?;
+            }
+            Frame::Map(val) if resp3 => {
+                self.write_pairs(buf, b'%', val)?;
+            }
+            // RESP2 里面map展开成 key value key value 的数组
+            Frame::Map(val) => {
+                write!(buf, "*{}\r\n", val.len() * 2)?;
+                for (key, value) in val {
+                    self.write_value(buf, key)?;
+                    self.write_value(buf, value)?;
+                }
+            }
+            Frame::Set(val) => {
+                self.write_aggregate(buf, if resp3 { b'~' } else { b'*' }, val)?;
+            }
+            Frame::Attribute(attributes, data) if resp3 => {
+                self.write_pairs(buf, b'|', attributes)?;
+                self.write_value(buf, data)?;
+            }
+            // RESP2 没有属性，直接丢掉
+            Frame::Attribute(_, data) => {
+                self.write_value(buf, data)?;
+            }
+            Frame::Push(val) => {
+                self.write_aggregate(buf, if resp3 { b'>' } else { b'*' }, val)?;
             }
-            Frame::Array(_val) => unreachable!()
         }
         Ok(())
     }
 
-    async fn write_decimal(&mut self, val: u64) -> std::io::Result<()> {
+    fn write_aggregate(&self, buf: &mut Vec<u8>, prefix: u8, val: &[Frame]) -> std::io::Result<()> {
         use std::io::Write;
 
-        let mut buff = [0u8; 12];
-        let mut buff = Cursor::new(&mut buff[..]);
-        write!(buff, "{}", val)?;
-        let position = buff.position() as usize;
-        self.stream.write_all(&buff.get_ref()[..position]).await?;
-        self.stream.write_all(b"\r\n").await?;
+        buf.push(prefix);
+        write!(buf, "{}\r\n", val.len())?;
+        for entry in val {
+            self.write_value(buf, entry)?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn write_pairs(&self, buf: &mut Vec<u8>, prefix: u8, val: &[(Frame, Frame)]) -> std::io::Result<()> {
+        use std::io::Write;
+
+        buf.push(prefix);
+        write!(buf, "{}\r\n", val.len())?;
+        for (key, value) in val {
+            self.write_value(buf, key)?;
+            self.write_value(buf, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_blob(buf: &mut Vec<u8>, prefix: u8, val: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    buf.push(prefix);
+    write!(buf, "{}\r\n", val.len())?;
+    buf.extend_from_slice(val);
+    buf.extend_from_slice(b"\r\n");
+    Ok(())
+}
+
+// 和redis一样，无穷大和NaN用 inf、-inf、nan 表示
+pub(crate) fn format_double(val: f64) -> String {
+    if val.is_nan() {
+        "nan".to_string()
+    } else if val.is_infinite() {
+        if val > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+    } else {
+        val.to_string()
+    }
+}
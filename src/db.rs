@@ -1,50 +1,279 @@
-use tokio::sync::{Notify, broadcast};
-use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, broadcast, oneshot, watch};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::ops::{Deref, DerefMut};
 use bytes::Bytes;
 use tokio::time::{Instant, Duration};
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, VecDeque};
+use crate::glob;
+use crate::scan_map::ScanMap;
+use crate::zset::{ZSet, ZAddOptions, ZAddResult, RangeBy};
+use crate::stream::{Stream, StreamId, StreamEntry, PendingEntryFields, XAddId, TrimOptions, ClaimOptions, PendingRange};
+use crate::stream::{Group, PendingSummary, PendingEntry, StreamInfo, GroupInfo, ConsumerInfo};
 
+// 每个连接持有一份，指向所有连接共享的数据和这个连接当前选中的库
 #[derive(Debug, Clone)]
 pub(crate) struct Db {
-    shared: Arc<Shared>
+    shared: Arc<Shared>,
+    index: usize,
+    // 正在执行EXEC的那个连接的Db，只有它能在事务期间拿到锁
+    transaction: bool,
+}
+
+// EXEC期间独占所有的库，释放的时候其他连接才能继续
+#[derive(Debug)]
+pub(crate) struct Transaction {
+    db: Db,
+}
+
+// 执行一条命令期间一直持有，所有的许可都还回来之后事务才能开始
+#[derive(Debug)]
+pub(crate) struct Permit {
+    shared: Arc<Shared>,
+}
+
+// 脚本执行期间一直持有，释放的时候说明脚本执行完了
+#[derive(Debug)]
+pub(crate) struct ScriptGuard {
+    shared: Arc<Shared>,
+    killed: Arc<AtomicBool>,
+    _done: watch::Sender<()>,
+}
+
+// WATCH的时候记下的key的版本，key不存在的时候是None
+#[derive(Debug, Clone)]
+pub(crate) struct WatchedKey {
+    index: usize,
+    key: Bytes,
+    version: Option<u64>,
+}
+
+// EXPIRE系列命令的条件，冲突的组合在解析命令的时候就拦下来了
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExpireOptions {
+    // 原来没有过期时间才设置
+    pub nx: bool,
+    // 原来有过期时间才设置
+    pub xx: bool,
+    // 新的过期时间比原来的晚才设置，没有过期时间当成无限长
+    pub gt: bool,
+    // 新的过期时间比原来的早才设置
+    pub lt: bool,
+}
+
+impl ExpireOptions {
+    fn allows(&self, current: Option<Instant>, new: Instant) -> bool {
+        if (self.nx && current.is_some()) || (self.xx && current.is_none()) {
+            return false;
+        }
+        if self.gt && current.is_none_or(|current| new <= current) {
+            return false;
+        }
+        !(self.lt && current.is_some_and(|current| new >= current))
+    }
 }
 
 #[derive(Debug)]
 struct Shared {
-    state: Mutex<State>,
+    state: Mutex<Databases>,
+    // 决定现在谁能执行命令，等待都是异步的，不会卡住tokio的线程
+    gate: Mutex<Gate>,
+    // 正在执行的脚本，不放在大锁里面，脚本执行期间才能SCRIPT KILL
+    script: Mutex<Option<RunningScript>>,
+    // 脚本执行超过这个时间之后，其他连接的命令都回复BUSY
+    script_time_limit: Duration,
     background_task: Notify,
 }
 
 #[derive(Debug)]
-struct State {
-    entries: HashMap<String, Entry>,
-    // 发布订阅模式
+struct RunningScript {
+    started: Instant,
+    killed: Arc<AtomicBool>,
+    // 脚本改过数据之后就不能被SCRIPT KILL了，不然只改了一半
+    wrote: bool,
+    // 脚本执行完的时候发送端被释放，等着的连接就知道了
+    done: watch::Receiver<()>,
+}
+
+// 所有的库共用一把锁，SWAPDB、MOVE这种跨库的操作才是原子的
+#[derive(Debug)]
+struct Databases {
+    dbs: Vec<State>,
+    // 发布订阅模式，和选中的是哪个库没有关系
     pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
+    // 按glob模式订阅，消息里要带上实际的频道名
+    pattern_sub: HashMap<String, broadcast::Sender<(String, Bytes)>>,
+    // SCRIPT LOAD和EVAL过的脚本，按SHA1找
+    scripts: HashMap<String, Bytes>,
+    shutdown: bool,
+}
+
+// 普通命令可以同时执行，EXEC和脚本要等它们都执行完，开始之后其他连接都要等着
+#[derive(Debug, Default)]
+struct Gate {
+    // 正在执行命令的连接数
+    active: usize,
+    // 有连接在执行EXEC或者脚本
+    exclusive: bool,
+    // 等着独占的连接数，有它们在的时候新的命令也要排在后面，不然一直有命令进来事务就开始不了
+    pending: usize,
+    // 等着的连接，状态变了之后全部唤醒重新检查一遍
+    waiters: Vec<oneshot::Sender<()>>,
+}
+
+// 等着独占的时候持有，不管等到了还是被取消了都要减掉pending
+struct Pending<'a> {
+    shared: &'a Shared,
+}
+
+// 锁住所有的库，但是只能操作当前选中的那个
+struct StateGuard<'a> {
+    guard: MutexGuard<'a, Databases>,
+    index: usize,
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.guard.dbs[self.index]
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut State {
+        &mut self.guard.dbs[self.index]
+    }
+}
+
+// 一个库里的数据
+#[derive(Debug, Default)]
+struct State {
+    entries: ScanMap<Entry>,
     // 将有过期时间的key放到btree结构中
-    expirations: BTreeMap<(Instant, u64), String>,
+    expirations: BTreeMap<(Instant, u64), Bytes>,
+    // 阻塞在每个key上的客户端，按开始等待的先后排队
+    blocked: HashMap<Bytes, VecDeque<u64>>,
+    waiters: HashMap<u64, Waiter>,
+    // 阻塞在流上的XREAD，有新消息的时候通过Notify唤醒
+    stream_waiters: HashMap<Bytes, Vec<(u64, Arc<Notify>)>>,
     next_id: u64,
-    shutdown: bool,
+    // 每改一次数据加一，脚本靠它判断执行的命令有没有写过数据
+    dirty: u64,
+}
+
+// 阻塞在列表上等待元素的客户端
+#[derive(Debug)]
+struct Waiter {
+    keys: Vec<Bytes>,
+    left: bool,
+    // BLMOVE弹出来的元素还要放到另一个列表的头部(true)或者尾部
+    destination: Option<(Bytes, bool)>,
+    tx: oneshot::Sender<crate::Result<(Bytes, Bytes)>>,
+}
+
+// 阻塞弹出的结果，列表里有元素直接返回，没有的话返回等待的id和接收端
+#[derive(Debug)]
+pub(crate) enum BlockingPop {
+    Ready(Bytes, Bytes),
+    Blocked(u64, oneshot::Receiver<crate::Result<(Bytes, Bytes)>>),
+    // 不阻塞的时候列表都是空的直接返回
+    Empty,
 }
 
 #[derive(Debug)]
 struct Entry {
     id: u64,
-    data: Bytes,
+    // 每次修改都会换一个新的，WATCH靠它判断key有没有被改过
+    version: u64,
+    value: Value,
     expires_at: Option<Instant>,
 }
 
+// key对应的值，不同的类型只能用对应的命令操作
+#[derive(Debug, Clone)]
+enum Value {
+    String(Bytes),
+    List(VecDeque<Bytes>),
+    Hash(ScanMap<Bytes>),
+    Set(HashSet<Bytes>),
+    ZSet(ZSet),
+    Stream(Stream),
+}
+
+impl Value {
+    // TYPE命令回复的类型名
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    // 释放这个值大概要做多少工作，按元素个数算
+    fn free_effort(&self) -> usize {
+        match self {
+            Value::String(_) => 1,
+            Value::List(list) => list.len(),
+            Value::Hash(hash) => hash.len(),
+            Value::Set(set) => set.len(),
+            Value::ZSet(zset) => zset.len(),
+            Value::Stream(stream) => stream.len(),
+        }
+    }
+}
+
+// 多个集合之间的运算
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+// 命令操作的key类型不对时返回的错误
+pub(crate) const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+// 脚本已经改过数据了，停下来的话数据就只改了一半
+const UNKILLABLE: &str = "UNKILLABLE Sorry the script already executed write commands against the dataset. \
+You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.";
+
+// 脚本执行太久了
+const BUSY: &str = "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSCRIPT.";
+
+// SELECT之类的命令指定的库不存在
+pub(crate) const OUT_OF_RANGE: &str = "ERR DB index is out of range";
+
+// UNLINK释放的元素个数超过这个数就交给后台线程去释放
+const LAZYFREE_THRESHOLD: usize = 64;
+
+// 字符串最长512MB
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+// XGROUP操作的流不存在
+const NO_STREAM: &str = "ERR The XGROUP subcommand requires the key to exist. \
+Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.";
+
 
 impl Db {
-    // 构造函数
-    pub(crate) fn new() -> Self {
+    // 构造函数，一共有databases个库，一开始选中的是0号库
+    pub(crate) fn new(databases: usize, script_time_limit: Duration) -> Self {
         let shared = Arc::new(Shared {
-            state: Mutex::new(State {
-                entries: HashMap::new(),
+            state: Mutex::new(Databases {
+                dbs: (0..databases).map(|_| State::default()).collect(),
                 pub_sub: HashMap::new(),
-                expirations: BTreeMap::new(),
-                next_id: 0,
+                pattern_sub: HashMap::new(),
+                scripts: HashMap::new(),
                 shutdown: false,
             }),
+            gate: Mutex::new(Gate::default()),
+            script: Mutex::new(None),
+            script_time_limit,
             background_task: Notify::new(),
         });
 
@@ -52,50 +281,201 @@ impl Db {
         tokio::task::spawn(purge_expired_tasks(shared.clone()));
 
         Self {
-            shared
+            shared,
+            index: 0,
+            transaction: false,
         }
     }
 
-    // get方法
-    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| {
-            entry.data.clone()
-        })
+    // 锁住数据，只操作选中的库
+    fn lock(&self) -> StateGuard<'_> {
+        StateGuard {
+            guard: self.shared.lock(),
+            index: self.index,
+        }
     }
 
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let mut state = self.shared.state.lock().unwrap(); // 获取锁
-        let id = state.next_id;
-        state.next_id += 1;
+    // 记下keys现在的版本，EXEC的时候拿来比较
+    pub(crate) fn watch(&self, keys: &[Bytes]) -> Vec<WatchedKey> {
+        let state = self.lock();
+        keys.iter()
+            .map(|key| WatchedKey {
+                index: self.index,
+                key: key.clone(),
+                version: state.entries.get(key).map(|entry| entry.version),
+            })
+            .collect()
+    }
+
+    // 开始执行一条命令，有事务在执行或者排队的时候等它们执行完
+    pub(crate) async fn enter(&self) -> Permit {
+        self.shared.enter(false).await;
+        Permit {
+            shared: self.shared.clone(),
+        }
+    }
 
-        let mut notify = false; // 需不需要触发gc
+    // 阻塞的命令等待期间把许可让出来，不然事务要一直等到它超时。等完了再重新拿回来
+    pub(crate) async fn unlocked<F: Future>(&self, future: F) -> F::Output {
+        // 等待的时候被取消了也要把许可补回去，Handler里的Permit释放的时候还要再减一次
+        struct Reenter<'a> {
+            shared: &'a Shared,
+            done: bool,
+        }
 
-        let expires_at = expire.map(|duration| {
-            let when = Instant::now() + duration; // 啥时候到期
+        impl Drop for Reenter<'_> {
+            fn drop(&mut self) {
+                if !self.done {
+                    self.shared.gate.lock().unwrap().active += 1;
+                }
+            }
+        }
 
-            notify = state.next_expiration().map(|expiration| {
-                expiration > when
-            }).unwrap_or(true);
+        self.shared.leave();
+        let mut reenter = Reenter { shared: &self.shared, done: false };
+        let output = future.await;
+        self.shared.enter(false).await;
+        reenter.done = true;
+        output
+    }
 
-            state.expirations.insert((when, id), key.clone());
-            when
+    // 开始执行事务，直到返回的Transaction释放之前其他连接都执行不了命令。
+    // WATCH的key被改过的话事务不执行，返回None
+    pub(crate) async fn transaction(&self, watched: &[WatchedKey]) -> Option<Transaction> {
+        let transaction = self.exclusive().await;
+        let databases = self.shared.lock();
+        let changed = watched.iter().any(|watched| {
+            let version = databases.dbs[watched.index].entries.get(&watched.key).map(|entry| entry.version);
+            version != watched.version
         });
+        if changed {
+            return None;
+        }
 
+        Some(transaction)
+    }
 
-        let prev = state.entries.insert(key, Entry {
-            id,
-            data: value,
-            expires_at,
+    // 独占所有的库，用在脚本这种不需要检查WATCH的地方。已经在事务里的时候不能再调用
+    pub(crate) async fn exclusive(&self) -> Transaction {
+        self.shared.enter(true).await;
+        self.new_transaction()
+    }
+
+    fn new_transaction(&self) -> Transaction {
+        let db = Db {
+            shared: self.shared.clone(),
+            index: self.index,
+            transaction: true,
+        };
+        Transaction { db }
+    }
+
+    // 登记一个正在执行的脚本，返回的ScriptGuard释放之前都可以被SCRIPT KILL
+    pub(crate) fn start_script(&self) -> ScriptGuard {
+        let killed = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = watch::channel(());
+        *self.shared.script.lock().unwrap() = Some(RunningScript {
+            started: Instant::now(),
+            killed: killed.clone(),
+            wrote: false,
+            done: rx,
         });
+        ScriptGuard {
+            shared: self.shared.clone(),
+            killed,
+            _done: tx,
+        }
+    }
 
-        // {{  这尼玛的是map里面之前有值，插入了一个相同的key就是返回之前的值，所以才有这一步
-        if let Some(prev) = prev {
-            if let Some(when) = prev.expires_at {
-                state.expirations.remove(&(when, prev.id));
+    // 让正在执行的脚本停下来，没有脚本在执行或者脚本已经改过数据的时候报错
+    pub(crate) fn kill_script(&self) -> crate::Result<()> {
+        match &*self.shared.script.lock().unwrap() {
+            Some(script) if script.wrote => Err(UNKILLABLE.into()),
+            Some(script) => {
+                script.killed.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            None => Err("NOTBUSY No scripts in execution right now.".into()),
+        }
+    }
+
+    // 有脚本在执行的时候等它执行完，超过时间限制了还没执行完返回BUSY
+    pub(crate) async fn wait_script(&self) -> crate::Result<()> {
+        loop {
+            let (deadline, mut done) = match &*self.shared.script.lock().unwrap() {
+                Some(script) => (script.started + self.shared.script_time_limit, script.done.clone()),
+                None => return Ok(()),
+            };
+            if Instant::now() >= deadline {
+                return Err(BUSY.into());
+            }
+
+            tokio::select! {
+                _ = done.changed() => {}
+                _ = tokio::time::sleep_until(deadline) => {}
             }
         }
-        //}}
+    }
+
+    // 缓存脚本，返回它的SHA1
+    pub(crate) fn script_load(&self, body: Bytes) -> String {
+        let sha1 = crate::lua::sha1_hex(&body);
+        self.shared.lock().scripts.insert(sha1.clone(), body);
+        sha1
+    }
+
+    // 按SHA1找缓存的脚本，不区分大小写
+    pub(crate) fn script_body(&self, sha1: &str) -> Option<Bytes> {
+        self.shared.lock().scripts.get(&sha1.to_lowercase()).cloned()
+    }
+
+    pub(crate) fn script_exists(&self, sha1s: &[String]) -> Vec<bool> {
+        let databases = self.shared.lock();
+        sha1s.iter().map(|sha1| databases.scripts.contains_key(&sha1.to_lowercase())).collect()
+    }
+
+    pub(crate) fn script_flush(&self) {
+        self.shared.lock().scripts.clear();
+    }
+
+    // 在事务里执行的命令不能阻塞
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.transaction
+    }
+
+    // get方法，key存的不是字符串返回WRONGTYPE
+    pub(crate) fn get(&self, key: &[u8]) -> crate::Result<Option<Bytes>> {
+        let state = self.lock();
+        Ok(state.get_string(key)?.cloned())
+    }
+
+    // 设置字符串，不管之前是什么类型都覆盖。must_exist是NX(Some(false))/XX(Some(true))的条件，
+    // expires_at为None的时候保留原来的过期时间(KEEPTTL)，Some(None)是去掉过期时间。
+    // get为true的时候旧值必须是字符串，否则什么都不做直接报错。返回有没有写入和旧值
+    pub(crate) fn set(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        must_exist: Option<bool>,
+        expires_at: Option<Option<Instant>>,
+        get: bool,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        let mut state = self.lock(); // 获取锁
+        let old = if get { state.get_string(&key)?.cloned() } else { None };
+        if must_exist.is_some_and(|must_exist| must_exist != state.entries.contains_key(&key)) {
+            return Ok((false, old));
+        }
+
+        let notify = match expires_at {
+            Some(expires_at) => {
+                state.insert(key.clone(), Value::String(value));
+                state.set_expiration(&key, expires_at)
+            }
+            None => {
+                state.put_string(key, value);
+                false
+            }
+        };
 
         // 后面需要全局notify 需要提前释放锁
         drop(state);
@@ -103,48 +483,1664 @@ impl Db {
         if notify {
             self.shared.background_task.notify_one();
         }
+        Ok((true, old))
     }
-}
 
-impl State {
-    // 查找小的key
-    fn next_expiration(&self) -> Option<Instant> {
-        self.expirations.keys().next().map(|instance| {
-            instance.0
+    // 批量设置，不管之前是什么类型都覆盖，过期时间也去掉。nx为true时只要有一个key已经存在就都不设置，
+    // 返回有没有设置。在同一把锁里完成，其他客户端看不到只设置了一部分的状态
+    pub(crate) fn mset(&self, pairs: Vec<(Bytes, Bytes)>, nx: bool) -> bool {
+        let mut state = self.lock();
+        if nx && pairs.iter().any(|(key, _)| state.entries.contains_key(key)) {
+            return false;
+        }
+
+        for (key, value) in pairs {
+            state.insert(key, Value::String(value));
+        }
+        true
+    }
+
+    // 批量获取，key不存在或者不是字符串的都是None
+    pub(crate) fn mget(&self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        let state = self.lock();
+        keys.iter()
+            .map(|key| state.get_string(key).ok().flatten().cloned())
+            .collect()
+    }
+
+    // 追加到字符串末尾，key不存在就新建，返回追加后的长度。过期时间不变
+    pub(crate) fn append(&self, key: Bytes, value: Bytes) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let current = match state.get_string_mut(&key)? {
+            Some(current) => current,
+            None => {
+                let len = value.len();
+                state.insert(key, Value::String(value));
+                return Ok(len);
+            }
+        };
+
+        let mut appended = Vec::with_capacity(current.len() + value.len());
+        appended.extend_from_slice(current);
+        appended.extend_from_slice(&value);
+        *current = Bytes::from(appended);
+        let len = current.len();
+        state.touch(&key);
+        Ok(len)
+    }
+
+    // 把字符串当成整数加上increment，key不存在当成0，返回加完之后的值。过期时间不变
+    pub(crate) fn incrby(&self, key: Bytes, increment: i64) -> crate::Result<i64> {
+        let mut state = self.lock();
+        let current = match state.get_string(&key)? {
+            Some(value) => parse_int(value).ok_or("ERR value is not an integer or out of range")?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(increment)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        state.put_string(key, Bytes::from(value.to_string()));
+        Ok(value)
+    }
+
+    // 和incrby一样，只是按浮点数算
+    pub(crate) fn incrbyfloat(&self, key: Bytes, increment: f64) -> crate::Result<Bytes> {
+        let mut state = self.lock();
+        let current = match state.get_string(&key)? {
+            Some(value) => parse_float(value).ok_or("ERR value is not a valid float")?,
+            None => 0.0,
+        };
+        let value = current + increment;
+        if !value.is_finite() {
+            return Err("ERR increment would produce NaN or Infinity".into());
+        }
+
+        let value = format_float(value);
+        state.put_string(key, value.clone());
+        Ok(value)
+    }
+
+    // 返回[start, end]之间的子串，负数表示从尾部开始数，越界的部分截掉
+    pub(crate) fn getrange(&self, key: &[u8], start: i64, end: i64) -> crate::Result<Bytes> {
+        let state = self.lock();
+        let value = match state.get_string(key)? {
+            Some(value) => value,
+            None => return Ok(Bytes::new()),
+        };
+
+        let len = value.len() as i64;
+        if start < 0 && end < 0 && start > end {
+            return Ok(Bytes::new());
+        }
+        let start = if start < 0 { (len + start).max(0) } else { start };
+        let end = if end < 0 { (len + end).max(0) } else { end.min(len - 1) };
+        if start > end || start >= len {
+            return Ok(Bytes::new());
+        }
+
+        Ok(value.slice(start as usize..=end as usize))
+    }
+
+    // 从offset开始覆盖，不够长的话中间补0，返回覆盖后的长度。过期时间不变
+    pub(crate) fn setrange(&self, key: Bytes, offset: usize, value: Bytes) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let len = state.get_string(&key)?.map_or(0, |current| current.len());
+        // 空串什么都不改，key不存在也不会新建
+        if value.is_empty() {
+            return Ok(len);
+        }
+        let end = offset.saturating_add(value.len());
+        if end > MAX_STRING_LEN {
+            return Err("ERR string exceeds maximum allowed size (proto-max-bulk-len)".into());
+        }
+
+        if state.get_string(&key)?.is_none() {
+            state.insert(key.clone(), Value::String(Bytes::new()));
+        }
+        let current = state.get_string_mut(&key)?.unwrap();
+        let mut overwritten = current.to_vec();
+        if overwritten.len() < end {
+            overwritten.resize(end, 0);
+        }
+        overwritten[offset..end].copy_from_slice(&value);
+        *current = Bytes::from(overwritten);
+        let len = current.len();
+        state.touch(&key);
+        Ok(len)
+    }
+
+    // 字符串长度，key不存在返回0
+    pub(crate) fn strlen(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_string(key)?.map_or(0, |value| value.len()))
+    }
+
+    // 获取之后把key删掉
+    pub(crate) fn getdel(&self, key: &[u8]) -> crate::Result<Option<Bytes>> {
+        let mut state = self.lock();
+        if state.get_string(key)?.is_none() {
+            return Ok(None);
+        }
+
+        match state.remove(key) {
+            Some(Entry { value: Value::String(value), .. }) => Ok(Some(value)),
+            _ => unreachable!(),
+        }
+    }
+
+    // 获取的同时修改过期时间，expires_at为None不动，Some(None)去掉过期时间
+    pub(crate) fn getex(&self, key: &[u8], expires_at: Option<Option<Instant>>) -> crate::Result<Option<Bytes>> {
+        let mut state = self.lock();
+        let value = match state.get_string(key)? {
+            Some(value) => value.clone(),
+            None => return Ok(None),
+        };
+
+        let notify = match expires_at {
+            Some(expires_at) => state.set_expiration(key, expires_at),
+            None => false,
+        };
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        Ok(Some(value))
+    }
+
+    // 修改已有key的过期时间，时间已经过了就直接删掉。key不存在或者不满足options的时候返回false
+    pub(crate) fn expire(&self, key: &[u8], expires_at: Instant, options: &ExpireOptions) -> bool {
+        let mut state = self.lock();
+        let current = match state.entries.get(key) {
+            Some(entry) => entry.expires_at,
+            None => return false,
+        };
+        if !options.allows(current, expires_at) {
+            return false;
+        }
+
+        let notify = state.set_expiration(key, Some(expires_at));
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        true
+    }
+
+    // 去掉过期时间，返回key原来有没有过期时间
+    pub(crate) fn persist(&self, key: &[u8]) -> bool {
+        let mut state = self.lock();
+        if state.entries.get(key).is_none_or(|entry| entry.expires_at.is_none()) {
+            return false;
+        }
+        // 去掉过期时间不会让最早的过期时间提前，不用唤醒后台任务
+        state.set_expiration(key, None);
+        true
+    }
+
+    // key的过期时刻，外层的None表示key不存在
+    pub(crate) fn expiration(&self, key: &[u8]) -> Option<Option<Instant>> {
+        let state = self.lock();
+        state.entries.get(key).map(|entry| entry.expires_at)
+    }
+
+    // 删除key，返回实际删掉的个数
+    pub(crate) fn del(&self, keys: &[Bytes]) -> usize {
+        let mut state = self.lock();
+        keys.iter().filter(|key| state.remove(key).is_some()).count()
+    }
+
+    // 和DEL一样马上从keyspace里摘掉，但是大的值放到后台线程去释放，不占用处理请求的时间
+    pub(crate) fn unlink(&self, keys: &[Bytes]) -> usize {
+        let mut state = self.lock();
+        let removed: Vec<Entry> = keys.iter().filter_map(|key| state.remove(key)).collect();
+        drop(state);
+
+        let count = removed.len();
+        let effort: usize = removed.iter().map(|entry| entry.value.free_effort()).sum();
+        if effort > LAZYFREE_THRESHOLD {
+            tokio::task::spawn_blocking(move || drop(removed));
+        }
+        count
+    }
+
+    // 存在的key的个数，重复的key重复计算
+    pub(crate) fn exists(&self, keys: &[Bytes]) -> usize {
+        let state = self.lock();
+        keys.iter().filter(|key| state.entries.contains_key(&key[..])).count()
+    }
+
+    // key的类型名，不存在的时候是none
+    pub(crate) fn key_type(&self, key: &[u8]) -> &'static str {
+        let state = self.lock();
+        state.entries.get(key).map_or("none", |entry| entry.value.type_name())
+    }
+
+    // 把key改名成destination，过期时间跟着一起走，destination原来的值会被覆盖。
+    // nx为true的时候destination已经存在就不改，返回有没有改名
+    pub(crate) fn rename(&self, key: &[u8], destination: Bytes, nx: bool) -> crate::Result<bool> {
+        let mut state = self.lock();
+        if !state.entries.contains_key(key) {
+            return Err("ERR no such key".into());
+        }
+        if key == &destination[..] {
+            return Ok(!nx);
+        }
+        if nx && state.entries.contains_key(&destination) {
+            return Ok(false);
+        }
+
+        let entry = state.remove(key).unwrap();
+        state.insert(destination.clone(), entry.value);
+        let notify = state.set_expiration(&destination, entry.expires_at);
+        state.signal_ready(&destination);
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        Ok(true)
+    }
+
+    // 把key的值复制一份到db库(默认是当前库)的destination，过期时间也一样。
+    // key不存在，或者destination已经存在又没有replace的时候返回false
+    pub(crate) fn copy(&self, key: &[u8], destination: Bytes, db: Option<usize>, replace: bool) -> crate::Result<bool> {
+        let mut databases = self.shared.lock();
+        let db = db.unwrap_or(self.index);
+        if db >= databases.dbs.len() {
+            return Err(OUT_OF_RANGE.into());
+        }
+        if db == self.index && key == &destination[..] {
+            return Err("ERR source and destination objects are the same".into());
+        }
+
+        let (value, expires_at) = match databases.dbs[self.index].entries.get(key) {
+            Some(entry) => (entry.value.clone(), entry.expires_at),
+            None => return Ok(false),
+        };
+        let state = &mut databases.dbs[db];
+        if !replace && state.entries.contains_key(&destination) {
+            return Ok(false);
+        }
+
+        state.insert(destination.clone(), value);
+        let notify = state.set_expiration(&destination, expires_at);
+        state.signal_ready(&destination);
+        drop(databases);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        Ok(true)
+    }
+
+    // 切换到另一个库，之后的操作都在那个库上
+    pub(crate) fn select(&self, index: usize) -> crate::Result<Db> {
+        if index >= self.shared.lock().dbs.len() {
+            return Err(OUT_OF_RANGE.into());
+        }
+        Ok(Db {
+            shared: self.shared.clone(),
+            index,
+            transaction: self.transaction,
         })
     }
-}
 
-impl Shared {
-    fn purge_expired_keys(&self) -> Option<Instant> {
-        let mut state = self.state.lock().unwrap(); // 先拿到state
-        if state.shutdown { // 链接关闭了直接返回了
-            return None;
+    // 交换两个库的数据，选中这两个库的连接马上就能看到对方的数据。
+    // 阻塞的客户端还留在原来的库上，换过来的数据里可能有它们要的东西
+    pub(crate) fn swapdb(&self, first: usize, second: usize) -> crate::Result<()> {
+        let mut databases = self.shared.lock();
+        if first >= databases.dbs.len() || second >= databases.dbs.len() {
+            return Err(OUT_OF_RANGE.into());
+        }
+        if first == second {
+            return Ok(());
         }
 
-        // 取得state的可变引用
-        let state = &mut *state;
-        let now = Instant::now();
-        while let Some((&(when, next_id), key)) = state.expirations.iter().next() {
-            if when > now {
-                return Some(when);
+        let (low, high) = databases.dbs.split_at_mut(first.max(second));
+        let (a, b) = (&mut low[first.min(second)], &mut high[0]);
+        std::mem::swap(&mut a.entries, &mut b.entries);
+        std::mem::swap(&mut a.expirations, &mut b.expirations);
+        // 两边的id都从较大的那个往后分配，换过来的entry的id才不会重复
+        let next_id = a.next_id.max(b.next_id);
+        a.next_id = next_id;
+        b.next_id = next_id;
+        // 换过来的key都算被修改过
+        a.touch_all();
+        b.touch_all();
+
+        a.wake_blocked();
+        b.wake_blocked();
+        Ok(())
+    }
+
+    // 把key移到db库，过期时间跟着一起走。key不存在或者db库里已经有这个key的时候返回false
+    pub(crate) fn move_key(&self, key: &[u8], db: usize) -> crate::Result<bool> {
+        let mut databases = self.shared.lock();
+        if db >= databases.dbs.len() {
+            return Err(OUT_OF_RANGE.into());
+        }
+        if db == self.index {
+            return Err("ERR source and destination objects are the same".into());
+        }
+        if !databases.dbs[self.index].entries.contains_key(key) || databases.dbs[db].entries.contains_key(key) {
+            return Ok(false);
+        }
+
+        let entry = databases.dbs[self.index].remove(key).unwrap();
+        let key = Bytes::copy_from_slice(key);
+        let state = &mut databases.dbs[db];
+        state.insert(key.clone(), entry.value);
+        let notify = state.set_expiration(&key, entry.expires_at);
+        state.signal_ready(&key);
+        drop(databases);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        Ok(true)
+    }
+
+    // 清空当前库(all为true的时候清空所有库)，lazy为true的时候数据放到后台线程去释放
+    pub(crate) fn flush(&self, all: bool, lazy: bool) {
+        let mut databases = self.shared.lock();
+        let range = if all { 0..databases.dbs.len() } else { self.index..self.index + 1 };
+        let removed: Vec<_> = databases.dbs[range]
+            .iter_mut()
+            .map(|state| {
+                state.dirty += 1;
+                state.expirations.clear();
+                std::mem::take(&mut state.entries)
+            })
+            .collect();
+        drop(databases);
+
+        if lazy {
+            tokio::task::spawn_blocking(move || drop(removed));
+        }
+    }
+
+    // 所有匹配glob模式的key，顺序不固定
+    pub(crate) fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let state = self.lock();
+        state.entries.keys().filter(|key| glob::matches(pattern, key)).cloned().collect()
+    }
+
+    // 按游标遍历keyspace，返回下一次的游标和这次遍历到的key以及它的类型
+    pub(crate) fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(Bytes, &'static str)>) {
+        let state = self.lock();
+        let (cursor, found) = state.entries.scan(cursor, count);
+        (cursor, found.into_iter().map(|(key, entry)| (key.clone(), entry.value.type_name())).collect())
+    }
+
+    // 往列表头部(left)或者尾部插入元素，key不存在就新建一个列表，返回插入后的长度
+    pub(crate) fn push(&self, key: Bytes, elements: Vec<Bytes>, left: bool) -> crate::Result<usize> {
+        let mut state = self.lock();
+        if state.get_list_mut(&key)?.is_none() {
+            state.insert(key.clone(), Value::List(VecDeque::new()));
+        }
+
+        let list = state.get_list_mut(&key)?.unwrap();
+        for element in elements {
+            if left {
+                list.push_front(element);
+            } else {
+                list.push_back(element);
             }
-            // 过期了直接删除
-            state.entries.remove(key);
-            state.expirations.remove(&(when, next_id));
         }
+        let len = list.len();
+        state.touch(&key);
 
-        None
+        // 返回的是插入后的长度，阻塞的客户端拿走的元素不算
+        state.serve_blocked(key);
+        Ok(len)
     }
 
-    fn is_shutdown(&self) -> bool {
-        self.state.lock().unwrap().shutdown
+    // 按顺序找第一个有元素的列表弹出一个元素，都是空的话就排队等待，block为false的时候不排队
+    pub(crate) fn blocking_pop(
+        &self,
+        keys: Vec<Bytes>,
+        left: bool,
+        destination: Option<(Bytes, bool)>,
+        block: bool,
+    ) -> crate::Result<BlockingPop> {
+        let mut state = self.lock();
+        for key in &keys {
+            if state.get_list(key)?.is_some() {
+                let element = state.pop_one(key, left, destination.as_ref())?.unwrap();
+                if let Some((destination, _)) = destination {
+                    state.serve_blocked(destination);
+                }
+                return Ok(BlockingPop::Ready(key.clone(), element));
+            }
+        }
+        if !block {
+            return Ok(BlockingPop::Empty);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let (tx, rx) = oneshot::channel();
+        for key in &keys {
+            let queue = state.blocked.entry(key.clone()).or_default();
+            // 同一个key写了多次只排一次队
+            if !queue.contains(&id) {
+                queue.push_back(id);
+            }
+        }
+        state.waiters.insert(id, Waiter { keys, left, destination, tx });
+
+        Ok(BlockingPop::Blocked(id, rx))
     }
-}
+
+    // 不再等待了(超时或者服务关闭)，返回false说明已经有元素发过来了
+    pub(crate) fn unblock(&self, id: u64) -> bool {
+        let mut state = self.lock();
+        state.remove_waiter(id).is_some()
+    }
+
+    // 从列表头部(left)或者尾部弹出最多count个元素，key不存在返回None，弹空了就把key删掉
+    pub(crate) fn pop(&self, key: &[u8], count: usize, left: bool) -> crate::Result<Option<Vec<Bytes>>> {
+        let mut state = self.lock();
+        let list = match state.get_list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        let mut popped = Vec::with_capacity(count.min(list.len()));
+        while popped.len() < count {
+            let element = if left { list.pop_front() } else { list.pop_back() };
+            match element {
+                Some(element) => popped.push(element),
+                None => break,
+            }
+        }
+
+        if list.is_empty() {
+            state.remove(key);
+        } else if !popped.is_empty() {
+            state.touch(key);
+        }
+        Ok(Some(popped))
+    }
+
+    // 返回列表[start, stop]之间的元素，负数表示从尾部开始数
+    pub(crate) fn lrange(&self, key: &[u8], start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let state = self.lock();
+        let list = match state.get_list(key)? {
+            Some(list) => list,
+            None => return Ok(vec![]),
+        };
+
+        let len = list.len() as i64;
+        let start = if start < 0 { (len + start).max(0) } else { start };
+        let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+        if start > stop || start >= len {
+            return Ok(vec![]);
+        }
+
+        Ok(list.range(start as usize..=stop as usize).cloned().collect())
+    }
+
+    // 列表长度，key不存在返回0
+    pub(crate) fn llen(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_list(key)?.map_or(0, |list| list.len()))
+    }
+
+    // 设置哈希表的字段，返回新增的字段数
+    pub(crate) fn hset(&self, key: Bytes, pairs: Vec<(Bytes, Bytes)>) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let hash = state.get_or_insert_hash(key.clone())?;
+
+        let mut added = 0;
+        for (field, value) in pairs {
+            if hash.insert(field, value).is_none() {
+                added += 1;
+            }
+        }
+        state.touch(&key);
+        Ok(added)
+    }
+
+    // 批量获取字段的值，不存在的字段对应None
+    pub(crate) fn hget(&self, key: &[u8], fields: &[Bytes]) -> crate::Result<Vec<Option<Bytes>>> {
+        let state = self.lock();
+        let hash = state.get_hash(key)?;
+        Ok(fields.iter().map(|field| hash.and_then(|hash| hash.get(field).cloned())).collect())
+    }
+
+    // 删除字段，返回实际删掉的字段数，删空了就把key删掉
+    pub(crate) fn hdel(&self, key: &[u8], fields: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let hash = match state.get_hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(0),
+        };
+
+        let removed = fields.iter().filter(|field| hash.remove(*field).is_some()).count();
+        if hash.is_empty() {
+            state.remove(key);
+        } else if removed > 0 {
+            state.touch(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn hlen(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_hash(key)?.map_or(0, |hash| hash.len()))
+    }
+
+    // 所有的字段和值，key不存在返回空
+    pub(crate) fn hgetall(&self, key: &[u8]) -> crate::Result<Vec<(Bytes, Bytes)>> {
+        let state = self.lock();
+        Ok(state.get_hash(key)?.map_or_else(Vec::new, |hash| {
+            hash.iter().map(|(field, value)| (field.clone(), value.clone())).collect()
+        }))
+    }
+
+    // 字段的值加上increment，字段不存在当成0，返回加完之后的值
+    pub(crate) fn hincrby(&self, key: Bytes, field: Bytes, increment: i64) -> crate::Result<i64> {
+        let mut state = self.lock();
+        let hash = state.get_or_insert_hash(key.clone())?;
+
+        let current = match hash.get(&field) {
+            Some(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .ok_or("ERR hash value is not an integer")?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(increment)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        hash.insert(field, Bytes::from(value.to_string()));
+        state.touch(&key);
+        Ok(value)
+    }
+
+    // 和hincrby一样，只是按浮点数算
+    pub(crate) fn hincrbyfloat(&self, key: Bytes, field: Bytes, increment: f64) -> crate::Result<Bytes> {
+        let mut state = self.lock();
+
+        // 先把新值算出来，出错的话不能留下一个空的哈希
+        let current = match state.get_hash(&key)?.and_then(|hash| hash.get(&field)) {
+            Some(value) => parse_float(value).ok_or("ERR hash value is not a float")?,
+            None => 0.0,
+        };
+        let value = current + increment;
+        if !value.is_finite() {
+            return Err("ERR increment would produce NaN or Infinity".into());
+        }
+
+        let value = format_float(value);
+        state.get_or_insert_hash(key.clone())?.insert(field, value.clone());
+        state.touch(&key);
+        Ok(value)
+    }
+
+    // 往集合里加元素，返回新加进去的元素个数
+    pub(crate) fn sadd(&self, key: Bytes, members: Vec<Bytes>) -> crate::Result<usize> {
+        let mut state = self.lock();
+        if state.get_set(&key)?.is_none() {
+            state.insert(key.clone(), Value::Set(HashSet::new()));
+        }
+
+        let set = state.get_set_mut(&key)?.unwrap();
+        let added = members.into_iter().filter(|member| set.insert(member.clone())).count();
+        if added > 0 {
+            state.touch(&key);
+        }
+        Ok(added)
+    }
+
+    // 从集合里删元素，返回实际删掉的个数，删空了就把key删掉
+    pub(crate) fn srem(&self, key: &[u8], members: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let set = match state.get_set_mut(key)? {
+            Some(set) => set,
+            None => return Ok(0),
+        };
+
+        let removed = members.iter().filter(|member| set.remove(*member)).count();
+        if set.is_empty() {
+            state.remove(key);
+        } else if removed > 0 {
+            state.touch(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn sismember(&self, key: &[u8], member: &[u8]) -> crate::Result<bool> {
+        let state = self.lock();
+        Ok(state.get_set(key)?.is_some_and(|set| set.contains(member)))
+    }
+
+    pub(crate) fn scard(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_set(key)?.map_or(0, |set| set.len()))
+    }
+
+    // 多个集合的交集、并集或者差集，在同一把锁里算完，结果是一致的快照
+    pub(crate) fn sop(&self, op: SetOp, keys: &[Bytes]) -> crate::Result<Vec<Bytes>> {
+        let state = self.lock();
+        Ok(state.sop(op, keys)?.into_iter().collect())
+    }
+
+    // 和sop一样，只是把结果存到destination里(覆盖原来的值)，返回结果的元素个数
+    pub(crate) fn sopstore(&self, op: SetOp, destination: Bytes, keys: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let result = state.sop(op, keys)?;
+        let len = result.len();
+
+        // 结果为空的时候redis是直接把destination删掉
+        if result.is_empty() {
+            state.remove(&destination);
+        } else {
+            state.insert(destination, Value::Set(result));
+        }
+        Ok(len)
+    }
+
+    // 添加或者更新有序集合的成员，每个成员返回一个结果
+    pub(crate) fn zadd(&self, key: Bytes, options: &ZAddOptions, pairs: Vec<(f64, Bytes)>) -> crate::Result<Vec<ZAddResult>> {
+        let mut state = self.lock();
+        if state.get_zset(&key)?.is_none() {
+            state.insert(key.clone(), Value::ZSet(ZSet::new()));
+        }
+
+        let zset = state.get_zset_mut(&key)?.unwrap();
+        let mut results = Vec::with_capacity(pairs.len());
+        let mut err = None;
+        for (score, member) in pairs {
+            match zset.add(member, score, options) {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    err = Some(e);
+                    break;
+                }
+            }
+        }
+
+        // XX之类的选项可能一个都没加进去，不能留下空的有序集合
+        if zset.is_empty() {
+            state.remove(&key);
+        } else if results.iter().any(|result| matches!(result, ZAddResult::Added(_) | ZAddResult::Updated(_))) {
+            state.touch(&key);
+        }
+        match err {
+            Some(err) => Err(err),
+            None => Ok(results),
+        }
+    }
+
+    // 删除成员，返回实际删掉的个数，删空了就把key删掉
+    pub(crate) fn zrem(&self, key: &[u8], members: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let zset = match state.get_zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(0),
+        };
+
+        let removed = members.iter().filter(|member| zset.remove(member)).count();
+        if zset.is_empty() {
+            state.remove(key);
+        } else if removed > 0 {
+            state.touch(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn zscore(&self, key: &[u8], member: &[u8]) -> crate::Result<Option<f64>> {
+        let state = self.lock();
+        Ok(state.get_zset(key)?.and_then(|zset| zset.score(member)))
+    }
+
+    pub(crate) fn zcard(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_zset(key)?.map_or(0, |zset| zset.len()))
+    }
+
+    pub(crate) fn zrank(&self, key: &[u8], member: &[u8]) -> crate::Result<Option<usize>> {
+        let state = self.lock();
+        Ok(state.get_zset(key)?.and_then(|zset| zset.rank(member)))
+    }
+
+    pub(crate) fn zrange(&self, key: &[u8], by: &RangeBy, rev: bool, limit: Option<(i64, i64)>) -> crate::Result<Vec<(Bytes, f64)>> {
+        let state = self.lock();
+        Ok(state.get_zset(key)?.map_or_else(Vec::new, |zset| zset.range(by, rev, limit)))
+    }
+
+    // 往流里追加一条消息，返回消息的ID，NOMKSTREAM并且key不存在的时候返回None
+    pub(crate) fn xadd(
+        &self,
+        key: Bytes,
+        id: XAddId,
+        fields: Vec<(Bytes, Bytes)>,
+        nomkstream: bool,
+        trim: Option<&TrimOptions>,
+    ) -> crate::Result<Option<StreamId>> {
+        let mut state = self.lock();
+        let id = match state.get_stream_mut(&key)? {
+            Some(stream) => {
+                let id = stream.add(id, fields)?;
+                if let Some(trim) = trim {
+                    stream.trim(trim);
+                }
+                state.touch(&key);
+                id
+            }
+            None if nomkstream => return Ok(None),
+            // ID不合法的时候不能留下一个空的流
+            None => {
+                let mut stream = Stream::new();
+                let id = stream.add(id, fields)?;
+                if let Some(trim) = trim {
+                    stream.trim(trim);
+                }
+                state.insert(key.clone(), Value::Stream(stream));
+                id
+            }
+        };
+
+        // notify_one在没有人等的时候会留一个许可，登记之后才检查的XREAD也不会错过这条消息
+        if let Some(waiters) = state.stream_waiters.get(&key) {
+            for (_, notify) in waiters {
+                notify.notify_one();
+            }
+        }
+        Ok(Some(id))
+    }
+
+    pub(crate) fn xrange(&self, key: &[u8], start: StreamId, end: StreamId, count: Option<usize>) -> crate::Result<Vec<StreamEntry>> {
+        let state = self.lock();
+        Ok(state.get_stream(key)?.map_or_else(Vec::new, |stream| stream.range(start, end, count, false)))
+    }
+
+    pub(crate) fn xlen(&self, key: &[u8]) -> crate::Result<usize> {
+        let state = self.lock();
+        Ok(state.get_stream(key)?.map_or(0, |stream| stream.len()))
+    }
+
+    // 返回删掉的消息条数，删空了流也还在
+    pub(crate) fn xtrim(&self, key: &[u8], options: &TrimOptions) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let trimmed = state.get_stream_mut(key)?.map_or(0, |stream| stream.trim(options));
+        if trimmed > 0 {
+            state.touch(key);
+        }
+        Ok(trimmed)
+    }
+
+    // XREAD的`$`，key不存在当成0-0
+    pub(crate) fn xlast_id(&self, key: &[u8]) -> crate::Result<StreamId> {
+        let state = self.lock();
+        Ok(state.get_stream(key)?.map_or(StreamId::MIN, |stream| stream.last_id()))
+    }
+
+    // 每个流读ID比ids里对应的大的消息，没有新消息的流不返回
+    pub(crate) fn xread(&self, keys: &[Bytes], ids: &[StreamId], count: Option<usize>) -> crate::Result<Vec<(Bytes, Vec<StreamEntry>)>> {
+        let state = self.lock();
+        let mut found = vec![];
+        for (key, id) in keys.iter().zip(ids) {
+            if let Some(stream) = state.get_stream(key)? {
+                let entries = stream.after(*id, count);
+                if !entries.is_empty() {
+                    found.push((key.clone(), entries));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    // 新建消费组，key不存在的时候要带MKSTREAM才会新建一个空的流
+    pub(crate) fn xgroup_create(&self, key: Bytes, group: Bytes, id: Option<StreamId>, mkstream: bool) -> crate::Result<()> {
+        let mut state = self.lock();
+        if state.get_stream(&key)?.is_none() {
+            if !mkstream {
+                return Err(NO_STREAM.into());
+            }
+            state.insert(key.clone(), Value::Stream(Stream::new()));
+        }
+
+        if state.get_stream_mut(&key)?.unwrap().create_group(group, id) {
+            state.touch(&key);
+            Ok(())
+        } else {
+            Err("BUSYGROUP Consumer Group name already exists".into())
+        }
+    }
+
+    pub(crate) fn xgroup_destroy(&self, key: &[u8], group: &[u8]) -> crate::Result<bool> {
+        let mut state = self.lock();
+        let stream = state.get_stream_mut(key)?.ok_or(NO_STREAM)?;
+        let destroyed = stream.destroy_group(group);
+        if destroyed {
+            state.touch(key);
+        }
+        Ok(destroyed)
+    }
+
+    pub(crate) fn xgroup_createconsumer(&self, key: &[u8], group: &[u8], consumer: Bytes) -> crate::Result<bool> {
+        let mut state = self.lock();
+        let created = state.get_group_mut(key, group)?.create_consumer(consumer);
+        if created {
+            state.touch(key);
+        }
+        Ok(created)
+    }
+
+    // 返回消费者被删掉的待确认消息条数
+    pub(crate) fn xgroup_delconsumer(&self, key: &[u8], group: &[u8], consumer: &[u8]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let deleted = state.get_group_mut(key, group)?.delete_consumer(consumer);
+        if deleted.is_some() {
+            state.touch(key);
+        }
+        Ok(deleted.unwrap_or(0))
+    }
+
+    // id为None表示流里最新的消息
+    pub(crate) fn xgroup_setid(&self, key: &[u8], group: &[u8], id: Option<StreamId>) -> crate::Result<()> {
+        let mut state = self.lock();
+        let last_id = state.get_stream(key)?.ok_or(NO_STREAM)?.last_id();
+        state.get_group_mut(key, group)?.set_last_id(id.unwrap_or(last_id));
+        state.touch(key);
+        Ok(())
+    }
+
+    // 以消费者的身份从每个流读消息，ids里None表示`>`。读新消息的流没有新消息的时候不返回，
+    // 读历史的流总是返回。所有的流都要有这个组，先检查完再读，不会只读了一部分
+    pub(crate) fn xreadgroup(
+        &self,
+        group: &Bytes,
+        consumer: &Bytes,
+        keys: &[Bytes],
+        ids: &[Option<StreamId>],
+        count: Option<usize>,
+        noack: bool,
+    ) -> crate::Result<Vec<(Bytes, Vec<PendingEntryFields>)>> {
+        let mut state = self.lock();
+        for key in keys {
+            if state.get_stream(key)?.and_then(|stream| stream.group(group)).is_none() {
+                return Err(format!(
+                    "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    String::from_utf8_lossy(key),
+                    String::from_utf8_lossy(group),
+                ).into());
+            }
+        }
+
+        let mut found = vec![];
+        for (key, id) in keys.iter().zip(ids) {
+            let stream = state.get_stream_mut(key)?.unwrap();
+            let entries = stream.read_group(group, consumer, *id, count, noack).unwrap();
+            if !entries.is_empty() {
+                state.touch(key);
+            }
+            if id.is_some() || !entries.is_empty() {
+                found.push((key.clone(), entries));
+            }
+        }
+        Ok(found)
+    }
+
+    // 返回确认掉的条数，key或者组不存在都是0
+    pub(crate) fn xack(&self, key: &[u8], group: &[u8], ids: &[StreamId]) -> crate::Result<usize> {
+        let mut state = self.lock();
+        let group = state.get_stream_mut(key)?.and_then(|stream| stream.group_mut(group));
+        let acked = group.map_or(0, |group| group.ack(ids));
+        if acked > 0 {
+            state.touch(key);
+        }
+        Ok(acked)
+    }
+
+    pub(crate) fn xpending(&self, key: &[u8], group: &[u8]) -> crate::Result<PendingSummary> {
+        let state = self.lock();
+        Ok(state.get_group(key, group)?.summary())
+    }
+
+    pub(crate) fn xpending_range(&self, key: &[u8], group: &[u8], range: &PendingRange) -> crate::Result<Vec<PendingEntry>> {
+        let state = self.lock();
+        Ok(state.get_group(key, group)?.pending_range(range))
+    }
+
+    // 认领空闲够久的待确认消息，返回认领到的消息
+    pub(crate) fn xclaim(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> crate::Result<Vec<StreamEntry>> {
+        let mut state = self.lock();
+        state.get_group(key, group)?;
+        let stream = state.get_stream_mut(key)?.unwrap();
+        let claimed = stream.claim(group, consumer, min_idle, ids, options).unwrap();
+        if !claimed.is_empty() {
+            state.touch(key);
+        }
+        Ok(claimed)
+    }
+
+    // 从start开始扫描并认领，返回下一次的游标、认领到的消息和已经被删掉的消息ID
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn xautoclaim(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> crate::Result<(StreamId, Vec<StreamEntry>, Vec<StreamId>)> {
+        let mut state = self.lock();
+        state.get_group(key, group)?;
+        let stream = state.get_stream_mut(key)?.unwrap();
+        let (next, claimed, deleted) = stream.autoclaim(group, consumer, min_idle, start, count, justid).unwrap();
+        if !claimed.is_empty() || !deleted.is_empty() {
+            state.touch(key);
+        }
+        Ok((next, claimed, deleted))
+    }
+
+    pub(crate) fn xinfo_stream(&self, key: &[u8]) -> crate::Result<StreamInfo> {
+        let state = self.lock();
+        Ok(state.get_stream(key)?.ok_or("ERR no such key")?.info())
+    }
+
+    pub(crate) fn xinfo_groups(&self, key: &[u8]) -> crate::Result<Vec<GroupInfo>> {
+        let state = self.lock();
+        Ok(state.get_stream(key)?.ok_or("ERR no such key")?.groups_info())
+    }
+
+    pub(crate) fn xinfo_consumers(&self, key: &[u8], group: &[u8]) -> crate::Result<Vec<ConsumerInfo>> {
+        let state = self.lock();
+        let stream = state.get_stream(key)?.ok_or("ERR no such key")?;
+        let group = stream.group(group).ok_or_else(|| missing_group(key, group))?;
+        Ok(group.consumers_info())
+    }
+
+    // 在keys上登记一个Notify，返回的id用来取消登记
+    pub(crate) fn xwatch(&self, keys: &[Bytes], notify: Arc<Notify>) -> u64 {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        for key in keys {
+            state.stream_waiters.entry(key.clone()).or_default().push((id, notify.clone()));
+        }
+        id
+    }
+
+    pub(crate) fn xunwatch(&self, keys: &[Bytes], id: u64) {
+        let mut state = self.lock();
+        for key in keys {
+            if let Some(waiters) = state.stream_waiters.get_mut(key) {
+                waiters.retain(|(waiter, _)| *waiter != id);
+                if waiters.is_empty() {
+                    state.stream_waiters.remove(key);
+                }
+            }
+        }
+    }
+
+    // 从cursor开始遍历哈希表，返回下一次的游标和这次遍历到的字段
+    pub(crate) fn hscan(&self, key: &[u8], cursor: u64, count: usize) -> crate::Result<(u64, Vec<(Bytes, Bytes)>)> {
+        let state = self.lock();
+        let hash = match state.get_hash(key)? {
+            Some(hash) => hash,
+            None => return Ok((0, vec![])),
+        };
+
+        let (cursor, found) = hash.scan(cursor, count);
+        Ok((cursor, found.into_iter().map(|(field, value)| (field.clone(), value.clone())).collect()))
+    }
+
+    // 订阅一个频道，频道不存在就新建一个
+    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
+        use std::collections::hash_map::Entry;
+
+        let mut state = self.shared.lock();
+        match state.pub_sub.entry(key) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                // 订阅者来不及消费的时候最多缓存1024条消息
+                let (tx, rx) = broadcast::channel(1024);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
+    // 按glob模式订阅
+    pub(crate) fn psubscribe(&self, pattern: String) -> broadcast::Receiver<(String, Bytes)> {
+        use std::collections::hash_map::Entry;
+
+        let mut state = self.shared.lock();
+        match state.pattern_sub.entry(pattern) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                let (tx, rx) = broadcast::channel(1024);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
+    // 往频道里发消息，返回收到消息的订阅者数量(模式订阅匹配上的也算)
+    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
+        let mut state = self.shared.lock();
+        let mut num = 0;
+
+        if let Some(tx) = state.pub_sub.get(key) {
+            num = tx.send(value.clone()).unwrap_or(0);
+            // 订阅者都走光了就把频道删掉，不然频道会越积越多
+            if num == 0 {
+                state.pub_sub.remove(key);
+            }
+        }
+
+        for (pattern, tx) in state.pattern_sub.iter() {
+            if glob::matches(pattern.as_bytes(), key.as_bytes()) {
+                num += tx.send((key.to_string(), value.clone())).unwrap_or(0);
+            }
+        }
+        state.pattern_sub.retain(|_, tx| tx.receiver_count() > 0);
+
+        num
+    }
+
+    // 有订阅者的频道，可以按glob模式过滤
+    pub(crate) fn pubsub_channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let state = self.shared.lock();
+        state.pub_sub.iter()
+            .filter(|(_, tx)| tx.receiver_count() > 0)
+            .filter(|(channel, _)| {
+                pattern.is_none_or(|pattern| glob::matches(pattern.as_bytes(), channel.as_bytes()))
+            })
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    // 每个频道的订阅者数量(不算模式订阅)
+    pub(crate) fn pubsub_numsub(&self, channels: &[String]) -> Vec<usize> {
+        let state = self.shared.lock();
+        channels.iter()
+            .map(|channel| state.pub_sub.get(channel).map_or(0, |tx| tx.receiver_count()))
+            .collect()
+    }
+
+    // 有订阅者的模式数量
+    pub(crate) fn pubsub_numpat(&self) -> usize {
+        let state = self.shared.lock();
+        state.pattern_sub.values()
+            .filter(|tx| tx.receiver_count() > 0)
+            .count()
+    }
+}
+
+impl State {
+    // 查找小的key
+    fn next_expiration(&self) -> Option<Instant> {
+        self.expirations.keys().next().map(|instance| {
+            instance.0
+        })
+    }
+
+    // 插入一个新的entry，替换掉之前的值，之前的过期时间也一起去掉。要过期的话再调set_expiration
+    fn insert(&mut self, key: Bytes, value: Value) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.dirty += 1;
+
+        let prev = self.entries.insert(key, Entry {
+            id,
+            version: id,
+            value,
+            expires_at: None,
+        });
+
+        // 之前有值的话，旧值的过期时间也要一起删掉
+        if let Some(prev) = prev {
+            if let Some(when) = prev.expires_at {
+                self.expirations.remove(&(when, prev.id));
+            }
+        }
+    }
+
+    // 确实改了key之后调用，换一个新的版本。类型不对或者什么都没改的时候不能调用，不然WATCH的事务会白白失败
+    fn touch(&mut self, key: &[u8]) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.version = self.next_id;
+            self.next_id += 1;
+            self.dirty += 1;
+        }
+    }
+
+    // 所有的key都换一个新的版本
+    fn touch_all(&mut self) {
+        self.dirty += 1;
+        for entry in self.entries.values_mut() {
+            entry.version = self.next_id;
+            self.next_id += 1;
+        }
+    }
+
+    // 删除key，连同它的过期时间
+    fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.dirty += 1;
+        if let Some(when) = entry.expires_at {
+            self.expirations.remove(&(when, entry.id));
+        }
+        Some(entry)
+    }
+
+    // 替换字符串的值，key不存在就新建，已有的过期时间不变
+    fn put_string(&mut self, key: Bytes, value: Bytes) {
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.value = Value::String(value);
+                self.touch(&key);
+            }
+            None => {
+                self.insert(key, Value::String(value));
+            }
+        }
+    }
+
+    // 修改已有key的过期时间，已经过了的直接删掉，返回需不需要唤醒后台任务
+    fn set_expiration(&mut self, key: &[u8], expires_at: Option<Instant>) -> bool {
+        if expires_at.is_some_and(|when| when <= Instant::now()) {
+            self.remove(key);
+            return false;
+        }
+
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let prev = std::mem::replace(&mut entry.expires_at, expires_at);
+        let id = entry.id;
+        if prev == expires_at {
+            return false;
+        }
+        self.touch(key);
+        let key = Bytes::copy_from_slice(key);
+        if let Some(when) = prev {
+            self.expirations.remove(&(when, id));
+        }
+
+        match expires_at {
+            Some(when) => {
+                let notify = self.next_expiration().is_none_or(|expiration| expiration > when);
+                self.expirations.insert((when, id), key);
+                notify
+            }
+            None => false,
+        }
+    }
+
+    // 从key弹出一个元素，如果有destination再把它放过去，key不存在返回None
+    fn pop_one(&mut self, key: &[u8], left: bool, destination: Option<&(Bytes, bool)>) -> crate::Result<Option<Bytes>> {
+        // 先检查目标的类型，免得弹出来之后放不进去
+        if let Some((destination, _)) = destination {
+            self.get_list(destination)?;
+        }
+
+        let list = match self.get_list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let element = if left { list.pop_front() } else { list.pop_back() };
+        let element = match element {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        if list.is_empty() {
+            self.remove(key);
+        } else {
+            self.touch(key);
+        }
+
+        if let Some((destination, to_left)) = destination {
+            if self.get_list_mut(destination)?.is_none() {
+                self.insert(destination.clone(), Value::List(VecDeque::new()));
+            }
+            let list = self.get_list_mut(destination)?.unwrap();
+            if *to_left {
+                list.push_front(element.clone());
+            } else {
+                list.push_back(element.clone());
+            }
+            self.touch(destination);
+        }
+
+        Ok(Some(element))
+    }
+
+    // 整个库的数据都换了，所有阻塞的客户端都要重新检查一遍
+    fn wake_blocked(&mut self) {
+        let keys: Vec<Bytes> = self.blocked.keys().chain(self.stream_waiters.keys()).cloned().collect();
+        for key in keys {
+            self.signal_ready(&key);
+        }
+    }
+
+    // key上出现了新的值，唤醒阻塞在上面的BLPOP和XREAD
+    fn signal_ready(&mut self, key: &Bytes) {
+        match self.entries.get(key).map(|entry| &entry.value) {
+            Some(Value::List(_)) => self.serve_blocked(key.clone()),
+            Some(Value::Stream(_)) => {
+                if let Some(waiters) = self.stream_waiters.get(key) {
+                    for (_, notify) in waiters {
+                        notify.notify_one();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 列表有新元素了，按排队顺序把元素交给阻塞在上面的客户端
+    fn serve_blocked(&mut self, key: Bytes) {
+        // BLMOVE会往别的列表里放元素，那个列表上也可能有人在等
+        let mut ready = vec![key];
+        while let Some(key) = ready.pop() {
+            while self.get_list(&key).is_ok_and(|list| list.is_some()) {
+                let id = match self.blocked.get_mut(&key).and_then(|queue| queue.pop_front()) {
+                    Some(id) => id,
+                    None => break,
+                };
+                let waiter = match self.remove_waiter(id) {
+                    Some(waiter) => waiter,
+                    None => continue,
+                };
+
+                // 客户端不等了，元素留给下一个
+                if waiter.tx.is_closed() {
+                    continue;
+                }
+
+                let result = self.pop_one(&key, waiter.left, waiter.destination.as_ref());
+                if let (Ok(_), Some((destination, _))) = (&result, &waiter.destination) {
+                    ready.push(destination.clone());
+                }
+                let result = result.map(|element| (key.clone(), element.unwrap()));
+                let _ = waiter.tx.send(result);
+            }
+        }
+    }
+
+    // 把等待的客户端从它阻塞的所有key的队列里删掉
+    fn remove_waiter(&mut self, id: u64) -> Option<Waiter> {
+        let waiter = self.waiters.remove(&id)?;
+        for key in &waiter.keys {
+            if let Some(queue) = self.blocked.get_mut(key) {
+                queue.retain(|waiting| *waiting != id);
+                if queue.is_empty() {
+                    self.blocked.remove(key);
+                }
+            }
+        }
+        Some(waiter)
+    }
+
+    fn get_stream(&self, key: &[u8]) -> crate::Result<Option<&Stream>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::Stream(stream), .. }) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_stream_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut Stream>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::Stream(stream), .. }) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    // 流或者组不存在都是NOGROUP
+    fn get_group(&self, key: &[u8], group: &[u8]) -> crate::Result<&Group> {
+        self.get_stream(key)?.and_then(|stream| stream.group(group)).ok_or_else(|| no_group(key, group))
+    }
+
+    // XGROUP用的，流不存在和组不存在的错误不一样
+    fn get_group_mut(&mut self, key: &[u8], group: &[u8]) -> crate::Result<&mut Group> {
+        match self.get_stream_mut(key)? {
+            Some(stream) => stream.group_mut(group).ok_or_else(|| missing_group(key, group)),
+            None => Err(NO_STREAM.into()),
+        }
+    }
+
+    fn get_zset(&self, key: &[u8]) -> crate::Result<Option<&ZSet>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::ZSet(zset), .. }) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_zset_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut ZSet>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::ZSet(zset), .. }) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_set(&self, key: &[u8]) -> crate::Result<Option<&HashSet<Bytes>>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::Set(set), .. }) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_set_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut HashSet<Bytes>>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::Set(set), .. }) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    // 不存在的key当成空集合，任何一个key不是集合都返回WRONGTYPE
+    fn sop(&self, op: SetOp, keys: &[Bytes]) -> crate::Result<HashSet<Bytes>> {
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            sets.push(self.get_set(key)?);
+        }
+
+        let empty = HashSet::new();
+        let (first, rest) = sets.split_first().unwrap();
+        let first = first.unwrap_or(&empty);
+
+        let result = match op {
+            SetOp::Inter => first
+                .iter()
+                .filter(|member| rest.iter().all(|set| set.is_some_and(|set| set.contains(*member))))
+                .cloned()
+                .collect(),
+            SetOp::Union => sets.iter().flatten().flat_map(|set| set.iter()).cloned().collect(),
+            SetOp::Diff => first
+                .iter()
+                .filter(|member| !rest.iter().any(|set| set.is_some_and(|set| set.contains(*member))))
+                .cloned()
+                .collect(),
+        };
+        Ok(result)
+    }
+
+    fn get_hash(&self, key: &[u8]) -> crate::Result<Option<&ScanMap<Bytes>>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::Hash(hash), .. }) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_hash_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut ScanMap<Bytes>>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::Hash(hash), .. }) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    // key不存在就新建一个空的哈希表
+    fn get_or_insert_hash(&mut self, key: Bytes) -> crate::Result<&mut ScanMap<Bytes>> {
+        if self.get_hash(&key)?.is_none() {
+            self.insert(key.clone(), Value::Hash(ScanMap::new()));
+        }
+        Ok(self.get_hash_mut(&key)?.unwrap())
+    }
+
+    fn get_string(&self, key: &[u8]) -> crate::Result<Option<&Bytes>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::String(value), .. }) => Ok(Some(value)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_string_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut Bytes>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::String(value), .. }) => Ok(Some(value)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_list(&self, key: &[u8]) -> crate::Result<Option<&VecDeque<Bytes>>> {
+        match self.entries.get(key) {
+            Some(Entry { value: Value::List(list), .. }) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_list_mut(&mut self, key: &[u8]) -> crate::Result<Option<&mut VecDeque<Bytes>>> {
+        match self.entries.get_mut(key) {
+            Some(Entry { value: Value::List(list), .. }) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Transaction {
+    // 事务里的命令都用这个Db执行，SELECT换库也只换它
+    pub(crate) fn db(&mut self) -> &mut Db {
+        &mut self.db
+    }
+
+    // 事务执行完了，返回连接之后要用的Db
+    pub(crate) fn finish(self) -> Db {
+        Db {
+            shared: self.db.shared.clone(),
+            index: self.db.index,
+            transaction: false,
+        }
+    }
+}
+
+impl ScriptGuard {
+    // 脚本执行过程中不停地检查这个标记
+    pub(crate) fn killed(&self) -> Arc<AtomicBool> {
+        self.killed.clone()
+    }
+
+    // 执行脚本里的一条命令，已经被SCRIPT KILL了就不执行，返回None。
+    // 执行期间拿着脚本的锁，SCRIPT KILL要么在它之前，要么就能看到它有没有改过数据
+    pub(crate) fn call<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let mut script = self.shared.script.lock().unwrap();
+        if self.killed.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let dirty = self.shared.dirty();
+        let result = f();
+        if self.shared.dirty() != dirty {
+            if let Some(script) = &mut *script {
+                script.wrote = true;
+            }
+        }
+        Some(result)
+    }
+}
+
+impl Drop for ScriptGuard {
+    fn drop(&mut self) {
+        // 脚本没执行完连接就被放掉了，Lua那边的线程也要停下来
+        self.killed.store(true, Ordering::SeqCst);
+        *self.shared.script.lock().unwrap() = None;
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        let mut gate = self.db.shared.gate.lock().unwrap();
+        gate.exclusive = false;
+        gate.wake();
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.shared.leave();
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        let mut gate = self.shared.gate.lock().unwrap();
+        gate.pending -= 1;
+        gate.wake();
+    }
+}
+
+impl Gate {
+    fn wake(&mut self) {
+        for waiter in self.waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
+impl Shared {
+    // 拿到所有库的锁。谁能执行命令由gate决定，这里只保证每个操作是原子的
+    fn lock(&self) -> MutexGuard<'_, Databases> {
+        self.state.lock().unwrap()
+    }
+
+    // 等到可以执行命令为止，exclusive为true的时候要等其他命令都执行完，然后独占所有的库
+    async fn enter(&self, exclusive: bool) {
+        let _pending = exclusive.then(|| {
+            self.gate.lock().unwrap().pending += 1;
+            Pending { shared: self }
+        });
+
+        loop {
+            let rx = {
+                let mut gate = self.gate.lock().unwrap();
+                if exclusive && !gate.exclusive && gate.active == 0 {
+                    gate.exclusive = true;
+                    return;
+                }
+                if !exclusive && !gate.exclusive && gate.pending == 0 {
+                    gate.active += 1;
+                    return;
+                }
+                // 先登记再放锁，状态在这之后变了也一定会被唤醒
+                let (tx, rx) = oneshot::channel();
+                gate.waiters.push(tx);
+                rx
+            };
+            let _ = rx.await;
+        }
+    }
+
+    // 一条命令执行完了，最后一个执行完的唤醒等着独占的连接
+    fn leave(&self) {
+        let mut gate = self.gate.lock().unwrap();
+        gate.active -= 1;
+        if gate.active == 0 {
+            gate.wake();
+        }
+    }
+
+    // 每个库都清理一遍，返回所有库里最早的下一个过期时间
+    fn purge_expired_keys(&self) -> Option<Instant> {
+        let mut databases = self.lock(); // 先拿到state
+        if databases.shutdown { // 链接关闭了直接返回了
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut next = None;
+        for state in databases.dbs.iter_mut() {
+            while let Some((&(when, next_id), key)) = state.expirations.iter().next() {
+                if when > now {
+                    next = Some(next.map_or(when, |next: Instant| next.min(when)));
+                    break;
+                }
+                // 过期了直接删除，和DEL一样走remove，WATCH和脚本才能知道这个key被改过
+                let key = key.clone();
+                state.remove(&key);
+                state.expirations.remove(&(when, next_id));
+            }
+        }
+
+        next
+    }
+
+    // 所有库加起来改过多少次数据
+    fn dirty(&self) -> u64 {
+        self.lock().dbs.iter().map(|state| state.dirty).sum()
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.lock().shutdown
+    }
+}
+
+fn no_group(key: &[u8], group: &[u8]) -> crate::Error {
+    format!(
+        "NOGROUP No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group),
+    ).into()
+}
+
+fn missing_group(key: &[u8], group: &[u8]) -> crate::Error {
+    format!(
+        "NOGROUP No such consumer group '{}' for key name '{}'",
+        String::from_utf8_lossy(group),
+        String::from_utf8_lossy(key),
+    ).into()
+}
+
+// 只认十进制整数，不能有空格和多余的符号
+fn parse_int(value: &[u8]) -> Option<i64> {
+    if value.first() == Some(&b'+') {
+        return None;
+    }
+    std::str::from_utf8(value).ok()?.parse::<i64>().ok()
+}
+
+fn parse_float(value: &[u8]) -> Option<f64> {
+    std::str::from_utf8(value).ok()?.parse::<f64>().ok().filter(|value| !value.is_nan())
+}
+
+// 整数不带小数点，其他的用最短的能还原的表示
+fn format_float(value: f64) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+// 后台清理任务最长睡这么久
+const MAX_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 async fn purge_expired_tasks(shared: Arc<Shared>) {
     while !shared.is_shutdown() { // 没有结束一直在后台运行
-        if let Some(when) = shared.purge_expired_keys() {
+        // 事务和脚本执行期间不能删key，和普通命令一样排队
+        shared.enter(false).await;
+        let next = shared.purge_expired_keys();
+        shared.leave();
+        if let Some(when) = next {
+            // tokio的定时器最多只能等两年多，过期时间再远也先醒过来再看一次
+            let when = when.min(Instant::now() + MAX_PURGE_INTERVAL);
             tokio::select! {
                 _ = tokio::time::sleep_until(when) => {}
                 _ = shared.background_task.notified() => {}
@@ -5,14 +5,25 @@ use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Frame {
     Simple(String),
     Error(String),
-    Integer(u64),
+    Integer(i64),
     Bulk(Bytes),
     Null,
     Array(Vec<Frame>),
+    // 下面的是RESP3新增的类型，RESP2的连接上会降级成上面的类型
+    Boolean(bool),
+    Double(f64),
+    BigNumber(String),
+    // 格式(比如`txt`、`mkd`)和内容
+    Verbatim(String, Bytes),
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    // 附加在真正的响应前面的属性
+    Attribute(Vec<(Frame, Frame)>, Box<Frame>),
+    Push(Vec<Frame>),
 }
 
 #[derive(Debug)]
@@ -22,20 +33,64 @@ pub enum Error {
 }
 
 impl Frame {
+    // 返回一个空的数组frame
+    pub(crate) fn array() -> Frame {
+        Frame::Array(vec![])
+    }
+
+    // 返回一个空的推送frame，RESP2的连接上会当成数组发出去
+    pub(crate) fn push() -> Frame {
+        Frame::Push(vec![])
+    }
+
+    // 往数组frame里面追加一个bulk
+    pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
+        match self {
+            Frame::Array(vec) | Frame::Set(vec) | Frame::Push(vec) => vec.push(Frame::Bulk(bytes)),
+            _ => panic!("not an array frame"),
+        }
+    }
+
+    // 往数组frame里面追加一个整数
+    pub(crate) fn push_int(&mut self, value: i64) {
+        match self {
+            Frame::Array(vec) | Frame::Set(vec) | Frame::Push(vec) => vec.push(Frame::Integer(value)),
+            _ => panic!("not an array frame"),
+        }
+    }
+
+    // 客户端收到了不符合预期的响应时转成错误
+    pub(crate) fn to_error(&self) -> crate::Error {
+        format!("unexpected frame: {:?}", self).into()
+    }
+
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
         match get_u8(src)? {
-            b'*' => { // 这是在redis中的意思是后面带一个数字，数字表示该条消息字段的总和
-                let len = get_decimal(src)?;
+            b'*' | b'~' | b'>' => { // 这是在redis中的意思是后面带一个数字，数字表示该条消息字段的总和
+                if b'-' == peer_u8(src)? {
+                    return check_null(src);
+                }
+                let len = get_length(src)?;
                 for _ in 0..len {
                     Frame::check(src)?;
                 }
                 Ok(())
             }
-            b'+' => {
-                get_line(src)?;
+            b'%' => {
+                let len = get_pairs_length(src)?;
+                for _ in 0..len {
+                    Frame::check(src)?;
+                }
                 Ok(())
             }
-            b'_' => {
+            b'|' => { // 属性后面还跟着真正的响应
+                let len = get_pairs_length(src)?;
+                for _ in 0..len {
+                    Frame::check(src)?;
+                }
+                Frame::check(src)
+            }
+            b'+' | b'-' | b'_' | b'#' | b',' | b'(' => {
                 get_line(src)?;
                 Ok(())
             }
@@ -43,13 +98,12 @@ impl Frame {
                 get_decimal(src)?;
                 Ok(())
             }
-            b'$' => {
+            b'$' | b'=' | b'!' => {
                 if b'-' == peer_u8(src)? {
-                    // Skip '-1\r\n'
-                    skip(src, 4)
+                    check_null(src)
                 } else {
-                    let len = get_decimal(src)? as usize;
-                    skip(src, (len + 2))
+                    let len = get_length(src)?;
+                    skip(src, len.checked_add(2).ok_or("protocol error; invalid frame format")?)
                 }
             }
 
@@ -60,12 +114,28 @@ impl Frame {
     pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
         match get_u8(src)? {
             b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+                match parse_aggregate(src)? {
+                    Some(out) => Ok(Frame::Array(out)),
+                    None => Ok(Frame::Null),
+                }
+            }
+            b'~' => { // 集合
+                match parse_aggregate(src)? {
+                    Some(out) => Ok(Frame::Set(out)),
+                    None => Ok(Frame::Null),
                 }
-                Ok(Frame::Array(out))
+            }
+            b'>' => { // 服务端主动推送的消息
+                match parse_aggregate(src)? {
+                    Some(out) => Ok(Frame::Push(out)),
+                    None => Ok(Frame::Null),
+                }
+            }
+            b'%' => Ok(Frame::Map(parse_pairs(src)?)),
+            b'|' => {
+                let attributes = parse_pairs(src)?;
+                let data = Frame::parse(src)?;
+                Ok(Frame::Attribute(attributes, Box::new(data)))
             }
             b'+' => { // 单行字符串
                 let line = get_line(src)?.to_vec();
@@ -81,31 +151,111 @@ impl Frame {
                 let string = String::from_utf8(line)?;
                 Ok(Frame::Error(string))
             }
+            b'_' => {
+                if !get_line(src)?.is_empty() {
+                    return Err("protocol error; invalid frame format".into());
+                }
+                Ok(Frame::Null)
+            }
+            b'#' => {
+                match get_line(src)? {
+                    b"t" => Ok(Frame::Boolean(true)),
+                    b"f" => Ok(Frame::Boolean(false)),
+                    _ => Err("protocol error; invalid frame format".into()),
+                }
+            }
+            b',' => {
+                let line = String::from_utf8(get_line(src)?.to_vec())?;
+                let value = line.parse::<f64>()
+                    .map_err(|_| Error::from("protocol error; invalid frame format"))?;
+                Ok(Frame::Double(value))
+            }
+            b'(' => {
+                let line = String::from_utf8(get_line(src)?.to_vec())?;
+                let digits = line.strip_prefix(|c| c == '-' || c == '+').unwrap_or(&line);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err("protocol error; invalid frame format".into());
+                }
+                Ok(Frame::BigNumber(line))
+            }
             b'$' => {
-                if b'-' == peer_u8(src)? { // 等于null的情况啊
-                    let line = get_line(src)?;
-                    if line != b"-1" {
-                        return Err("protocol error; invalid frame format".into());
-                    }
-                    Ok(Frame::Null)
-                } else {
-                    let len = get_decimal(src)?.try_into()?;
-                    let n = len + 2;
-                    if src.remaining() < n {
-                        return Err(Error::Incomplete);
+                match parse_blob(src)? {
+                    Some(data) => Ok(Frame::Bulk(data)),
+                    None => Ok(Frame::Null),
+                }
+            }
+            b'!' => { // 二进制安全的错误信息
+                match parse_blob(src)? {
+                    Some(data) => Ok(Frame::Error(String::from_utf8(data.to_vec())?)),
+                    None => Ok(Frame::Null),
+                }
+            }
+            b'=' => { // 带格式的字符串，前面3个字节是格式，然后是一个`:`
+                match parse_blob(src)? {
+                    Some(data) if data.len() >= 4 && data[3] == b':' => {
+                        let format = String::from_utf8(data[..3].to_vec())?;
+                        Ok(Frame::Verbatim(format, data.slice(4..)))
                     }
-                    let data = Bytes::copy_from_slice(&src.bytes()[..len]);
-                    skip(src, n)?;
-                    Ok(Frame::Bulk(data))
+                    _ => Err("protocol error; invalid frame format".into()),
                 }
             }
-            _ => unimplemented!()
+            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into())
+        }
+    }
+}
+
+// 解析数组、集合和推送的元素，长度为-1表示null
+fn parse_aggregate(src: &mut Cursor<&[u8]>) -> Result<Option<Vec<Frame>>, Error> {
+    if b'-' == peer_u8(src)? {
+        let line = get_line(src)?;
+        if line != b"-1" {
+            return Err("protocol error; invalid frame format".into());
+        }
+        return Ok(None);
+    }
+
+    let len = get_length(src)?;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        out.push(Frame::parse(src)?);
+    }
+    Ok(Some(out))
+}
+
+// 解析map和属性的键值对
+fn parse_pairs(src: &mut Cursor<&[u8]>) -> Result<Vec<(Frame, Frame)>, Error> {
+    let len = get_length(src)?;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        let key = Frame::parse(src)?;
+        let value = Frame::parse(src)?;
+        out.push((key, value));
+    }
+    Ok(out)
+}
+
+// 解析带长度的二进制数据，长度为-1表示null
+fn parse_blob(src: &mut Cursor<&[u8]>) -> Result<Option<Bytes>, Error> {
+    if b'-' == peer_u8(src)? { // 等于null的情况啊
+        let line = get_line(src)?;
+        if line != b"-1" {
+            return Err("protocol error; invalid frame format".into());
         }
+        return Ok(None);
     }
+
+    let len = get_length(src)?;
+    let n = len.checked_add(2).ok_or("protocol error; invalid frame format")?;
+    if src.remaining() < n {
+        return Err(Error::Incomplete);
+    }
+    let data = Bytes::copy_from_slice(&src.bytes()[..len]);
+    skip(src, n)?;
+    Ok(Some(data))
 }
 
 fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
-    if !src.has_remaining() {
+    if src.remaining() < n {
         return Err(Error::Incomplete);
     }
     src.advance(n);
@@ -130,12 +280,53 @@ fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     Ok(src.get_u8())
 }
 
-fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
-    use atoi::atoi;
+// 有符号的十进制整数，可以带正负号，超出i64范围的当成格式错误
+fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
     let line = get_line(src)?;
-    atoi::<u64>(line).ok_or_else(|| {
-        "protocol error; invalid frame format".into()
-    })
+    let (negative, digits) = match line.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        Some((b'+', digits)) => (false, digits),
+        _ => (false, line),
+    };
+    if digits.is_empty() {
+        return Err("protocol error; invalid frame format".into());
+    }
+
+    // 按负数累加，这样i64::MIN也能表示
+    let mut value: i64 = 0;
+    for &b in digits {
+        if !b.is_ascii_digit() {
+            return Err("protocol error; invalid frame format".into());
+        }
+        value = value
+            .checked_mul(10)
+            .and_then(|value| value.checked_sub((b - b'0') as i64))
+            .ok_or("protocol error; invalid frame format")?;
+    }
+
+    if negative {
+        Ok(value)
+    } else {
+        value.checked_neg().ok_or_else(|| "protocol error; invalid frame format".into())
+    }
+}
+
+// 长度为负数的时候只能是表示null的-1
+fn check_null(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    if get_line(src)? != b"-1" {
+        return Err("protocol error; invalid frame format".into());
+    }
+    Ok(())
+}
+
+// 聚合类型和二进制数据的长度，不能是负数
+fn get_length(src: &mut Cursor<&[u8]>) -> Result<usize, Error> {
+    Ok(get_decimal(src)?.try_into()?)
+}
+
+// map和属性里面键值对的个数，返回frame的个数
+fn get_pairs_length(src: &mut Cursor<&[u8]>) -> Result<usize, Error> {
+    get_length(src)?.checked_mul(2).ok_or_else(|| "protocol error; invalid frame format".into())
 }
 
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
@@ -0,0 +1,100 @@
+// redis风格的glob匹配，支持 `*`、`?`、`[...]`(`^`取反、`a-z`范围) 和 `\` 转义
+pub(crate) fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // 最近一次遇到的`*`，匹配失败的时候回溯到这里让`*`多吃一个字符
+    let mut star: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    while p < pattern.len() && pattern[p] == b'*' {
+                        p += 1;
+                    }
+                    // `*`在最后，后面的都能匹配上
+                    if p == pattern.len() {
+                        return true;
+                    }
+                    star = Some((p, s));
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    s += 1;
+                    continue;
+                }
+                b'[' => {
+                    let (matched, next) = match_class(pattern, p, string[s]);
+                    if matched {
+                        p = next;
+                        s += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == string[s] {
+                        p += 2;
+                        s += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == string[s] {
+                        p += 1;
+                        s += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // 没匹配上，能回溯就回溯
+        match star {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                star = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+// 匹配`[...]`，返回是否匹配上以及`]`后面的位置，没闭合的`[`一直匹配到结尾
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> (bool, usize) {
+    p += 1;
+    let not = p < pattern.len() && pattern[p] == b'^';
+    if not {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            p += 1;
+            if pattern[p] == c {
+                matched = true;
+            }
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' {
+            let (mut start, mut end) = (pattern[p], pattern[p + 2]);
+            if start > end {
+                std::mem::swap(&mut start, &mut end);
+            }
+            if start <= c && c <= end {
+                matched = true;
+            }
+            p += 2;
+        } else if pattern[p] == c {
+            matched = true;
+        }
+        p += 1;
+    }
+
+    let next = if p < pattern.len() { p + 1 } else { p };
+    (matched != not, next)
+}
@@ -5,11 +5,23 @@ pub mod connection;
 pub mod frame;
 pub mod cmd;
 pub mod parse;
+pub mod client;
+mod glob;
+mod zset;
+mod stream;
+mod scan_map;
+mod lua;
 
 
 // redis-server 默认监听端口
 pub const DEFAULT_PORT: &str = "6378";
 
+// 默认有多少个库，SELECT的下标从0开始
+pub const DEFAULT_DATABASES: usize = 16;
+
+// 脚本执行超过这个时间之后，其他连接的命令都回复BUSY，只能等它执行完或者SCRIPT KILL
+pub const DEFAULT_SCRIPT_TIME_LIMIT: std::time::Duration = std::time::Duration::from_secs(5);
+
 // 自定义redis的Error(使用鸭子类型，只要实现了线程安全的error都可以)
 pub type Error = Box<dyn Send + Sync + std::error::Error>;
 
@@ -0,0 +1,264 @@
+use bytes::Bytes;
+use mlua::{Lua, LuaOptions, StdLib, HookTriggers, Value, Table, MultiValue};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::cmd::Command;
+use crate::connection::{Connection, format_double};
+use crate::db::{Db, ScriptGuard};
+use crate::frame::Frame;
+use tokio::sync::mpsc;
+
+// 执行这么多条指令检查一次有没有被SCRIPT KILL
+const KILL_CHECK_INSTRUCTIONS: u32 = 1000;
+
+// redis.call出错的时候把错误抛出去，redis.pcall则是把错误当成返回值
+const REDIS_CALL: &str = r#"
+redis.call = function(...)
+    local reply = redis.pcall(...)
+    if type(reply) == 'table' and reply.err then
+        error(reply, 0)
+    end
+    return reply
+end
+"#;
+
+const KILLED: &str = "ERR Script killed by user with SCRIPT KILL...";
+
+// 脚本的SHA1，EVALSHA和SCRIPT EXISTS都用小写的十六进制
+pub(crate) fn sha1_hex(body: &[u8]) -> String {
+    sha1_smol::Sha1::from(body).digest().to_string()
+}
+
+// 只编译不执行，SCRIPT LOAD的时候检查语法
+pub(crate) fn compile(body: &[u8]) -> crate::Result<()> {
+    let lua = new_lua().map_err(|err| format!("ERR {}", err))?;
+    lua.load(body).set_name("@user_script").into_function().map_err(compile_error)?;
+    Ok(())
+}
+
+// redis.call要执行的命令，和送回回复的通道
+type Request = (Frame, std::sync::mpsc::Sender<Frame>);
+
+// 执行脚本。Lua放在tokio的阻塞线程上跑，不会占着执行协程的线程；redis.call的命令送回这里在db上同步执行，
+// 回复再送回给Lua。调用之前要已经独占了所有的库。被SCRIPT KILL之后脚本会在下一次检查的时候中止
+pub(crate) async fn eval(
+    db: &mut Db,
+    dst: &mut Connection,
+    body: Bytes,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    guard: &ScriptGuard,
+) -> Frame {
+    let killed = guard.killed();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Request>();
+    let script = tokio::task::spawn_blocking({
+        let killed = killed.clone();
+        move || {
+            run(&body, keys, args, killed, tx).unwrap_or_else(|err| match err {
+                mlua::Error::SyntaxError { .. } => Frame::Error(compile_error(err).to_string()),
+                err => Frame::Error(format!("ERR Error running script: {}", root_cause(&err))),
+            })
+        }
+    });
+
+    // Lua那边执行完了发送端就被释放了
+    while let Some((frame, reply)) = rx.recv().await {
+        let _ = reply.send(execute(db, dst, frame, guard));
+    }
+    let result = script.await;
+    if killed.load(Ordering::SeqCst) {
+        return Frame::Error(KILLED.to_string());
+    }
+    result.unwrap_or_else(|err| Frame::Error(format!("ERR Error running script: {}", err)))
+}
+
+fn run(
+    body: &[u8],
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    killed: Arc<AtomicBool>,
+    requests: mpsc::UnboundedSender<Request>,
+) -> mlua::Result<Frame> {
+    let lua = new_lua()?;
+    let globals = lua.globals();
+    globals.set("KEYS", lua.create_sequence_from(keys.iter().map(|key| lua.create_string(key)).collect::<mlua::Result<Vec<_>>>()?)?)?;
+    globals.set("ARGV", lua.create_sequence_from(args.iter().map(|arg| lua.create_string(arg)).collect::<mlua::Result<Vec<_>>>()?)?)?;
+
+    let redis = lua.create_table()?;
+    redis.set("error_reply", lua.create_function(|lua, msg: mlua::String| reply_table(lua, "err", msg))?)?;
+    redis.set("status_reply", lua.create_function(|lua, msg: mlua::String| reply_table(lua, "ok", msg))?)?;
+    redis.set("sha1hex", lua.create_function(|_, body: mlua::String| Ok(sha1_hex(body.as_bytes())))?)?;
+    redis.set("pcall", lua.create_function(move |lua, args: MultiValue| {
+        let reply = call(lua, &requests, args)?;
+        frame_to_lua(lua, reply)
+    })?)?;
+    globals.set("redis", redis)?;
+    lua.load(REDIS_CALL).exec()?;
+
+    let function = lua.load(body).set_name("@user_script").into_function()?;
+    lua.set_hook(HookTriggers::new().every_nth_instruction(KILL_CHECK_INSTRUCTIONS), move |lua, _| {
+        if !killed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        // 之后每条指令都报错，脚本里的pcall接住了也马上又会报错
+        lua.set_hook(HookTriggers::new().every_nth_instruction(1), |_, _| Err(mlua::Error::RuntimeError(KILLED.to_string())));
+        Err(mlua::Error::RuntimeError(KILLED.to_string()))
+    });
+
+    // 脚本里的错误也当成返回值，error(redis.error_reply(...))抛出来的表才能原样回复给客户端
+    let protected: mlua::Function = globals.get("pcall")?;
+    let (ok, value): (bool, Value) = protected.call(function)?;
+    if ok {
+        return Ok(lua_to_frame(value));
+    }
+    Ok(match value {
+        Value::Table(table) if matches!(table.raw_get("err")?, Value::String(_)) => lua_to_frame(Value::Table(table)),
+        Value::Error(err) => Frame::Error(format!("ERR Error running script: {}", root_cause(&err))),
+        value => {
+            let msg = lua.coerce_string(value)?.map(|msg| msg.to_string_lossy().into_owned());
+            Frame::Error(format!("ERR Error running script: {}", msg.unwrap_or_default()))
+        }
+    })
+}
+
+// 和Redis一样只加载基础库、table、string和math，读写文件的函数也去掉
+fn new_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH, LuaOptions::new())?;
+    lua.globals().set("dofile", Value::Nil)?;
+    lua.globals().set("loadfile", Value::Nil)?;
+    Ok(lua)
+}
+
+// redis.pcall执行一条命令，返回这条命令的回复。在Lua的线程上调用，等着连接那边执行完
+fn call(lua: &Lua, requests: &mpsc::UnboundedSender<Request>, args: MultiValue) -> mlua::Result<Frame> {
+    if args.is_empty() {
+        return Ok(Frame::Error("ERR Please specify at least one argument for this redis lib call".to_string()));
+    }
+
+    let mut frame = Frame::array();
+    for arg in args {
+        match arg {
+            Value::String(_) | Value::Integer(_) | Value::Number(_) => {
+                let arg = lua.coerce_string(arg)?.unwrap();
+                frame.push_bulk(Bytes::copy_from_slice(arg.as_bytes()));
+            }
+            _ => {
+                let msg = "ERR Lua redis lib command arguments must be strings or integers";
+                return Ok(Frame::Error(msg.to_string()));
+            }
+        }
+    }
+
+    // 连接那边不等了说明脚本已经被放弃了，当成被kill处理
+    let (tx, rx) = std::sync::mpsc::channel();
+    requests.send((frame, tx)).map_err(|_| mlua::Error::RuntimeError(KILLED.to_string()))?;
+    rx.recv().map_err(|_| mlua::Error::RuntimeError(KILLED.to_string()))
+}
+
+// 在连接这边执行脚本送过来的命令
+fn execute(db: &mut Db, dst: &mut Connection, frame: Frame, guard: &ScriptGuard) -> Frame {
+    let cmd = match Command::from_frame(frame) {
+        Ok(cmd) => cmd,
+        Err(err) => return Frame::Error(err.to_string()),
+    };
+    if !allowed(&cmd) {
+        return Frame::Error("ERR This Redis command is not allowed from script".to_string());
+    }
+
+    guard
+        .call(|| cmd.execute(db, dst))
+        .unwrap_or_else(|| Frame::Error(KILLED.to_string()))
+}
+
+// 会改连接状态或者一直等下去的命令不能在脚本里执行
+fn allowed(cmd: &Command) -> bool {
+    !matches!(
+        cmd,
+        Command::Hello(_)
+            | Command::Multi(_)
+            | Command::Exec(_)
+            | Command::Discard(_)
+            | Command::Watch(_)
+            | Command::Unwatch(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::PSubscribe(_)
+            | Command::PUnsubscribe(_)
+            | Command::Eval(_)
+            | Command::EvalSha(_)
+            | Command::Script(_)
+    )
+}
+
+fn reply_table<'lua>(lua: &'lua Lua, field: &str, msg: mlua::String<'lua>) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.raw_set(field, msg)?;
+    Ok(table)
+}
+
+// 命令的回复转成Lua的值，按RESP2的规则：状态和错误是带ok、err字段的表，空值是false
+fn frame_to_lua(lua: &Lua, frame: Frame) -> mlua::Result<Value<'_>> {
+    let value = match frame {
+        Frame::Simple(msg) => Value::Table(reply_table(lua, "ok", lua.create_string(&msg)?)?),
+        Frame::Error(msg) => Value::Table(reply_table(lua, "err", lua.create_string(&msg)?)?),
+        Frame::Integer(val) => Value::Integer(val),
+        Frame::Bulk(val) => Value::String(lua.create_string(&val)?),
+        Frame::Null => Value::Boolean(false),
+        Frame::Array(items) | Frame::Set(items) | Frame::Push(items) => {
+            let table = lua.create_table()?;
+            for (i, item) in items.into_iter().enumerate() {
+                table.raw_seti(i + 1, frame_to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        Frame::Boolean(val) => Value::Integer(val as mlua::Integer),
+        Frame::Double(val) => Value::String(lua.create_string(format_double(val))?),
+        Frame::BigNumber(val) => Value::String(lua.create_string(&val)?),
+        Frame::Verbatim(_, val) => Value::String(lua.create_string(&val)?),
+        Frame::Map(pairs) => {
+            let items = pairs.into_iter().flat_map(|(key, value)| vec![key, value]).collect();
+            frame_to_lua(lua, Frame::Array(items))?
+        }
+        Frame::Attribute(_, frame) => frame_to_lua(lua, *frame)?,
+    };
+    Ok(value)
+}
+
+// 脚本的返回值转成回复：数字截断成整数，true是1，false和nil是空值，
+// 表里有err、ok字段的是错误和状态，其他的表取数组部分直到第一个nil
+fn lua_to_frame(value: Value) -> Frame {
+    match value {
+        Value::Boolean(true) => Frame::Integer(1),
+        Value::Integer(val) => Frame::Integer(val),
+        Value::Number(val) => Frame::Integer(val as i64),
+        Value::String(val) => Frame::Bulk(Bytes::copy_from_slice(val.as_bytes())),
+        Value::Table(table) => {
+            if let Ok(Value::String(msg)) = table.raw_get("err") {
+                return Frame::Error(msg.to_string_lossy().into_owned());
+            }
+            if let Ok(Value::String(msg)) = table.raw_get("ok") {
+                return Frame::Simple(msg.to_string_lossy().into_owned());
+            }
+            let items = table.sequence_values::<Value>().map_while(Result::ok).map(lua_to_frame).collect();
+            Frame::Array(items)
+        }
+        Value::Error(err) => Frame::Error(format!("ERR {}", root_cause(&err))),
+        _ => Frame::Null,
+    }
+}
+
+fn compile_error(err: mlua::Error) -> crate::Error {
+    match err {
+        mlua::Error::SyntaxError { message, .. } => format!("ERR Error compiling script: {}", message).into(),
+        err => format!("ERR Error compiling script: {}", err).into(),
+    }
+}
+
+// Rust函数里出的错会被一层层包起来，只要最里面的
+fn root_cause(err: &mlua::Error) -> String {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => root_cause(cause),
+        mlua::Error::RuntimeError(msg) => msg.clone(),
+        err => err.to_string(),
+    }
+}
@@ -1,8 +1,8 @@
 use std::vec;
+use std::convert::TryFrom;
 use crate::frame::Frame;
 use std::fmt;
 use std::fmt::Formatter;
-use crate::cmd::Command;
 use bytes::Bytes;
 
 #[derive(Debug)]
@@ -22,7 +22,7 @@ impl Parse {
         let array = match frame {
             Frame::Array(array) => array,
             frame => {
-                return Err(format!("protocol error; expected array, got {:?}", frame).into());
+                return Err(format!("ERR Protocol error: expected array, got {:?}", frame).into());
             }
         };
         Ok(Self {
@@ -35,8 +35,8 @@ impl Parse {
             Frame::Simple(s) => Ok(s),
             Frame::Bulk(data) => std::str::from_utf8(&data[..])
                 .map(|s| s.to_string())
-                .map_err(|_| "protocol error; invalid string".into()),
-            frame => Err(format!("protocol error; expected simple frame or bulk frame, got {:?}", frame).into()),
+                .map_err(|_| "ERR Protocol error: invalid string".into()),
+            frame => Err(format!("ERR Protocol error: expected simple frame or bulk frame, got {:?}", frame).into()),
         }
     }
 
@@ -44,21 +44,28 @@ impl Parse {
         match self.next()? {
             Frame::Bulk(b) => Ok(b),
             Frame::Simple(s) => Ok(Bytes::from(s)),
-            frame => Err(format!("protocol error; expected simple frame or bulk frame, got {:?}", frame).into()),
+            frame => Err(format!("ERR Protocol error: expected simple frame or bulk frame, got {:?}", frame).into()),
         }
     }
 
-    pub(crate) fn next_int(&mut self) -> Result<u64, ParseError> {
-        use atoi::atoi;
-        const MSG: &str = "protocol error; invalid number";
+    // 有符号的整数参数，超出i64范围的算错误
+    pub(crate) fn next_int(&mut self) -> Result<i64, ParseError> {
+        const MSG: &str = "ERR value is not an integer or out of range";
+        let parse = |data: &[u8]| std::str::from_utf8(data).ok()?.parse::<i64>().ok();
         match self.next()? {
             Frame::Integer(v) => Ok(v),
-            Frame::Simple(data) => atoi::<u64>(data.as_bytes()).ok_or_else(|| MSG.into()),
-            Frame::Bulk(data) => atoi::<u64>(data.as_ref()).ok_or_else(|| MSG.into()),
-            frame => Err(format!("protocol error; expected int frame but got {:?}", frame).into())
+            Frame::Simple(data) => parse(data.as_bytes()).ok_or_else(|| MSG.into()),
+            Frame::Bulk(data) => parse(&data).ok_or_else(|| MSG.into()),
+            frame => Err(format!("ERR Protocol error: expected int frame but got {:?}", frame).into())
         }
     }
 
+    // 不能是负数的整数参数，比如COUNT
+    pub(crate) fn next_uint(&mut self) -> Result<u64, ParseError> {
+        let value = self.next_int()?;
+        u64::try_from(value).map_err(|_| "ERR value is out of range, must be positive".into())
+    }
+
     fn next(&mut self) -> Result<Frame, ParseError> {
         self.parts.next().ok_or(ParseError::EndOfStream)
     }
@@ -67,7 +74,7 @@ impl Parse {
         if self.parts.next().is_none() {
             Ok(())
         } else {
-            Err("protocol error; expected end of frame, but there was more".into())
+            Err("ERR Protocol error: expected end of frame, but there was more".into())
         }
     }
 }
@@ -88,7 +95,7 @@ impl From<&str> for ParseError {
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::EndOfStream => "protocol error; unexpected end of stream".fmt(f),
+            ParseError::EndOfStream => "ERR Protocol error: unexpected end of stream".fmt(f),
             ParseError::Other(e) => e.fmt(f)
         }
     }
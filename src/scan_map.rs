@@ -0,0 +1,90 @@
+use bytes::Bytes;
+use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+// 支持游标遍历的哈希表 = 哈希表 + 按(key的哈希值, key)排序的索引
+// 游标就是下一个要返回的哈希值，每次SCAN只需要O(log n + count)。哈希值和map的容量无关，
+// 所以扩容之后顺序也不会变，整个遍历过程中一直存在的key至少会返回一次。
+// 只读的操作直接Deref到哈希表，增删key必须走这里的方法，不然索引就对不上了
+#[derive(Debug, Clone)]
+pub(crate) struct ScanMap<V> {
+    map: HashMap<Bytes, V>,
+    index: BTreeSet<(u64, Bytes)>,
+}
+
+impl<V> ScanMap<V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            index: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: Bytes, value: V) -> Option<V> {
+        let prev = self.map.insert(key.clone(), value);
+        if prev.is_none() {
+            self.index.insert((scan_hash(&key), key));
+        }
+        prev
+    }
+
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Bytes: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, value) = self.map.remove_entry(key)?;
+        self.index.remove(&(scan_hash(&key), key));
+        Some(value)
+    }
+
+    pub(crate) fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Bytes: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get_mut(key)
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.map.values_mut()
+    }
+
+    // 从cursor开始按哈希值遍历，返回下一次的游标(0表示结束)和这次遍历到的key
+    pub(crate) fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&Bytes, &V)>) {
+        let mut found: Vec<(&Bytes, &V)> = vec![];
+        let mut last = None;
+        for (hash, key) in self.index.range((cursor, Bytes::new())..) {
+            // 哈希值一样的key要一起返回，不然下一次就跳过去了
+            if found.len() >= count.max(1) && last != Some(*hash) {
+                return (*hash, found);
+            }
+            last = Some(*hash);
+            found.push((key, &self.map[key]));
+        }
+        (0, found)
+    }
+}
+
+impl<V> Default for ScanMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Deref for ScanMap<V> {
+    type Target = HashMap<Bytes, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+// 0留给表示遍历开始和结束的游标
+fn scan_hash(key: &[u8]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish().max(1)
+}
@@ -2,14 +2,13 @@ use tokio::net::{TcpListener, TcpStream};
 use std::future::Future;
 use tokio::sync::{Semaphore, broadcast, mpsc};
 use std::sync::Arc;
-use tracing::{debug, error, info, instrument};
-use crate::db::Db;
+use tracing::{debug, error, info};
+use crate::db::{Db, WatchedKey};
 use tokio::time::{sleep, Duration};
 use crate::shutdown::Shutdown;
-use tokio::io::BufWriter;
-use bytes::BytesMut;
 use crate::connection::Connection;
-use crate::cmd::Command;
+use crate::cmd::{Command, Script};
+use crate::frame::Frame;
 
 #[derive(Debug)]
 struct Listener {
@@ -23,20 +22,64 @@ struct Listener {
 
 #[derive(Debug)]
 struct Handler {
+    // 这个连接当前选中的库，SELECT的时候会换掉
     db: Db,
     connection: Connection,
+    // MULTI之后排队的命令，不在事务里的时候是None
+    queued: Option<Queued>,
+    // WATCH的key和当时的版本，EXEC或者DISCARD之后清空
+    watched: Vec<WatchedKey>,
     limit_connections: Arc<Semaphore>,
     shutdown: Shutdown,
     _shutdown_complete: mpsc::Sender<()>,
 }
 
+// 等着EXEC一起执行的命令
+#[derive(Debug, Default)]
+struct Queued {
+    commands: Vec<Command>,
+    // 排队的时候有命令出错了，EXEC的时候整个事务都不执行
+    aborted: bool,
+}
+
 pub const MAX_CONNECTIONS: usize = 250;
 
 pub async fn run(listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
+    run_with_databases(listener, shutdown, crate::DEFAULT_DATABASES).await
+}
+
+// 指定库的数量，至少要有一个
+pub async fn run_with_databases(listener: TcpListener, shutdown: impl Future, databases: usize) -> crate::Result<()> {
+    let config = Config {
+        databases,
+        ..Config::default()
+    };
+    run_with_config(listener, shutdown, config).await
+}
+
+// 服务端的配置，没有指定的用默认值
+#[derive(Debug, Clone)]
+pub struct Config {
+    // 库的数量，至少要有一个
+    pub databases: usize,
+    // 脚本执行超过这个时间之后其他连接的命令都回复BUSY
+    pub script_time_limit: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            databases: crate::DEFAULT_DATABASES,
+            script_time_limit: crate::DEFAULT_SCRIPT_TIME_LIMIT,
+        }
+    }
+}
+
+pub async fn run_with_config(listener: TcpListener, shutdown: impl Future, config: Config) -> crate::Result<()> {
     let (notify_shutdown, _) = broadcast::channel(1);
     let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
     let mut server = Listener {
-        db: Db::new(),
+        db: Db::new(config.databases.max(1), config.script_time_limit),
         listener,
         limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
         notify_shutdown,
@@ -78,6 +121,8 @@ impl Listener {
             let mut handler = Handler {
                 db: self.db.clone(),
                 connection: Connection::new(socket),
+                queued: None,
+                watched: vec![],
                 limit_connections: self.limit_connections.clone(),
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
@@ -106,7 +151,7 @@ impl Listener {
             }
 
             sleep(Duration::from_secs(backoff)).await;
-            backoff * 2;
+            backoff *= 2;
         }
     }
 }
@@ -126,11 +171,124 @@ impl Handler {
                 None => return Ok(())
             };
 
-            let cmd = Command::from_frame(frame)?;
+            // 命令有问题回一个错误就行了，不用断开连接
+            let cmd = match Command::from_frame(frame) {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    self.reject(err).await?;
+                    continue;
+                }
+            };
             debug!(?cmd);
-            cmd.apply(&self.db, &mut self.connection, &mut self.shutdown).await?;
+            self.apply(cmd).await?;
+        }
+
+        Ok(())
+    }
+
+    // 事务相关的命令改的是这个连接上的状态，其他命令在事务里的话先排队
+    async fn apply(&mut self, cmd: Command) -> crate::Result<()> {
+        // 有脚本在执行的时候先等着，执行太久了就只能SCRIPT KILL
+        if !matches!(cmd, Command::Script(Script::Kill)) {
+            if let Err(err) = self.db.wait_script().await {
+                return self.reject(err).await;
+            }
+        }
+
+        // EXEC和脚本自己会独占所有的库，订阅之后就一直在等消息了，其他命令执行期间都占着一个许可
+        let permit = match &cmd {
+            Command::Exec(_) | Command::Eval(_) | Command::EvalSha(_) | Command::Script(Script::Kill)
+            | Command::Subscribe(_) | Command::PSubscribe(_) => None,
+            _ => Some(self.db.enter().await),
+        };
+        if permit.is_none() {
+            return self.dispatch(cmd).await;
         }
 
+        // 回复先攒起来，许可还回去之后再写到socket，客户端不读的话也不会一直占着许可让事务等下去
+        self.connection.start_capture();
+        let res = self.dispatch(cmd).await;
+        let replies = self.connection.finish_capture();
+        drop(permit);
+        for reply in &replies {
+            self.connection.write_frame(reply).await?;
+        }
+        res
+    }
+
+    async fn dispatch(&mut self, cmd: Command) -> crate::Result<()> {
+        let in_multi = self.queued.is_some();
+        match cmd {
+            Command::Multi(_) if in_multi => self.write_error("ERR MULTI calls can not be nested").await,
+            Command::Multi(cmd) => {
+                self.queued = Some(Queued::default());
+                cmd.apply(&mut self.connection).await
+            }
+            Command::Exec(cmd) => {
+                let queued = match self.queued.take() {
+                    Some(queued) => queued,
+                    None => return self.write_error("ERR EXEC without MULTI").await,
+                };
+                let watched = std::mem::take(&mut self.watched);
+                if queued.aborted {
+                    return self.write_error("EXECABORT Transaction discarded because of previous errors.").await;
+                }
+                cmd.apply(&mut self.db, &mut self.connection, queued.commands, watched).await
+            }
+            Command::Discard(cmd) => {
+                if self.queued.take().is_none() {
+                    return self.write_error("ERR DISCARD without MULTI").await;
+                }
+                self.watched.clear();
+                cmd.apply(&mut self.connection).await
+            }
+            Command::Watch(_) if in_multi => self.reject("ERR WATCH inside MULTI is not allowed").await,
+            Command::Watch(cmd) => cmd.apply(&self.db, &mut self.connection, &mut self.watched).await,
+            Command::Unwatch(cmd) if !in_multi => {
+                self.watched.clear();
+                self.respond(cmd.execute()).await
+            }
+            // 订阅之后连接就一直在等消息了，不能放到事务里
+            Command::Subscribe(_) | Command::PSubscribe(_) | Command::Unsubscribe(_) | Command::PUnsubscribe(_)
+                if in_multi =>
+            {
+                self.reject("ERR Command not allowed inside a transaction").await
+            }
+            // 不认识的命令直接回错误，EXEC的时候也不执行了
+            Command::Unknown(cmd) if in_multi => {
+                self.queued.as_mut().unwrap().aborted = true;
+                self.respond(cmd.execute()).await
+            }
+            cmd if in_multi => {
+                self.queued.as_mut().unwrap().commands.push(cmd);
+                self.respond(Frame::Simple("QUEUED".to_string())).await
+            }
+            cmd => cmd.apply(&mut self.db, &mut self.connection, &mut self.shutdown).await,
+        }
+    }
+
+    // 命令没有执行就回了错误，在事务里的话EXEC的时候整个事务都不执行
+    async fn reject(&mut self, err: impl ToString) -> crate::Result<()> {
+        if let Some(queued) = &mut self.queued {
+            queued.aborted = true;
+        }
+        self.write_error(err).await
+    }
+
+    async fn write_error(&mut self, err: impl ToString) -> crate::Result<()> {
+        self.respond(Frame::Error(err.to_string())).await
+    }
+
+    async fn respond(&mut self, response: Frame) -> crate::Result<()> {
+        debug!(?response);
+        self.connection.write_frame(&response).await?;
         Ok(())
     }
+}
+
+impl Drop for Handler {
+    // 连接处理完了要把许可还回去，不然连接数到上限之后就再也接不进来了
+    fn drop(&mut self) {
+        self.limit_connections.add_permits(1);
+    }
 }
\ No newline at end of file
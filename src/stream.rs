@@ -0,0 +1,686 @@
+use bytes::Bytes;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 流里消息的ID，毫秒时间戳-序号，按(ms, seq)排序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+// XADD的ID参数，`*`和`ms-*`让服务端生成
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XAddId {
+    Auto,
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+// 按长度或者按最小ID裁剪
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimStrategy {
+    MaxLen(u64),
+    MinId(StreamId),
+}
+
+// XADD和XTRIM的裁剪选项，LIMIT只能和`~`一起用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrimOptions {
+    pub strategy: TrimStrategy,
+    // `~`，不要求精确裁剪，这里还是按精确的裁
+    pub approx: bool,
+    // 最多删掉多少条
+    pub limit: Option<u64>,
+}
+
+// XCLAIM的选项，IDLE和TIME都是用来改投递时间的
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClaimOptions {
+    // 认领之后当成已经空闲了多少毫秒
+    pub idle: Option<u64>,
+    // 直接指定投递时间，unix毫秒
+    pub time: Option<u64>,
+    // 直接指定投递次数
+    pub retry_count: Option<u64>,
+    // 不在PEL里的消息也认领过来
+    pub force: bool,
+    // 只回复ID，不增加投递次数
+    pub justid: bool,
+}
+
+// XPENDING带范围的形式
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingRange {
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: u64,
+    // 只看这个消费者的
+    pub consumer: Option<Bytes>,
+    // 只看空闲了至少这么多毫秒的
+    pub idle: Option<u64>,
+}
+
+// 一条消息
+pub(crate) type StreamEntry = (StreamId, Vec<(Bytes, Bytes)>);
+
+// 从PEL里读历史的时候，已经被删掉的消息内容是None
+pub(crate) type PendingEntryFields = (StreamId, Option<Vec<(Bytes, Bytes)>>);
+
+// 消息按ID顺序放在BTreeMap里，删掉的消息不会影响last_id
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stream {
+    entries: BTreeMap<StreamId, Vec<(Bytes, Bytes)>>,
+    last_id: StreamId,
+    // 一共加过多少条消息，包括已经删掉的
+    entries_added: u64,
+    groups: BTreeMap<Bytes, Group>,
+}
+
+// 消费组，组里的消费者分着读同一个流，每条消息只发给其中一个
+#[derive(Debug, Clone)]
+pub(crate) struct Group {
+    // 最后一条发给这个组的消息
+    last_id: StreamId,
+    // 发出去还没确认的消息(PEL)
+    pending: BTreeMap<StreamId, Pending>,
+    consumers: BTreeMap<Bytes, Consumer>,
+}
+
+#[derive(Debug, Clone)]
+struct Pending {
+    consumer: Bytes,
+    // 最后一次投递的时间，unix毫秒
+    delivered_at: u64,
+    deliveries: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Consumer {
+    // 这个消费者自己的PEL，和组里的PEL保持一致
+    pending: BTreeSet<StreamId>,
+    // 最后一次来读或者认领的时间
+    seen_at: u64,
+    // 最后一次真的读到或者认领到消息的时间
+    active_at: Option<u64>,
+}
+
+// XPENDING不带范围时的汇总
+#[derive(Debug)]
+pub(crate) struct PendingSummary {
+    pub(crate) count: usize,
+    pub(crate) first: Option<StreamId>,
+    pub(crate) last: Option<StreamId>,
+    // 有待确认消息的消费者和各自的条数
+    pub(crate) consumers: Vec<(Bytes, usize)>,
+}
+
+// XPENDING带范围时的一条
+#[derive(Debug)]
+pub(crate) struct PendingEntry {
+    pub(crate) id: StreamId,
+    pub(crate) consumer: Bytes,
+    pub(crate) idle: u64,
+    pub(crate) deliveries: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct StreamInfo {
+    pub(crate) length: usize,
+    pub(crate) last_id: StreamId,
+    pub(crate) entries_added: u64,
+    pub(crate) groups: usize,
+    pub(crate) first: Option<StreamEntry>,
+    pub(crate) last: Option<StreamEntry>,
+}
+
+#[derive(Debug)]
+pub(crate) struct GroupInfo {
+    pub(crate) name: Bytes,
+    pub(crate) consumers: usize,
+    pub(crate) pending: usize,
+    pub(crate) last_id: StreamId,
+}
+
+#[derive(Debug)]
+pub(crate) struct ConsumerInfo {
+    pub(crate) name: Bytes,
+    pub(crate) pending: usize,
+    pub(crate) idle: u64,
+    // 从来没读到过消息是None
+    pub(crate) inactive: Option<u64>,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    pub fn new(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    // 紧挨着的下一个ID，开区间`(`转成闭区间用
+    pub(crate) fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| StreamId::new(ms, 0)),
+        }
+    }
+
+    pub(crate) fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_sub(1).map(|ms| StreamId::new(ms, u64::MAX)),
+        }
+    }
+
+    // 解析`ms-seq`，只有ms的时候序号用默认值
+    pub(crate) fn parse(s: &[u8], default_seq: u64) -> Option<StreamId> {
+        let s = std::str::from_utf8(s).ok()?;
+        match s.find('-') {
+            Some(i) => Some(StreamId::new(parse_u64(&s[..i])?, parse_u64(&s[i + 1..])?)),
+            None => Some(StreamId::new(parse_u64(s)?, default_seq)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl XAddId {
+    pub(crate) fn parse(s: &[u8]) -> Option<XAddId> {
+        if s == b"*" {
+            return Some(XAddId::Auto);
+        }
+        if let Some(ms) = s.strip_suffix(b"-*") {
+            return Some(XAddId::AutoSeq(parse_u64(std::str::from_utf8(ms).ok()?)?));
+        }
+        StreamId::parse(s, 0).map(XAddId::Explicit)
+    }
+}
+
+impl fmt::Display for XAddId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XAddId::Auto => "*".fmt(f),
+            XAddId::AutoSeq(ms) => write!(f, "{}-*", ms),
+            XAddId::Explicit(id) => id.fmt(f),
+        }
+    }
+}
+
+impl Stream {
+    pub(crate) fn new() -> Stream {
+        Stream::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    // 追加一条消息，新的ID必须比之前所有的都大
+    pub(crate) fn add(&mut self, id: XAddId, fields: Vec<(Bytes, Bytes)>) -> crate::Result<StreamId> {
+        const SMALLER: &str = "ERR The ID specified in XADD is equal or smaller than the target stream top item";
+        let last = self.last_id;
+
+        let id = match id {
+            XAddId::Auto => {
+                let now = now_ms();
+                // 时钟往回走了就接着上一个ID的毫秒数继续
+                if now > last.ms {
+                    StreamId::new(now, 0)
+                } else {
+                    last.next().ok_or("ERR The stream has exhausted the last possible ID, unable to add more items")?
+                }
+            }
+            XAddId::AutoSeq(ms) if ms > last.ms => StreamId::new(ms, 0),
+            XAddId::AutoSeq(ms) if ms == last.ms => {
+                let seq = last.seq.checked_add(1).ok_or(SMALLER)?;
+                // 空的流上用0-*，第一条是0-1
+                StreamId::new(ms, seq)
+            }
+            XAddId::AutoSeq(_) => return Err(SMALLER.into()),
+            XAddId::Explicit(id) => {
+                if id == StreamId::MIN {
+                    return Err("ERR The ID specified in XADD must be greater than 0-0".into());
+                }
+                if id <= last {
+                    return Err(SMALLER.into());
+                }
+                id
+            }
+        };
+
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+        Ok(id)
+    }
+
+    // [start, end]之间的消息，rev的时候从后往前
+    pub(crate) fn range(&self, start: StreamId, end: StreamId, count: Option<usize>, rev: bool) -> Vec<StreamEntry> {
+        if start > end {
+            return vec![];
+        }
+
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let clone = |(id, fields): (&StreamId, &Vec<(Bytes, Bytes)>)| (*id, fields.clone());
+        if rev {
+            range.rev().take(count).map(clone).collect()
+        } else {
+            range.take(count).map(clone).collect()
+        }
+    }
+
+    // ID比after大的消息，XREAD用
+    pub(crate) fn after(&self, after: StreamId, count: Option<usize>) -> Vec<StreamEntry> {
+        match after.next() {
+            Some(start) => self.range(start, StreamId::MAX, count, false),
+            None => vec![],
+        }
+    }
+
+    // 从最老的消息开始删，返回删掉的条数
+    pub(crate) fn trim(&mut self, options: &TrimOptions) -> usize {
+        let limit = options.limit.map_or(usize::MAX, |limit| limit as usize);
+        let mut removed = 0;
+        while removed < limit {
+            let first = match self.entries.keys().next() {
+                Some(first) => *first,
+                None => break,
+            };
+            let evict = match options.strategy {
+                TrimStrategy::MaxLen(max) => self.entries.len() as u64 > max,
+                TrimStrategy::MinId(min) => first < min,
+            };
+            if !evict {
+                break;
+            }
+            self.entries.remove(&first);
+            removed += 1;
+        }
+        removed
+    }
+}
+
+impl Stream {
+    // 新建消费组，id为None表示从最新的消息之后开始，组已经存在返回false
+    pub(crate) fn create_group(&mut self, name: Bytes, id: Option<StreamId>) -> bool {
+        if self.groups.contains_key(&name) {
+            return false;
+        }
+        let last_id = id.unwrap_or(self.last_id);
+        self.groups.insert(name, Group::new(last_id));
+        true
+    }
+
+    pub(crate) fn destroy_group(&mut self, name: &[u8]) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    pub(crate) fn group(&self, name: &[u8]) -> Option<&Group> {
+        self.groups.get(name)
+    }
+
+    pub(crate) fn group_mut(&mut self, name: &[u8]) -> Option<&mut Group> {
+        self.groups.get_mut(name)
+    }
+
+    // 给消费者读消息，after为None表示读还没发给组里任何人的新消息(`>`)，
+    // 否则读这个消费者自己PEL里ID比after大的消息，消息已经被删掉的话内容是None
+    pub(crate) fn read_group(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        after: Option<StreamId>,
+        count: Option<usize>,
+        noack: bool,
+    ) -> Option<Vec<PendingEntryFields>> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        group.seen(consumer, now);
+        let count = count.unwrap_or(usize::MAX);
+
+        let found: Vec<_> = match after {
+            None => {
+                let start = match group.last_id.next() {
+                    Some(start) => start,
+                    None => return Some(vec![]),
+                };
+                let found: Vec<_> = self
+                    .entries
+                    .range(start..)
+                    .take(count)
+                    .map(|(id, fields)| (*id, Some(fields.clone())))
+                    .collect();
+                for (id, _) in &found {
+                    group.last_id = *id;
+                    if !noack {
+                        group.deliver(*id, consumer, now, 1);
+                    }
+                }
+                found
+            }
+            Some(after) => {
+                let ids: Vec<StreamId> = group.consumers[consumer]
+                    .pending
+                    .range((std::ops::Bound::Excluded(after), std::ops::Bound::Unbounded))
+                    .take(count)
+                    .copied()
+                    .collect();
+                for id in &ids {
+                    let pending = group.pending.get_mut(id).unwrap();
+                    pending.delivered_at = now;
+                    pending.deliveries += 1;
+                }
+                let entries = &self.entries;
+                ids.into_iter().map(|id| (id, entries.get(&id).cloned())).collect()
+            }
+        };
+
+        if !found.is_empty() {
+            group.active(consumer, now);
+        }
+        Some(found)
+    }
+
+    // 把空闲时间不少于min_idle的待确认消息转给consumer，已经被删掉的消息直接从PEL里去掉
+    pub(crate) fn claim(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Option<Vec<StreamEntry>> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        group.seen(consumer, now);
+        let delivered_at = match (options.time, options.idle) {
+            (Some(time), _) => time,
+            (None, Some(idle)) => now.saturating_sub(idle),
+            (None, None) => now,
+        };
+
+        let mut claimed = vec![];
+        for id in ids {
+            let fields = match self.entries.get(id) {
+                Some(fields) => fields,
+                None => {
+                    group.unpend(id);
+                    continue;
+                }
+            };
+
+            match group.pending.get(id) {
+                Some(pending) if now.saturating_sub(pending.delivered_at) < min_idle => continue,
+                Some(_) => {}
+                // 强制认领的消息之前没有投递过
+                None if options.force => group.deliver(*id, consumer, now, 0),
+                None => continue,
+            }
+
+            group.transfer(*id, consumer);
+            let pending = group.pending.get_mut(id).unwrap();
+            pending.delivered_at = delivered_at;
+            match options.retry_count {
+                Some(retry_count) => pending.deliveries = retry_count,
+                None if !options.justid => pending.deliveries += 1,
+                None => {}
+            }
+            claimed.push((*id, fields.clone()));
+        }
+
+        if !claimed.is_empty() {
+            group.active(consumer, now);
+        }
+        Some(claimed)
+    }
+
+    // 从start开始扫PEL，最多认领count条，最多看count*10条。
+    // 返回下一次开始的ID(扫完了是0-0)、认领到的消息和已经被删掉的消息ID
+    pub(crate) fn autoclaim(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> Option<(StreamId, Vec<StreamEntry>, Vec<StreamId>)> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        group.seen(consumer, now);
+
+        let mut scan = group.pending.range(start..).map(|(id, _)| *id);
+        let ids: Vec<StreamId> = scan.by_ref().take(count.saturating_mul(10)).collect();
+        let mut next = scan.next();
+
+        let mut claimed = vec![];
+        let mut deleted = vec![];
+        for id in &ids {
+            if claimed.len() == count {
+                next = Some(*id);
+                break;
+            }
+            let fields = match self.entries.get(id) {
+                Some(fields) => fields,
+                None => {
+                    group.unpend(id);
+                    deleted.push(*id);
+                    continue;
+                }
+            };
+            if now.saturating_sub(group.pending[id].delivered_at) < min_idle {
+                continue;
+            }
+
+            group.transfer(*id, consumer);
+            let pending = group.pending.get_mut(id).unwrap();
+            pending.delivered_at = now;
+            if !justid {
+                pending.deliveries += 1;
+            }
+            claimed.push((*id, fields.clone()));
+        }
+
+        if !claimed.is_empty() {
+            group.active(consumer, now);
+        }
+        Some((next.unwrap_or(StreamId::MIN), claimed, deleted))
+    }
+
+    pub(crate) fn info(&self) -> StreamInfo {
+        let clone = |(id, fields): (&StreamId, &Vec<(Bytes, Bytes)>)| (*id, fields.clone());
+        StreamInfo {
+            length: self.entries.len(),
+            last_id: self.last_id,
+            entries_added: self.entries_added,
+            groups: self.groups.len(),
+            first: self.entries.iter().next().map(clone),
+            last: self.entries.iter().next_back().map(clone),
+        }
+    }
+
+    pub(crate) fn groups_info(&self) -> Vec<GroupInfo> {
+        self.groups
+            .iter()
+            .map(|(name, group)| GroupInfo {
+                name: name.clone(),
+                consumers: group.consumers.len(),
+                pending: group.pending.len(),
+                last_id: group.last_id,
+            })
+            .collect()
+    }
+}
+
+impl Group {
+    fn new(last_id: StreamId) -> Group {
+        Group {
+            last_id,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn set_last_id(&mut self, id: StreamId) {
+        self.last_id = id;
+    }
+
+    // 已经存在返回false
+    pub(crate) fn create_consumer(&mut self, name: Bytes) -> bool {
+        if self.consumers.contains_key(&name) {
+            return false;
+        }
+        self.consumers.insert(name, Consumer::new(now_ms()));
+        true
+    }
+
+    // 删掉消费者，它的待确认消息也一起删掉，返回删掉的条数，消费者不存在返回None
+    pub(crate) fn delete_consumer(&mut self, name: &[u8]) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in &consumer.pending {
+            self.pending.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+
+    // 确认消息，返回确实在PEL里的条数
+    pub(crate) fn ack(&mut self, ids: &[StreamId]) -> usize {
+        ids.iter().filter(|id| self.unpend(id)).count()
+    }
+
+    pub(crate) fn summary(&self) -> PendingSummary {
+        PendingSummary {
+            count: self.pending.len(),
+            first: self.pending.keys().next().copied(),
+            last: self.pending.keys().next_back().copied(),
+            consumers: self
+                .consumers
+                .iter()
+                .filter(|(_, consumer)| !consumer.pending.is_empty())
+                .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+                .collect(),
+        }
+    }
+
+    // 范围内的待确认消息，可以只看某个消费者的或者空闲够久的
+    pub(crate) fn pending_range(&self, range: &PendingRange) -> Vec<PendingEntry> {
+        if range.start > range.end {
+            return vec![];
+        }
+
+        let now = now_ms();
+        self.pending
+            .range(range.start..=range.end)
+            .filter(|(_, pending)| range.consumer.as_ref().is_none_or(|consumer| pending.consumer == consumer))
+            .map(|(id, pending)| PendingEntry {
+                id: *id,
+                consumer: pending.consumer.clone(),
+                idle: now.saturating_sub(pending.delivered_at),
+                deliveries: pending.deliveries,
+            })
+            .filter(|entry| range.idle.is_none_or(|idle| entry.idle >= idle))
+            .take(range.count as usize)
+            .collect()
+    }
+
+    pub(crate) fn consumers_info(&self) -> Vec<ConsumerInfo> {
+        let now = now_ms();
+        self.consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: consumer.pending.len(),
+                idle: now.saturating_sub(consumer.seen_at),
+                inactive: consumer.active_at.map(|active_at| now.saturating_sub(active_at)),
+            })
+            .collect()
+    }
+
+    // 消费者第一次出现就自动创建
+    fn seen(&mut self, consumer: &Bytes, now: u64) {
+        self.consumers
+            .entry(consumer.clone())
+            .or_insert_with(|| Consumer::new(now))
+            .seen_at = now;
+    }
+
+    fn active(&mut self, consumer: &Bytes, now: u64) {
+        if let Some(consumer) = self.consumers.get_mut(consumer) {
+            consumer.active_at = Some(now);
+        }
+    }
+
+    // 新投递一条消息，已经在别的消费者的PEL里的话转过来，投递次数重新算
+    fn deliver(&mut self, id: StreamId, consumer: &Bytes, now: u64, deliveries: u64) {
+        self.unpend(&id);
+        self.pending.insert(id, Pending {
+            consumer: consumer.clone(),
+            delivered_at: now,
+            deliveries,
+        });
+        self.consumers.get_mut(consumer).unwrap().pending.insert(id);
+    }
+
+    // 把PEL里的一条消息转给consumer
+    fn transfer(&mut self, id: StreamId, consumer: &Bytes) {
+        let pending = self.pending.get_mut(&id).unwrap();
+        if pending.consumer == consumer {
+            return;
+        }
+        if let Some(owner) = self.consumers.get_mut(&pending.consumer) {
+            owner.pending.remove(&id);
+        }
+        pending.consumer = consumer.clone();
+        self.consumers.get_mut(consumer).unwrap().pending.insert(id);
+    }
+
+    // 从组和消费者的PEL里删掉，返回之前在不在
+    fn unpend(&mut self, id: &StreamId) -> bool {
+        match self.pending.remove(id) {
+            Some(pending) => {
+                if let Some(consumer) = self.consumers.get_mut(&pending.consumer) {
+                    consumer.pending.remove(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Consumer {
+    fn new(now: u64) -> Consumer {
+        Consumer {
+            pending: BTreeSet::new(),
+            seen_at: now,
+            active_at: None,
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// 不接受正负号，和Redis一样
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
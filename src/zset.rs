@@ -0,0 +1,457 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+
+// 有序集合 = 成员到分数的哈希表 + 按(分数, 成员)排序的跳表
+// 哈希表负责O(1)查分数，跳表负责O(log n)的排名和范围查询
+#[derive(Debug, Clone)]
+pub(crate) struct ZSet {
+    dict: HashMap<Bytes, f64>,
+    list: SkipList,
+}
+
+// 分数区间的一端，Exclusive对应参数里的`(`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+// 字典序区间的一端，Min和Max对应参数里的`-`和`+`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+// ZRANGE按什么取范围，按排名的时候下标可以是负数
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+// ZADD的选项，冲突的组合在解析命令的时候就拦下来了
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZAddOptions {
+    // 只添加新成员
+    pub nx: bool,
+    // 只更新已有的成员
+    pub xx: bool,
+    // 新分数比原来的大才更新
+    pub gt: bool,
+    // 新分数比原来的小才更新
+    pub lt: bool,
+    // 回复里把分数变了的成员也算上
+    pub ch: bool,
+    // 把分数加到原来的分数上
+    pub incr: bool,
+}
+
+// 添加一个成员的结果，带上最终的分数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ZAddResult {
+    Added(f64),
+    Updated(f64),
+    Unchanged(f64),
+    // 被NX/XX/GT/LT拦下来了
+    Skipped,
+}
+
+impl ScoreBound {
+    // 分数在区间下界的左边
+    fn before_min(&self, score: f64) -> bool {
+        match *self {
+            ScoreBound::Inclusive(min) => score < min,
+            ScoreBound::Exclusive(min) => score <= min,
+        }
+    }
+
+    // 分数没有超过区间上界
+    fn within_max(&self, score: f64) -> bool {
+        match *self {
+            ScoreBound::Inclusive(max) => score <= max,
+            ScoreBound::Exclusive(max) => score < max,
+        }
+    }
+}
+
+impl LexBound {
+    fn before_min(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(min) => member < &min[..],
+            LexBound::Exclusive(min) => member <= &min[..],
+        }
+    }
+
+    fn within_max(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(max) => member <= &max[..],
+            LexBound::Exclusive(max) => member < &max[..],
+        }
+    }
+}
+
+impl ZSet {
+    pub(crate) fn new() -> Self {
+        Self {
+            dict: HashMap::new(),
+            list: SkipList::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.dict.is_empty()
+    }
+
+    pub(crate) fn score(&self, member: &[u8]) -> Option<f64> {
+        self.dict.get(member).copied()
+    }
+
+    // 添加或者更新一个成员，INCR的时候score是增量
+    pub(crate) fn add(&mut self, member: Bytes, score: f64, options: &ZAddOptions) -> crate::Result<ZAddResult> {
+        let current = match self.dict.get(&member) {
+            Some(current) => *current,
+            None => {
+                if options.xx {
+                    return Ok(ZAddResult::Skipped);
+                }
+                self.list.insert(score, member.clone());
+                self.dict.insert(member, score);
+                return Ok(ZAddResult::Added(score));
+            }
+        };
+
+        if options.nx {
+            return Ok(ZAddResult::Skipped);
+        }
+
+        let score = if options.incr { current + score } else { score };
+        if score.is_nan() {
+            return Err("ERR resulting score is not a number (NaN)".into());
+        }
+        if (options.gt && score <= current) || (options.lt && score >= current) {
+            return Ok(ZAddResult::Skipped);
+        }
+        if score == current {
+            return Ok(ZAddResult::Unchanged(score));
+        }
+
+        // 分数变了，在跳表里的位置也要跟着变
+        self.list.remove(current, &member);
+        self.list.insert(score, member.clone());
+        self.dict.insert(member, score);
+        Ok(ZAddResult::Updated(score))
+    }
+
+    pub(crate) fn remove(&mut self, member: &[u8]) -> bool {
+        match self.dict.remove(member) {
+            Some(score) => {
+                self.list.remove(score, member);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 按分数从小到大的排名，从0开始
+    pub(crate) fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.list.count_while(|node| node.less(score, member)))
+    }
+
+    // 取出范围内的成员和分数，rev表示从大到小，limit是(offset, count)，count为负数表示不限制
+    pub(crate) fn range(&self, by: &RangeBy, rev: bool, limit: Option<(i64, i64)>) -> Vec<(Bytes, f64)> {
+        let len = self.len() as i64;
+
+        // 先算出范围在正序里对应的排名区间[start, end)
+        let (start, end) = match by {
+            RangeBy::Rank(start, stop) => {
+                let start = if *start < 0 { (len + start).max(0) } else { *start };
+                let stop = if *stop < 0 { len + stop } else { (*stop).min(len - 1) };
+                if start > stop || start >= len {
+                    return vec![];
+                }
+                // 倒序的时候下标是从最大的那个开始数的
+                if rev {
+                    (len - 1 - stop, len - start)
+                } else {
+                    (start, stop + 1)
+                }
+            }
+            RangeBy::Score(min, max) => (
+                self.list.count_while(|node| min.before_min(node.score)) as i64,
+                self.list.count_while(|node| max.within_max(node.score)) as i64,
+            ),
+            RangeBy::Lex(min, max) => (
+                self.list.count_while(|node| min.before_min(&node.member)) as i64,
+                self.list.count_while(|node| max.within_max(&node.member)) as i64,
+            ),
+        };
+
+        let (offset, count) = limit.unwrap_or((0, -1));
+        // offset是用户给的，可能大到加上start就溢出，所以跟区间长度比
+        if offset < 0 || offset >= end - start {
+            return vec![];
+        }
+        let available = end - start - offset;
+        let count = if count < 0 { available } else { count.min(available) };
+        if count == 0 {
+            return vec![];
+        }
+
+        let first = if rev { end - 1 - offset } else { start + offset };
+        self.list.walk(first as usize, count as usize, rev)
+    }
+}
+
+const MAX_LEVEL: usize = 32;
+
+// 空指针
+const NIL: usize = usize::MAX;
+
+// 节点都放在一个Vec里，用下标代替指针，0号是不存数据的头节点
+#[derive(Debug, Clone)]
+struct SkipList {
+    nodes: Vec<Node>,
+    // 删掉的节点留下来的空位
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+    seed: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    backward: usize,
+    levels: Vec<Level>,
+}
+
+// span是到下一个节点跨过了多少个节点，用来算排名
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    next: usize,
+    span: usize,
+}
+
+impl Node {
+    // 先按分数排，分数一样的按成员的字典序排
+    fn less(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || (self.score == score && &self.member[..] < member)
+    }
+}
+
+impl SkipList {
+    fn new() -> Self {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            backward: NIL,
+            levels: vec![Level { next: NIL, span: 0 }; MAX_LEVEL],
+        };
+
+        Self {
+            nodes: vec![head],
+            free: vec![],
+            level: 1,
+            len: 0,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    // 每往上一层的概率是1/4
+    fn random_level(&mut self) -> usize {
+        let mut level = 1;
+        loop {
+            // xorshift，够用了，不用为了这个引入rand
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            if level < MAX_LEVEL && self.seed & 3 == 0 {
+                level += 1;
+            } else {
+                return level;
+            }
+        }
+    }
+
+    fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [0; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+
+        let mut x = 0;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            loop {
+                let Level { next, span } = self.nodes[x].levels[i];
+                if next != NIL && self.nodes[next].less(score, &member) {
+                    rank[i] += span;
+                    x = next;
+                } else {
+                    break;
+                }
+            }
+            update[i] = x;
+        }
+
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = 0;
+                self.nodes[0].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = self.alloc(Node {
+            member,
+            score,
+            backward: if update[0] == 0 { NIL } else { update[0] },
+            levels: vec![Level { next: NIL, span: 0 }; level],
+        });
+
+        for i in 0..level {
+            let prev = update[i];
+            let Level { next, span } = self.nodes[prev].levels[i];
+            self.nodes[node].levels[i] = Level { next, span: span - (rank[0] - rank[i]) };
+            self.nodes[prev].levels[i] = Level { next: node, span: rank[0] - rank[i] + 1 };
+        }
+        // 更高的层没有指向新节点，但是跨过了它
+        for (i, prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*prev].levels[i].span += 1;
+        }
+
+        let next = self.nodes[node].levels[0].next;
+        if next != NIL {
+            self.nodes[next].backward = node;
+        }
+        self.len += 1;
+    }
+
+    fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [0; MAX_LEVEL];
+
+        let mut x = 0;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.nodes[x].levels[i].next;
+                if next != NIL && self.nodes[next].less(score, member) {
+                    x = next;
+                } else {
+                    break;
+                }
+            }
+            update[i] = x;
+        }
+
+        let x = self.nodes[x].levels[0].next;
+        if x == NIL || self.nodes[x].score != score || self.nodes[x].member != member {
+            return false;
+        }
+
+        for (i, prev) in update.iter().enumerate().take(self.level) {
+            let removed = self.nodes[x].levels.get(i).copied();
+            let level = &mut self.nodes[*prev].levels[i];
+            match removed {
+                Some(removed) if level.next == x => {
+                    level.span += removed.span;
+                    level.span -= 1;
+                    level.next = removed.next;
+                }
+                _ => level.span -= 1,
+            }
+        }
+
+        let (next, backward) = (self.nodes[x].levels[0].next, self.nodes[x].backward);
+        if next != NIL {
+            self.nodes[next].backward = backward;
+        }
+        while self.level > 1 && self.nodes[0].levels[self.level - 1].next == NIL {
+            self.level -= 1;
+        }
+        self.len -= 1;
+
+        self.nodes[x].member = Bytes::new();
+        self.nodes[x].levels = vec![];
+        self.free.push(x);
+        true
+    }
+
+    // 从头开始数有多少个节点满足pred，pred必须是对前面一段节点成立、后面都不成立的
+    fn count_while(&self, pred: impl Fn(&Node) -> bool) -> usize {
+        let mut x = 0;
+        let mut rank = 0;
+        for i in (0..self.level).rev() {
+            loop {
+                let Level { next, span } = self.nodes[x].levels[i];
+                if next != NIL && pred(&self.nodes[next]) {
+                    rank += span;
+                    x = next;
+                } else {
+                    break;
+                }
+            }
+        }
+        rank
+    }
+
+    // 排名为rank(从0开始)的节点
+    fn get_by_rank(&self, rank: usize) -> usize {
+        let target = rank + 1;
+        let mut x = 0;
+        let mut traversed = 0;
+        for i in (0..self.level).rev() {
+            loop {
+                let Level { next, span } = self.nodes[x].levels[i];
+                if next != NIL && traversed + span <= target {
+                    traversed += span;
+                    x = next;
+                } else {
+                    break;
+                }
+            }
+            if traversed == target {
+                return x;
+            }
+        }
+        NIL
+    }
+
+    // 从排名为first的节点开始往后(rev往前)取count个
+    fn walk(&self, first: usize, count: usize, rev: bool) -> Vec<(Bytes, f64)> {
+        let mut result = Vec::with_capacity(count);
+        let mut x = self.get_by_rank(first);
+        while x != NIL && result.len() < count {
+            let node = &self.nodes[x];
+            result.push((node.member.clone(), node.score));
+            x = if rev { node.backward } else { node.levels[0].next };
+        }
+        result
+    }
+
+    fn alloc(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
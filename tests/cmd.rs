@@ -0,0 +1,565 @@
+use bytes::Bytes;
+use proptest::prelude::*;
+use std::time::Duration;
+use w::cmd::{Command, Get, Set, SetCondition, Publish, Subscribe, Unsubscribe, PSubscribe, PUnsubscribe, PubSub, Hello, Unknown};
+use w::cmd::{Append, GetRange, SetRange, StrLen, GetDel, GetEx, MGet, MSet, MSetNx, Expiry};
+use w::cmd::{Incr, Decr, IncrBy, DecrBy, IncrByFloat};
+use w::cmd::{Del, Unlink, Exists, Touch, Type, Rename, RenameNx, Copy, Keys, Scan};
+use w::cmd::{Select, SwapDb, Move, FlushDb, FlushAll};
+use w::cmd::{Multi, Exec, Discard, Watch, Unwatch};
+use w::cmd::{Eval, EvalSha, Script};
+use w::cmd::{Expire, PExpire, ExpireAt, PExpireAt, Ttl, PTtl, Persist, ExpireTime, PExpireTime, ExpireOptions};
+use w::cmd::{LPush, RPush, LPop, RPop, LRange, LLen, BLPop, BRPop, BLMove, Direction};
+use w::cmd::{HSet, HGet, HMGet, HDel, HExists, HLen, HKeys, HVals, HGetAll, HIncrBy, HIncrByFloat, HScan};
+use w::cmd::{SAdd, SRem, SIsMember, SMembers, SCard, SInter, SUnion, SDiff, SInterStore, SUnionStore, SDiffStore};
+use w::cmd::{XAdd, XRange, XRead, XLen, XTrim, StreamId, XAddId, TrimStrategy, TrimOptions};
+use w::cmd::{XGroup, XReadGroup, XAck, XPending, XClaim, XAutoClaim, XInfo, ClaimOptions, PendingRange};
+use w::cmd::{ZAdd, ZIncrBy, ZRem, ZScore, ZCard, ZRank, ZRange, ZRangeByScore, ZAddOptions, ScoreBound, LexBound, RangeBy};
+
+// 已经实现了的命令名，生成Unknown的时候要排除掉
+const COMMANDS: &[&str] = &[
+    "get", "set", "publish", "subscribe", "unsubscribe", "psubscribe", "punsubscribe", "pubsub",
+    "hello", "lpush", "rpush", "lpop", "rpop", "lrange", "llen",
+    "blpop", "brpop", "blmove", "hset", "hget", "hmget", "hdel", "hexists", "hlen", "hkeys", "hvals",
+    "hgetall", "hincrby", "hincrbyfloat", "hscan", "sadd", "srem", "sismember", "smembers", "scard",
+    "sinter", "sunion", "sdiff", "sinterstore", "sunionstore", "sdiffstore", "zadd", "zincrby", "zrem",
+    "zscore", "zcard", "zrank", "zrange", "zrangebyscore", "xadd", "xrange", "xread", "xlen", "xtrim",
+    "xgroup", "xreadgroup", "xack", "xpending", "xclaim", "xautoclaim", "xinfo", "append", "getrange",
+    "setrange", "strlen", "getdel", "getex", "mget", "mset", "msetnx", "incr", "decr", "incrby", "decrby",
+    "incrbyfloat", "expire", "pexpire", "expireat", "pexpireat", "ttl", "pttl", "persist", "expiretime",
+    "pexpiretime", "del", "unlink", "exists", "touch", "type", "rename", "renamenx", "copy",
+    "keys", "scan", "select", "swapdb", "move", "flushdb", "flushall", "multi", "exec", "discard",
+    "watch", "unwatch", "eval", "evalsha", "script",
+];
+
+fn elements() -> impl Strategy<Value = Vec<Bytes>> {
+    proptest::collection::vec(any::<Vec<u8>>().prop_map(Bytes::from), 1..8)
+}
+
+// 阻塞超时是按秒传的，精确到毫秒
+fn timeout() -> impl Strategy<Value = Duration> {
+    any::<u32>().prop_map(|ms| Duration::from_millis(ms as u64))
+}
+
+// 计数、偏移之类的参数走的是有符号整数解析，不能超过i64的范围
+fn count() -> impl Strategy<Value = u64> {
+    0..=i64::MAX as u64
+}
+
+fn direction() -> impl Strategy<Value = Direction> {
+    prop_oneof![Just(Direction::Left), Just(Direction::Right)]
+}
+
+fn string_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    let value = || any::<Vec<u8>>().prop_map(Bytes::from);
+    let pairs = || proptest::collection::vec((value(), value()), 1..8);
+    // 解析的时候过期时间要大于0，并且不能超过i64的范围
+    let expiry = prop_oneof![
+        (1..=i64::MAX as u64).prop_map(|ms| Expiry::In(Duration::from_millis(ms))),
+        (1..=i64::MAX as u64).prop_map(Expiry::At),
+        Just(Expiry::Persist),
+    ];
+    let condition = prop_oneof![Just(SetCondition::Nx), Just(SetCondition::Xx)];
+    // 外层的None是不带过期时间，Some(None)是KEEPTTL
+    let ttl = proptest::option::of(proptest::option::of(prop_oneof![
+        (1..=i64::MAX as u64).prop_map(|ms| Expiry::In(Duration::from_millis(ms))),
+        (1..=i64::MAX as u64).prop_map(Expiry::At),
+    ]));
+    prop_oneof![
+        (key(), value(), proptest::option::of(condition), any::<bool>(), ttl).prop_map(|(key, value, condition, get, ttl)| {
+            let mut set = Set::new(key, value, None);
+            if let Some(condition) = condition {
+                set = set.with_condition(condition);
+            }
+            if get {
+                set = set.with_get();
+            }
+            match ttl {
+                Some(Some(expiry)) => set = set.with_expiry(expiry),
+                Some(None) => set = set.with_keep_ttl(),
+                None => {}
+            }
+            Command::Set(set)
+        }),
+        (key(), value()).prop_map(|(key, value)| Command::Append(Append::new(key, value))),
+        (key(), any::<i64>(), any::<i64>()).prop_map(|(key, start, end)| Command::GetRange(GetRange::new(key, start, end))),
+        (key(), count(), value()).prop_map(|(key, offset, value)| Command::SetRange(SetRange::new(key, offset, value))),
+        key().prop_map(|key| Command::StrLen(StrLen::new(key))),
+        key().prop_map(|key| Command::GetDel(GetDel::new(key))),
+        (key(), proptest::option::of(expiry)).prop_map(|(key, expiry)| {
+            let mut getex = GetEx::new(key);
+            if let Some(expiry) = expiry {
+                getex = getex.with_expiry(expiry);
+            }
+            Command::GetEx(getex)
+        }),
+        elements().prop_map(|keys| Command::MGet(MGet::new(keys))),
+        pairs().prop_map(|pairs| Command::MSet(MSet::new(pairs))),
+        pairs().prop_map(|pairs| Command::MSetNx(MSetNx::new(pairs))),
+        key().prop_map(|key| Command::Incr(Incr::new(key))),
+        key().prop_map(|key| Command::Decr(Decr::new(key))),
+        (key(), any::<i64>()).prop_map(|(key, increment)| Command::IncrBy(IncrBy::new(key, increment))),
+        (key(), any::<i64>()).prop_map(|(key, decrement)| Command::DecrBy(DecrBy::new(key, decrement))),
+        (key(), any::<f64>().prop_filter("nan", |f| !f.is_nan()))
+            .prop_map(|(key, increment)| Command::IncrByFloat(IncrByFloat::new(key, increment))),
+    ]
+}
+
+fn list_command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        (any::<Vec<u8>>(), elements()).prop_map(|(key, elements)| Command::LPush(LPush::new(key, elements))),
+        (any::<Vec<u8>>(), elements()).prop_map(|(key, elements)| Command::RPush(RPush::new(key, elements))),
+        (any::<Vec<u8>>(), proptest::option::of(count()))
+            .prop_map(|(key, count)| Command::LPop(LPop::new(key, count))),
+        (any::<Vec<u8>>(), proptest::option::of(count()))
+            .prop_map(|(key, count)| Command::RPop(RPop::new(key, count))),
+        (any::<Vec<u8>>(), any::<i64>(), any::<i64>())
+            .prop_map(|(key, start, stop)| Command::LRange(LRange::new(key, start, stop))),
+        any::<Vec<u8>>().prop_map(|key| Command::LLen(LLen::new(key))),
+        (elements(), timeout()).prop_map(|(keys, timeout)| Command::BLPop(BLPop::new(keys, timeout))),
+        (elements(), timeout()).prop_map(|(keys, timeout)| Command::BRPop(BRPop::new(keys, timeout))),
+        (any::<Vec<u8>>(), any::<Vec<u8>>(), direction(), direction(), timeout())
+            .prop_map(|(source, destination, wherefrom, whereto, timeout)| {
+                Command::BLMove(BLMove::new(source, destination, wherefrom, whereto, timeout))
+            }),
+    ]
+}
+
+fn hash_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    prop_oneof![
+        (key(), proptest::collection::vec((any::<Vec<u8>>(), any::<Vec<u8>>()), 1..8)).prop_map(|(key, pairs)| {
+            let pairs = pairs.into_iter().map(|(field, value)| (Bytes::from(field), Bytes::from(value))).collect();
+            Command::HSet(HSet::new(key, pairs))
+        }),
+        (key(), key()).prop_map(|(key, field)| Command::HGet(HGet::new(key, field))),
+        (key(), elements()).prop_map(|(key, fields)| Command::HMGet(HMGet::new(key, fields))),
+        (key(), elements()).prop_map(|(key, fields)| Command::HDel(HDel::new(key, fields))),
+        (key(), key()).prop_map(|(key, field)| Command::HExists(HExists::new(key, field))),
+        key().prop_map(|key| Command::HLen(HLen::new(key))),
+        key().prop_map(|key| Command::HKeys(HKeys::new(key))),
+        key().prop_map(|key| Command::HVals(HVals::new(key))),
+        key().prop_map(|key| Command::HGetAll(HGetAll::new(key))),
+        (key(), key(), any::<i64>())
+            .prop_map(|(key, field, increment)| Command::HIncrBy(HIncrBy::new(key, field, increment))),
+        (key(), key(), any::<f64>().prop_filter("nan", |f| !f.is_nan()))
+            .prop_map(|(key, field, increment)| Command::HIncrByFloat(HIncrByFloat::new(key, field, increment))),
+        (key(), any::<u64>(), proptest::option::of(key()), proptest::option::of(1..=i64::MAX as u64), any::<bool>())
+            .prop_map(|(key, cursor, pattern, count, novalues)| {
+                let mut hscan = HScan::new(key, cursor);
+                if let Some(pattern) = pattern {
+                    hscan = hscan.with_pattern(pattern);
+                }
+                if let Some(count) = count {
+                    hscan = hscan.with_count(count);
+                }
+                if novalues {
+                    hscan = hscan.with_novalues();
+                }
+                Command::HScan(hscan)
+            }),
+    ]
+}
+
+fn set_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    prop_oneof![
+        (key(), elements()).prop_map(|(key, members)| Command::SAdd(SAdd::new(key, members))),
+        (key(), elements()).prop_map(|(key, members)| Command::SRem(SRem::new(key, members))),
+        (key(), key()).prop_map(|(key, member)| Command::SIsMember(SIsMember::new(key, member))),
+        key().prop_map(|key| Command::SMembers(SMembers::new(key))),
+        key().prop_map(|key| Command::SCard(SCard::new(key))),
+        elements().prop_map(|keys| Command::SInter(SInter::new(keys))),
+        elements().prop_map(|keys| Command::SUnion(SUnion::new(keys))),
+        elements().prop_map(|keys| Command::SDiff(SDiff::new(keys))),
+        (key(), elements()).prop_map(|(destination, keys)| Command::SInterStore(SInterStore::new(destination, keys))),
+        (key(), elements()).prop_map(|(destination, keys)| Command::SUnionStore(SUnionStore::new(destination, keys))),
+        (key(), elements()).prop_map(|(destination, keys)| Command::SDiffStore(SDiffStore::new(destination, keys))),
+    ]
+}
+
+fn score() -> impl Strategy<Value = f64> {
+    any::<f64>().prop_filter("nan", |score| !score.is_nan())
+}
+
+fn score_bound() -> impl Strategy<Value = ScoreBound> {
+    prop_oneof![score().prop_map(ScoreBound::Inclusive), score().prop_map(ScoreBound::Exclusive)]
+}
+
+fn lex_bound() -> impl Strategy<Value = LexBound> {
+    prop_oneof![
+        Just(LexBound::Min),
+        Just(LexBound::Max),
+        any::<Vec<u8>>().prop_map(|member| LexBound::Inclusive(Bytes::from(member))),
+        any::<Vec<u8>>().prop_map(|member| LexBound::Exclusive(Bytes::from(member))),
+    ]
+}
+
+// 只生成合法的选项组合
+fn zadd_options() -> impl Strategy<Value = ZAddOptions> {
+    (0..3u8, 0..3u8, any::<bool>(), any::<bool>()).prop_map(|(exists, compare, ch, incr)| ZAddOptions {
+        nx: exists == 1,
+        xx: exists == 2,
+        gt: exists != 1 && compare == 1,
+        lt: exists != 1 && compare == 2,
+        ch,
+        incr,
+    })
+}
+
+fn zset_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    let pairs = proptest::collection::vec((score(), any::<Vec<u8>>().prop_map(Bytes::from)), 1..8);
+    let limit = || proptest::option::of((any::<i64>(), any::<i64>()));
+    prop_oneof![
+        (key(), zadd_options(), pairs).prop_map(|(key, options, mut pairs)| {
+            if options.incr {
+                pairs.truncate(1);
+            }
+            Command::ZAdd(ZAdd::new(key, options, pairs))
+        }),
+        (key(), score(), key()).prop_map(|(key, increment, member)| Command::ZIncrBy(ZIncrBy::new(key, increment, member))),
+        (key(), elements()).prop_map(|(key, members)| Command::ZRem(ZRem::new(key, members))),
+        (key(), key()).prop_map(|(key, member)| Command::ZScore(ZScore::new(key, member))),
+        key().prop_map(|key| Command::ZCard(ZCard::new(key))),
+        (key(), key()).prop_map(|(key, member)| Command::ZRank(ZRank::new(key, member))),
+        (
+            key(),
+            prop_oneof![
+                (any::<i64>(), any::<i64>()).prop_map(|(start, stop)| (RangeBy::Rank(start, stop), false)),
+                (score_bound(), score_bound()).prop_map(|(min, max)| (RangeBy::Score(min, max), true)),
+                (lex_bound(), lex_bound()).prop_map(|(min, max)| (RangeBy::Lex(min, max), true)),
+            ],
+            any::<bool>(),
+            limit(),
+            any::<bool>(),
+        ).prop_map(|(key, (by, can_limit), rev, limit, withscores)| {
+            let lex = matches!(by, RangeBy::Lex(..));
+            let mut zrange = ZRange::new(key, by);
+            if rev {
+                zrange = zrange.with_rev();
+            }
+            if let (true, Some((offset, count))) = (can_limit, limit) {
+                zrange = zrange.with_limit(offset, count);
+            }
+            if withscores && !lex {
+                zrange = zrange.with_scores();
+            }
+            Command::ZRange(zrange)
+        }),
+        (key(), score_bound(), score_bound(), any::<bool>(), limit()).prop_map(|(key, min, max, withscores, limit)| {
+            let mut zrange = ZRangeByScore::new(key, min, max);
+            if withscores {
+                zrange = zrange.with_scores();
+            }
+            if let Some((offset, count)) = limit {
+                zrange = zrange.with_limit(offset, count);
+            }
+            Command::ZRangeByScore(zrange)
+        }),
+    ]
+}
+
+fn stream_id() -> impl Strategy<Value = StreamId> {
+    (any::<u64>(), any::<u64>()).prop_map(|(ms, seq)| StreamId::new(ms, seq))
+}
+
+// LIMIT只能和`~`一起出现
+fn trim_options() -> impl Strategy<Value = TrimOptions> {
+    (
+        prop_oneof![(0..=i64::MAX as u64).prop_map(TrimStrategy::MaxLen), stream_id().prop_map(TrimStrategy::MinId)],
+        any::<bool>(),
+        proptest::option::of(count()),
+    ).prop_map(|(strategy, approx, limit)| TrimOptions { strategy, approx, limit: limit.filter(|_| approx) })
+}
+
+fn stream_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    let id = prop_oneof![
+        Just(XAddId::Auto),
+        any::<u64>().prop_map(XAddId::AutoSeq),
+        stream_id().prop_map(XAddId::Explicit),
+    ];
+    let fields = proptest::collection::vec(
+        (any::<Vec<u8>>().prop_map(Bytes::from), any::<Vec<u8>>().prop_map(Bytes::from)),
+        1..8,
+    );
+    prop_oneof![
+        (key(), id, fields, any::<bool>(), proptest::option::of(trim_options()))
+            .prop_map(|(key, id, fields, nomkstream, trim)| {
+                let mut xadd = XAdd::new(key, id, fields);
+                if nomkstream {
+                    xadd = xadd.with_nomkstream();
+                }
+                if let Some(trim) = trim {
+                    xadd = xadd.with_trim(trim);
+                }
+                Command::XAdd(xadd)
+            }),
+        (key(), stream_id(), stream_id(), proptest::option::of(count())).prop_map(|(key, start, end, count)| {
+            let mut xrange = XRange::new(key, start, end);
+            if let Some(count) = count {
+                xrange = xrange.with_count(count);
+            }
+            Command::XRange(xrange)
+        }),
+        (
+            proptest::collection::vec((key().prop_map(Bytes::from), proptest::option::of(stream_id())), 1..8),
+            proptest::option::of(count()),
+            proptest::option::of(timeout()),
+        ).prop_map(|(streams, count, block)| {
+            let (keys, ids) = streams.into_iter().unzip();
+            let mut xread = XRead::new(keys, ids);
+            if let Some(count) = count {
+                xread = xread.with_count(count);
+            }
+            if let Some(block) = block {
+                xread = xread.with_block(block);
+            }
+            Command::XRead(xread)
+        }),
+        key().prop_map(|key| Command::XLen(XLen::new(key))),
+        (key(), trim_options()).prop_map(|(key, options)| Command::XTrim(XTrim::new(key, options))),
+    ]
+}
+
+fn group_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>().prop_map(Bytes::from);
+    let ids = || proptest::collection::vec(stream_id(), 1..8);
+    let claim_options = (
+        proptest::option::of(count()),
+        proptest::option::of(count()),
+        proptest::option::of(count()),
+        any::<bool>(),
+        any::<bool>(),
+    ).prop_map(|(idle, time, retry_count, force, justid)| ClaimOptions { idle, time, retry_count, force, justid });
+    let pending_range = (
+        stream_id(),
+        stream_id(),
+        count(),
+        proptest::option::of(key()),
+        proptest::option::of(count()),
+    ).prop_map(|(start, end, count, consumer, idle)| PendingRange { start, end, count, consumer, idle });
+    prop_oneof![
+        prop_oneof![
+            (key(), key(), proptest::option::of(stream_id()), any::<bool>())
+                .prop_map(|(key, group, id, mkstream)| XGroup::Create { key, group, id, mkstream }),
+            (key(), key()).prop_map(|(key, group)| XGroup::Destroy { key, group }),
+            (key(), key(), key()).prop_map(|(key, group, consumer)| XGroup::CreateConsumer { key, group, consumer }),
+            (key(), key(), key()).prop_map(|(key, group, consumer)| XGroup::DelConsumer { key, group, consumer }),
+            (key(), key(), proptest::option::of(stream_id())).prop_map(|(key, group, id)| XGroup::SetId { key, group, id }),
+        ].prop_map(Command::XGroup),
+        (
+            key(),
+            key(),
+            proptest::collection::vec((key(), proptest::option::of(stream_id())), 1..8),
+            proptest::option::of(count()),
+            proptest::option::of(timeout()),
+            any::<bool>(),
+        ).prop_map(|(group, consumer, streams, count, block, noack)| {
+            let (keys, ids) = streams.into_iter().unzip();
+            let mut xreadgroup = XReadGroup::new(group, consumer, keys, ids);
+            if let Some(count) = count {
+                xreadgroup = xreadgroup.with_count(count);
+            }
+            if let Some(block) = block {
+                xreadgroup = xreadgroup.with_block(block);
+            }
+            if noack {
+                xreadgroup = xreadgroup.with_noack();
+            }
+            Command::XReadGroup(xreadgroup)
+        }),
+        (key(), key(), ids()).prop_map(|(key, group, ids)| Command::XAck(XAck::new(key, group, ids))),
+        (key(), key(), proptest::option::of(pending_range)).prop_map(|(key, group, range)| {
+            let mut xpending = XPending::new(key, group);
+            if let Some(range) = range {
+                xpending = xpending.with_range(range);
+            }
+            Command::XPending(xpending)
+        }),
+        (key(), key(), key(), count(), ids(), claim_options).prop_map(|(key, group, consumer, min_idle, ids, options)| {
+            Command::XClaim(XClaim::new(key, group, consumer, min_idle, ids, options))
+        }),
+        (key(), key(), key(), count(), stream_id(), proptest::option::of(1..=i64::MAX as u64), any::<bool>())
+            .prop_map(|(key, group, consumer, min_idle, start, count, justid)| {
+                let mut xautoclaim = XAutoClaim::new(key, group, consumer, min_idle, start);
+                if let Some(count) = count {
+                    xautoclaim = xautoclaim.with_count(count);
+                }
+                if justid {
+                    xautoclaim = xautoclaim.with_justid();
+                }
+                Command::XAutoClaim(xautoclaim)
+            }),
+        prop_oneof![
+            key().prop_map(XInfo::Stream),
+            key().prop_map(XInfo::Groups),
+            (key(), key()).prop_map(|(key, group)| XInfo::Consumers(key, group)),
+        ].prop_map(Command::XInfo),
+    ]
+}
+
+fn expire_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>();
+    // NX不能和其他选项一起用，GT和LT不能一起用
+    let options = || prop_oneof![
+        Just(ExpireOptions { nx: true, ..Default::default() }),
+        (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(|(xx, gt, lt)| ExpireOptions { xx, gt, lt: lt && !gt, nx: false }),
+    ];
+    prop_oneof![
+        (key(), any::<i64>(), options()).prop_map(|(key, seconds, options)| {
+            Command::Expire(Expire::new(key, seconds).with_options(options))
+        }),
+        (key(), any::<i64>(), options()).prop_map(|(key, ms, options)| {
+            Command::PExpire(PExpire::new(key, ms).with_options(options))
+        }),
+        (key(), any::<i64>(), options()).prop_map(|(key, timestamp, options)| {
+            Command::ExpireAt(ExpireAt::new(key, timestamp).with_options(options))
+        }),
+        (key(), any::<i64>(), options()).prop_map(|(key, timestamp, options)| {
+            Command::PExpireAt(PExpireAt::new(key, timestamp).with_options(options))
+        }),
+        key().prop_map(|key| Command::Ttl(Ttl::new(key))),
+        key().prop_map(|key| Command::PTtl(PTtl::new(key))),
+        key().prop_map(|key| Command::Persist(Persist::new(key))),
+        key().prop_map(|key| Command::ExpireTime(ExpireTime::new(key))),
+        key().prop_map(|key| Command::PExpireTime(PExpireTime::new(key))),
+    ]
+}
+
+fn keys_command() -> impl Strategy<Value = Command> {
+    let key = || any::<Vec<u8>>().prop_map(Bytes::from);
+    let keys = || proptest::collection::vec(key(), 1..8);
+    prop_oneof![
+        keys().prop_map(|keys| Command::Del(Del::new(keys))),
+        keys().prop_map(|keys| Command::Unlink(Unlink::new(keys))),
+        keys().prop_map(|keys| Command::Exists(Exists::new(keys))),
+        keys().prop_map(|keys| Command::Touch(Touch::new(keys))),
+        key().prop_map(|key| Command::Type(Type::new(key))),
+        (key(), key()).prop_map(|(key, destination)| Command::Rename(Rename::new(key, destination))),
+        (key(), key()).prop_map(|(key, destination)| Command::RenameNx(RenameNx::new(key, destination))),
+        (key(), key(), proptest::option::of(index()), any::<bool>()).prop_map(|(source, destination, db, replace)| {
+            let mut copy = Copy::new(source, destination);
+            if let Some(db) = db {
+                copy = copy.with_db(db);
+            }
+            Command::Copy(if replace { copy.with_replace() } else { copy })
+        }),
+        key().prop_map(|pattern| Command::Keys(Keys::new(pattern))),
+        (
+            any::<u64>(),
+            proptest::option::of(key()),
+            proptest::option::of(1..=i64::MAX as u64),
+            proptest::option::of("[a-z]{1,8}"),
+        ).prop_map(|(cursor, pattern, count, key_type)| {
+            let mut scan = Scan::new(cursor);
+            if let Some(pattern) = pattern {
+                scan = scan.with_pattern(pattern);
+            }
+            if let Some(count) = count {
+                scan = scan.with_count(count);
+            }
+            if let Some(key_type) = key_type {
+                scan = scan.with_type(key_type);
+            }
+            Command::Scan(scan)
+        }),
+    ]
+}
+
+// 库的下标，解析的时候走的是i64
+fn index() -> impl Strategy<Value = usize> {
+    0..=i64::MAX as usize
+}
+
+fn db_command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        index().prop_map(|index| Command::Select(Select::new(index))),
+        (index(), index()).prop_map(|(first, second)| Command::SwapDb(SwapDb::new(first, second))),
+        (any::<Vec<u8>>(), index()).prop_map(|(key, db)| Command::Move(Move::new(key, db))),
+        any::<bool>().prop_map(|lazy| Command::FlushDb(if lazy { FlushDb::new().with_async() } else { FlushDb::new() })),
+        any::<bool>().prop_map(|lazy| Command::FlushAll(if lazy { FlushAll::new().with_async() } else { FlushAll::new() })),
+    ]
+}
+
+fn multi_command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        Just(Command::Multi(Multi::new())),
+        Just(Command::Exec(Exec::new())),
+        Just(Command::Discard(Discard::new())),
+        elements().prop_map(|keys| Command::Watch(Watch::new(keys))),
+        Just(Command::Unwatch(Unwatch::new())),
+    ]
+}
+
+fn script_command() -> impl Strategy<Value = Command> {
+    let keys = || proptest::collection::vec(any::<Vec<u8>>().prop_map(Bytes::from), 0..4);
+    prop_oneof![
+        (any::<Vec<u8>>(), keys(), keys())
+            .prop_map(|(script, keys, args)| Command::Eval(Eval::new(script, keys, args))),
+        ("[0-9a-f]{40}", keys(), keys())
+            .prop_map(|(sha1, keys, args)| Command::EvalSha(EvalSha::new(sha1, keys, args))),
+        prop_oneof![
+            any::<Vec<u8>>().prop_map(|script| Script::Load(Bytes::from(script))),
+            proptest::collection::vec("[0-9a-f]{40}", 1..4).prop_map(Script::Exists),
+            Just(Script::Flush),
+            Just(Script::Kill),
+        ]
+        .prop_map(Command::Script),
+    ]
+}
+
+fn command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        any::<Vec<u8>>().prop_map(|key| Command::Get(Get::new(key))),
+        (any::<String>(), any::<Vec<u8>>())
+            .prop_map(|(channel, message)| Command::Publish(Publish::new(channel, Bytes::from(message)))),
+        proptest::collection::vec(any::<String>(), 1..8)
+            .prop_map(|channels| Command::Subscribe(Subscribe::new(&channels))),
+        proptest::collection::vec(any::<String>(), 0..8)
+            .prop_map(|channels| Command::Unsubscribe(Unsubscribe::new(&channels))),
+        proptest::collection::vec(any::<String>(), 1..8)
+            .prop_map(|patterns| Command::PSubscribe(PSubscribe::new(&patterns))),
+        proptest::collection::vec(any::<String>(), 0..8)
+            .prop_map(|patterns| Command::PUnsubscribe(PUnsubscribe::new(&patterns))),
+        prop_oneof![
+            proptest::option::of(any::<String>()).prop_map(PubSub::Channels),
+            proptest::collection::vec(any::<String>(), 0..8).prop_map(PubSub::NumSub),
+            Just(PubSub::NumPat),
+        ].prop_map(Command::PubSub),
+        (
+            proptest::option::of(count()),
+            proptest::option::of((any::<String>(), any::<Vec<u8>>())),
+            proptest::option::of(any::<String>()),
+        ).prop_map(|(protover, auth, setname)| {
+            let auth = auth.map(|(username, password)| (username, Bytes::from(password)));
+            Command::Hello(Hello::new(protover, auth, setname))
+        }),
+        string_command(),
+        list_command(),
+        hash_command(),
+        set_command(),
+        zset_command(),
+        stream_command(),
+        group_command(),
+        expire_command(),
+        keys_command(),
+        db_command(),
+        multi_command(),
+        script_command(),
+        // 命令名解析的时候会转成小写，并且不能和已有的命令重名
+        "[a-z]{1,16}"
+            .prop_filter("known command", |name| !COMMANDS.contains(&&name[..]))
+            .prop_map(|name| Command::Unknown(Unknown::new(name))),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn command_round_trip(cmd in command()) {
+        let parsed = Command::from_frame(cmd.clone().into_frame()).unwrap();
+        prop_assert_eq!(parsed, cmd);
+    }
+}
@@ -0,0 +1,55 @@
+// 集成测试共用的工具函数，每个测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use w::connection::Connection;
+use w::frame::Frame;
+use w::server;
+
+// 在随机端口上启动一个服务端，返回监听的地址
+pub async fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, std::future::pending::<()>()).await });
+
+    addr
+}
+
+pub async fn connect(addr: SocketAddr) -> Connection {
+    Connection::new(TcpStream::connect(addr).await.unwrap())
+}
+
+// 发送一条命令并等待回复
+pub async fn request(connection: &mut Connection, args: &[&[u8]]) -> Frame {
+    send(connection, args).await;
+    connection.read_frame().await.unwrap().unwrap()
+}
+
+// 只发送不等回复，用来测试阻塞的命令
+pub async fn send(connection: &mut Connection, args: &[&[u8]]) {
+    let frame = Frame::Array(args.iter().map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg))).collect());
+    connection.write_frame(&frame).await.unwrap();
+}
+
+pub fn bulk(item: impl AsRef<[u8]>) -> Frame {
+    Frame::Bulk(Bytes::copy_from_slice(item.as_ref()))
+}
+
+pub fn simple(item: &str) -> Frame {
+    Frame::Simple(item.to_string())
+}
+
+pub fn ok() -> Frame {
+    simple("OK")
+}
+
+pub fn error(msg: &str) -> Frame {
+    Frame::Error(msg.to_string())
+}
+
+pub fn is_error(frame: &Frame, prefix: &str) -> bool {
+    matches!(frame, Frame::Error(msg) if msg.starts_with(prefix))
+}
@@ -0,0 +1,188 @@
+use std::time::Duration;
+use tokio::net::TcpListener;
+use w::frame::Frame;
+use w::server;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, ok, error};
+
+#[tokio::test]
+async fn select_isolates_data() {
+    let addr = start_server().await;
+    let mut first = connect(addr).await;
+    let mut second = connect(addr).await;
+
+    request(&mut first, &[b"set", b"k", b"db0"]).await;
+    assert_eq!(ok(), request(&mut first, &[b"select", b"3"]).await);
+    assert_eq!(Frame::Null, request(&mut first, &[b"get", b"k"]).await);
+    request(&mut first, &[b"set", b"k", b"db3"]).await;
+
+    // 选中的库是每个连接自己的
+    assert_eq!(bulk("db0"), request(&mut second, &[b"get", b"k"]).await);
+    assert_eq!(ok(), request(&mut second, &[b"select", b"3"]).await);
+    assert_eq!(bulk("db3"), request(&mut second, &[b"get", b"k"]).await);
+
+    assert_eq!(ok(), request(&mut first, &[b"select", b"15"]).await);
+    assert_eq!(error("ERR DB index is out of range"), request(&mut first, &[b"select", b"16"]).await);
+    assert_eq!(error("ERR DB index is out of range"), request(&mut first, &[b"select", b"-1"]).await);
+    assert_eq!(
+        error("ERR value is not an integer or out of range"),
+        request(&mut first, &[b"select", b"abc"]).await
+    );
+    // 选库失败的时候还停留在原来的库
+    request(&mut first, &[b"set", b"k", b"db15"]).await;
+    assert_eq!(ok(), request(&mut first, &[b"select", b"0"]).await);
+    assert_eq!(bulk("db0"), request(&mut first, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn configurable_database_count() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { server::run_with_databases(listener, std::future::pending::<()>(), 2).await });
+
+    let mut connection = connect(addr).await;
+    assert_eq!(ok(), request(&mut connection, &[b"select", b"1"]).await);
+    assert_eq!(error("ERR DB index is out of range"), request(&mut connection, &[b"select", b"2"]).await);
+}
+
+#[tokio::test]
+async fn expiration_in_other_database() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"select", b"5"]).await;
+    request(&mut connection, &[b"set", b"k", b"v", b"PX", b"50"]).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"pttl", b"k"]).await);
+}
+
+#[tokio::test]
+async fn swap_databases() {
+    let addr = start_server().await;
+    let mut first = connect(addr).await;
+    let mut second = connect(addr).await;
+
+    request(&mut first, &[b"set", b"k", b"db0", b"PX", b"100"]).await;
+    request(&mut second, &[b"select", b"1"]).await;
+    request(&mut second, &[b"set", b"k", b"db1"]).await;
+    request(&mut second, &[b"set", b"only1", b"v"]).await;
+
+    assert_eq!(ok(), request(&mut first, &[b"swapdb", b"0", b"1"]).await);
+    assert_eq!(bulk("db1"), request(&mut first, &[b"get", b"k"]).await);
+    assert_eq!(bulk("v"), request(&mut first, &[b"get", b"only1"]).await);
+    assert_eq!(bulk("db0"), request(&mut second, &[b"get", b"k"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut second, &[b"exists", b"only1"]).await);
+
+    // 过期时间跟着数据一起换过去了
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(Frame::Null, request(&mut second, &[b"get", b"k"]).await);
+    assert_eq!(bulk("db1"), request(&mut first, &[b"get", b"k"]).await);
+
+    // 换过去的key和新写的key不会弄混过期时间
+    request(&mut second, &[b"set", b"a", b"v", b"PX", b"50"]).await;
+    request(&mut first, &[b"swapdb", b"1", b"0"]).await;
+    request(&mut second, &[b"set", b"a", b"kept"]).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(bulk("kept"), request(&mut second, &[b"get", b"a"]).await);
+    assert_eq!(Frame::Null, request(&mut first, &[b"get", b"a"]).await);
+
+    assert_eq!(ok(), request(&mut first, &[b"swapdb", b"2", b"2"]).await);
+    assert_eq!(error("ERR DB index is out of range"), request(&mut first, &[b"swapdb", b"0", b"16"]).await);
+    assert_eq!(error("ERR invalid first DB index"), request(&mut first, &[b"swapdb", b"a", b"1"]).await);
+    assert_eq!(error("ERR invalid second DB index"), request(&mut first, &[b"swapdb", b"0", b"b"]).await);
+}
+
+#[tokio::test]
+async fn swapdb_serves_blocked_clients() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let mut blocked = connect(addr).await;
+
+    blocked.write_frame(&Frame::Array(vec![bulk("blpop"), bulk("list"), bulk("0")])).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut connection, &[b"select", b"1"]).await;
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+    request(&mut connection, &[b"swapdb", b"0", b"1"]).await;
+    assert_eq!(Frame::Array(vec![bulk("list"), bulk("a")]), blocked.read_frame().await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn move_and_copy_between_databases() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"k", b"v", b"EX", b"100"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"move", b"k", b"2"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"k"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"move", b"k", b"2"]).await);
+
+    request(&mut connection, &[b"set", b"k", b"other"]).await;
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"move", b"k", b"2"]).await);
+    assert_eq!(
+        error("ERR source and destination objects are the same"),
+        request(&mut connection, &[b"move", b"k", b"0"]).await
+    );
+    assert_eq!(error("ERR DB index is out of range"), request(&mut connection, &[b"move", b"k", b"16"]).await);
+
+    // COPY到别的库，同名的key也可以
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"copy", b"k", b"k", b"DB", b"2"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"copy", b"k", b"k", b"db", b"2", b"REPLACE"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"copy", b"k", b"c", b"DB", b"3"]).await);
+    assert_eq!(
+        error("ERR DB index is out of range"),
+        request(&mut connection, &[b"copy", b"k", b"c", b"DB", b"99"]).await
+    );
+
+    request(&mut connection, &[b"select", b"2"]).await;
+    assert_eq!(bulk("other"), request(&mut connection, &[b"get", b"k"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"k"]).await);
+    request(&mut connection, &[b"select", b"3"]).await;
+    assert_eq!(bulk("other"), request(&mut connection, &[b"get", b"c"]).await);
+}
+
+#[tokio::test]
+async fn move_keeps_expiration() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"k", b"v", b"PX", b"50"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"move", b"k", b"1"]).await);
+    request(&mut connection, &[b"set", b"k", b"kept"]).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(bulk("kept"), request(&mut connection, &[b"get", b"k"]).await);
+    request(&mut connection, &[b"select", b"1"]).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn flush_databases() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    for db in [b"0", b"1", b"2"].iter() {
+        request(&mut connection, &[b"select", *db]).await;
+        request(&mut connection, &[b"set", b"a", b"v", b"EX", b"100"]).await;
+        request(&mut connection, &[b"rpush", b"b", b"x"]).await;
+    }
+
+    // 当前是2号库
+    assert_eq!(ok(), request(&mut connection, &[b"flushdb"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"a", b"b"]).await);
+    request(&mut connection, &[b"select", b"1"]).await;
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"exists", b"a", b"b"]).await);
+    assert_eq!(ok(), request(&mut connection, &[b"flushdb", b"async"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"a", b"b"]).await);
+
+    request(&mut connection, &[b"select", b"0"]).await;
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"exists", b"a", b"b"]).await);
+    assert_eq!(ok(), request(&mut connection, &[b"flushall", b"ASYNC"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"a", b"b"]).await);
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"ttl", b"a"]).await);
+
+    assert_eq!(ok(), request(&mut connection, &[b"flushall", b"SYNC"]).await);
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"flushall", b"LATER"]).await);
+}
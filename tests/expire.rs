@@ -0,0 +1,162 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, error};
+
+fn integer(frame: Frame) -> i64 {
+    match frame {
+        Frame::Integer(value) => value,
+        frame => panic!("expected integer but got {:?}", frame),
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
+}
+
+#[tokio::test]
+async fn ttl_and_persist() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"ttl", b"k"]).await);
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"pttl", b"k"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"100"]).await);
+
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"k"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"expiretime", b"k"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"persist", b"k"]).await);
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"100"]).await);
+    assert_eq!(Frame::Integer(100), request(&mut connection, &[b"ttl", b"k"]).await);
+    let pttl = integer(request(&mut connection, &[b"pttl", b"k"]).await);
+    assert!(pttl > 99_000 && pttl <= 100_000, "{}", pttl);
+
+    // 列表之类的其他类型也可以设置过期时间
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpire", b"list", b"5000"]).await);
+    assert_eq!(Frame::Integer(5), request(&mut connection, &[b"ttl", b"list"]).await);
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"persist", b"k"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"k"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"persist", b"k"]).await);
+}
+
+#[tokio::test]
+async fn absolute_expire_time() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    let at = now_millis() / 1000 + 1000;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expireat", b"k", at.to_string().as_bytes()]).await);
+    assert_eq!(Frame::Integer(at), request(&mut connection, &[b"expiretime", b"k"]).await);
+
+    let at = now_millis() + 5000;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpireat", b"k", at.to_string().as_bytes()]).await);
+    let pexpiretime = integer(request(&mut connection, &[b"pexpiretime", b"k"]).await);
+    assert!((pexpiretime - at).abs() <= 2, "{} {}", pexpiretime, at);
+
+    // 已经过去的时间直接删掉key
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpireat", b"k", b"1000"]).await);
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"pexpiretime", b"k"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"-1"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+
+    assert_eq!(
+        error("ERR invalid expire time in 'expire' command"),
+        request(&mut connection, &[b"expire", b"k", b"9223372036854775807"]).await
+    );
+    assert_eq!(
+        error("ERR invalid expire time in 'pexpire' command"),
+        request(&mut connection, &[b"pexpire", b"k", b"9223372036854775807"]).await
+    );
+    assert_eq!(
+        error("ERR value is not an integer or out of range"),
+        request(&mut connection, &[b"expire", b"k", b"1.5"]).await
+    );
+}
+
+#[tokio::test]
+async fn expire_options() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"100", b"XX"]).await);
+    // 没有过期时间当成无限长，GT不会生效，LT会
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"100", b"GT"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"100", b"lt"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"50", b"nx"]).await);
+    assert_eq!(Frame::Integer(100), request(&mut connection, &[b"ttl", b"k"]).await);
+
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"200", b"LT"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"200", b"GT", b"XX"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"expire", b"k", b"150", b"GT"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"150", b"XX"]).await);
+    assert_eq!(Frame::Integer(150), request(&mut connection, &[b"ttl", b"k"]).await);
+
+    request(&mut connection, &[b"persist", b"k"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"k", b"50", b"NX"]).await);
+    assert_eq!(Frame::Integer(50), request(&mut connection, &[b"ttl", b"k"]).await);
+
+    assert_eq!(
+        error("ERR NX and XX, GT or LT options at the same time are not compatible"),
+        request(&mut connection, &[b"expire", b"k", b"10", b"NX", b"GT"]).await
+    );
+    assert_eq!(
+        error("ERR GT and LT options at the same time are not compatible"),
+        request(&mut connection, &[b"pexpire", b"k", b"10", b"GT", b"LT"]).await
+    );
+    assert_eq!(error("ERR Unsupported option FOO"), request(&mut connection, &[b"expire", b"k", b"10", b"FOO"]).await);
+    assert_eq!(Frame::Integer(50), request(&mut connection, &[b"ttl", b"k"]).await);
+}
+
+#[tokio::test]
+async fn earlier_deadline_wakes_purge_task() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    // 后台任务先按10秒之后的过期时间睡下去，再把另一个key的过期时间提前
+    request(&mut connection, &[b"set", b"late", b"v", b"EX", b"10"]).await;
+    request(&mut connection, &[b"set", b"early", b"v"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpire", b"early", b"50"]).await);
+
+    // 把最早的过期时间往后挪也要能正常删除
+    request(&mut connection, &[b"set", b"moved", b"v", b"PX", b"20"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpire", b"moved", b"100"]).await);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(Frame::Integer(-2), request(&mut connection, &[b"ttl", b"early"]).await);
+    assert_eq!(Frame::Bulk("v".into()), request(&mut connection, &[b"get", b"moved"]).await);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"moved"]).await);
+    assert_eq!(Frame::Bulk("v".into()), request(&mut connection, &[b"get", b"late"]).await);
+}
+
+#[tokio::test]
+async fn far_future_expire() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    // 过期时间超出了定时器能等的范围，后台任务不能因此挂掉
+    request(&mut connection, &[b"set", b"far", b"v"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"expire", b"far", b"100000000"]).await);
+    request(&mut connection, &[b"set", b"near", b"v", b"PX", b"50"]).await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"near"]).await);
+    assert_eq!(Frame::Integer(100000000), request(&mut connection, &[b"ttl", b"far"]).await);
+
+    // 换算回unix时间的时候不能溢出
+    request(&mut connection, &[b"set", b"max", b"v"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"pexpireat", b"max", b"9223372036854775807"]).await);
+    assert!(integer(request(&mut connection, &[b"pexpiretime", b"max"]).await) > i64::MAX - 1000);
+    assert_eq!(Frame::Integer(i64::MAX / 1000), request(&mut connection, &[b"expiretime", b"max"]).await);
+}
@@ -0,0 +1,124 @@
+use bytes::Bytes;
+use std::io::Cursor;
+use w::frame::{Error, Frame};
+
+fn parse(src: &[u8]) -> Frame {
+    let mut buf = Cursor::new(src);
+    Frame::check(&mut buf).unwrap();
+    assert_eq!(src.len() as u64, buf.position());
+
+    buf.set_position(0);
+    Frame::parse(&mut buf).unwrap()
+}
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+#[test]
+fn parse_resp3_simple_types() {
+    assert_eq!(Frame::Null, parse(b"_\r\n"));
+    assert_eq!(Frame::Boolean(true), parse(b"#t\r\n"));
+    assert_eq!(Frame::Boolean(false), parse(b"#f\r\n"));
+    assert_eq!(Frame::Double(1.5), parse(b",1.5\r\n"));
+    assert_eq!(Frame::Double(f64::INFINITY), parse(b",inf\r\n"));
+    assert_eq!(Frame::Double(f64::NEG_INFINITY), parse(b",-inf\r\n"));
+    assert!(matches!(parse(b",nan\r\n"), Frame::Double(d) if d.is_nan()));
+    assert_eq!(
+        Frame::BigNumber("3492890328409238509324850943850943825024385".into()),
+        parse(b"(3492890328409238509324850943850943825024385\r\n")
+    );
+    assert_eq!(
+        Frame::Verbatim("txt".into(), Bytes::from_static(b"Some string")),
+        parse(b"=15\r\ntxt:Some string\r\n")
+    );
+    assert_eq!(Frame::Error("SYNTAX invalid syntax".into()), parse(b"!21\r\nSYNTAX invalid syntax\r\n"));
+}
+
+#[test]
+fn parse_resp3_aggregate_types() {
+    assert_eq!(
+        Frame::Map(vec![(bulk("first"), Frame::Integer(1)), (bulk("second"), Frame::Integer(2))]),
+        parse(b"%2\r\n$5\r\nfirst\r\n:1\r\n$6\r\nsecond\r\n:2\r\n")
+    );
+    assert_eq!(
+        Frame::Set(vec![Frame::Simple("orange".into()), Frame::Boolean(true)]),
+        parse(b"~2\r\n+orange\r\n#t\r\n")
+    );
+    assert_eq!(
+        Frame::Push(vec![bulk("message"), bulk("ch"), bulk("hi")]),
+        parse(b">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n")
+    );
+    assert_eq!(
+        Frame::Attribute(
+            vec![(bulk("ttl"), Frame::Integer(3600))],
+            Box::new(Frame::Array(vec![Frame::Integer(2039123), Frame::Integer(9543892)])),
+        ),
+        parse(b"|1\r\n$3\r\nttl\r\n:3600\r\n*2\r\n:2039123\r\n:9543892\r\n")
+    );
+    assert_eq!(Frame::Null, parse(b"*-1\r\n"));
+}
+
+#[test]
+fn check_incomplete_frames() {
+    let incomplete: &[&[u8]] = &[
+        b"%2\r\n$5\r\nfirst\r\n:1\r\n",
+        b"|1\r\n$3\r\nttl\r\n:3600\r\n",
+        b"=15\r\ntxt:Some",
+        b"$5\r\nhel",
+        b",1.5",
+    ];
+
+    for src in incomplete {
+        let mut buf = Cursor::new(*src);
+        assert!(matches!(Frame::check(&mut buf), Err(Error::Incomplete)), "{:?}", src);
+    }
+}
+
+#[test]
+fn parse_invalid_frames() {
+    let invalid: &[&[u8]] = &[b"#x\r\n", b",abc\r\n", b"(12a\r\n", b"=3\r\ntxt\r\n", b"_x\r\n"];
+
+    for src in invalid {
+        let mut buf = Cursor::new(*src);
+        assert!(matches!(Frame::parse(&mut buf), Err(Error::Other(_))), "{:?}", src);
+    }
+}
+
+#[test]
+fn parse_integer_boundaries() {
+    assert_eq!(Frame::Integer(-1), parse(b":-1\r\n"));
+    assert_eq!(Frame::Integer(5), parse(b":+5\r\n"));
+    assert_eq!(Frame::Integer(0), parse(b":-0\r\n"));
+    assert_eq!(Frame::Integer(i64::MAX), parse(b":9223372036854775807\r\n"));
+    assert_eq!(Frame::Integer(i64::MIN), parse(b":-9223372036854775808\r\n"));
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(-3), Frame::Null]),
+        parse(b"*2\r\n:-3\r\n$-1\r\n")
+    );
+}
+
+#[test]
+fn check_invalid_integers_and_lengths() {
+    let invalid: &[&[u8]] = &[
+        b":9223372036854775808\r\n",
+        b":-9223372036854775809\r\n",
+        b":99999999999999999999999\r\n",
+        b":\r\n",
+        b":-\r\n",
+        b":+\r\n",
+        b":1a\r\n",
+        b":--1\r\n",
+        b"*-2\r\n",
+        b"$-5\r\n",
+        b"%-1\r\n",
+        b"~18446744073709551616\r\n",
+    ];
+
+    for src in invalid {
+        let mut buf = Cursor::new(*src);
+        assert!(matches!(Frame::check(&mut buf), Err(Error::Other(_))), "{:?}", src);
+        let mut buf = Cursor::new(*src);
+        assert!(matches!(Frame::parse(&mut buf), Err(Error::Other(_))), "{:?}", src);
+    }
+}
@@ -0,0 +1,147 @@
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, bulk};
+
+// 哈希表是无序的，排序之后再比较
+fn sorted(frame: Frame) -> Vec<Frame> {
+    let mut items = match frame {
+        Frame::Array(items) => items,
+        frame => panic!("expected array, got {:?}", frame),
+    };
+    items.sort_by_key(|item| format!("{:?}", item));
+    items
+}
+
+#[tokio::test]
+async fn set_get_and_delete_fields() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"hset", b"user", b"name", b"ann", b"age", b"30"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"hset", b"user", b"age", b"31", b"city", b"oslo"]).await);
+
+    assert_eq!(bulk(b"31"), request(&mut connection, &[b"hget", b"user", b"age"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"hget", b"user", b"missing"]).await);
+    assert_eq!(
+        Frame::Array(vec![bulk(b"ann"), Frame::Null, bulk(b"oslo")]),
+        request(&mut connection, &[b"hmget", b"user", b"name", b"missing", b"city"]).await,
+    );
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"hexists", b"user", b"name"]).await);
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"hlen", b"user"]).await);
+    assert_eq!(
+        vec![bulk(b"age"), bulk(b"city"), bulk(b"name")],
+        sorted(request(&mut connection, &[b"hkeys", b"user"]).await),
+    );
+    assert_eq!(
+        vec![bulk(b"31"), bulk(b"ann"), bulk(b"oslo")],
+        sorted(request(&mut connection, &[b"hvals", b"user"]).await),
+    );
+
+    // RESP2下HGETALL是字段和值交替的数组
+    match request(&mut connection, &[b"hgetall", b"user"]).await {
+        Frame::Array(items) => assert_eq!(6, items.len()),
+        frame => panic!("expected array, got {:?}", frame),
+    }
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"hdel", b"user", b"name", b"city", b"missing"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"hdel", b"user", b"age"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"hlen", b"user"]).await);
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'hset' command".to_string()),
+        request(&mut connection, &[b"hset", b"user", b"name"]).await,
+    );
+}
+
+#[tokio::test]
+async fn increment_fields() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(5), request(&mut connection, &[b"hincrby", b"counters", b"a", b"5"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"hincrby", b"counters", b"a", b"-3"]).await);
+    assert_eq!(bulk(b"2.5"), request(&mut connection, &[b"hincrbyfloat", b"counters", b"a", b"0.5"]).await);
+    assert_eq!(bulk(b"3"), request(&mut connection, &[b"hincrbyfloat", b"counters", b"a", b"0.5"]).await);
+
+    request(&mut connection, &[b"hset", b"counters", b"name", b"ann"]).await;
+    assert_eq!(
+        Frame::Error("ERR hash value is not an integer".to_string()),
+        request(&mut connection, &[b"hincrby", b"counters", b"name", b"1"]).await,
+    );
+    assert_eq!(
+        Frame::Error("ERR hash value is not a float".to_string()),
+        request(&mut connection, &[b"hincrbyfloat", b"counters", b"name", b"1"]).await,
+    );
+
+    request(&mut connection, &[b"hset", b"counters", b"max", b"9223372036854775807"]).await;
+    assert_eq!(
+        Frame::Error("ERR increment or decrement would overflow".to_string()),
+        request(&mut connection, &[b"hincrby", b"counters", b"max", b"1"]).await,
+    );
+
+    // 出错的时候不能留下空的哈希
+    assert_eq!(
+        Frame::Error("ERR increment would produce NaN or Infinity".to_string()),
+        request(&mut connection, &[b"hincrbyfloat", b"fresh", b"a", b"inf"]).await,
+    );
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"fresh"]).await);
+}
+
+#[tokio::test]
+async fn scan_fields() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    for i in 0..100 {
+        let field = format!("field:{}", i);
+        request(&mut connection, &[b"hset", b"big", field.as_bytes(), b"v"]).await;
+    }
+
+    // 一边遍历一边加新字段，一开始就有的字段都要遍历到
+    let mut cursor = b"0".to_vec();
+    let mut seen = std::collections::HashSet::new();
+    let mut added = 0;
+    loop {
+        let response = request(&mut connection, &[b"hscan", b"big", &cursor, b"COUNT", b"7", b"NOVALUES"]).await;
+        let (next, items) = match response {
+            Frame::Array(mut parts) => match (parts.remove(0), parts.remove(0)) {
+                (Frame::Bulk(next), Frame::Array(items)) => (next, items),
+                parts => panic!("unexpected hscan reply {:?}", parts),
+            },
+            frame => panic!("unexpected hscan reply {:?}", frame),
+        };
+        for item in items {
+            match item {
+                Frame::Bulk(field) => seen.insert(field),
+                frame => panic!("unexpected field {:?}", frame),
+            };
+        }
+
+        let field = format!("new:{}", added);
+        request(&mut connection, &[b"hset", b"big", field.as_bytes(), b"v"]).await;
+        added += 1;
+
+        if &next[..] == b"0" {
+            break;
+        }
+        cursor = next.to_vec();
+    }
+
+    for i in 0..100 {
+        assert!(seen.contains(format!("field:{}", i).as_bytes()));
+    }
+
+    // field:10 ~ field:19 各有一个字段和一个值
+    let response = request(&mut connection, &[b"hscan", b"big", b"0", b"MATCH", b"field:1?", b"COUNT", b"1000"]).await;
+    match response {
+        Frame::Array(parts) => match &parts[..] {
+            [Frame::Bulk(next), Frame::Array(items)] => {
+                assert_eq!(&next[..], b"0");
+                assert_eq!(20, items.len());
+            }
+            parts => panic!("unexpected hscan reply {:?}", parts),
+        },
+        frame => panic!("unexpected hscan reply {:?}", frame),
+    }
+}
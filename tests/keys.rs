@@ -0,0 +1,255 @@
+use std::time::Duration;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, simple, error};
+
+#[tokio::test]
+async fn del_exists_and_type() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"s", b"v"]).await;
+    request(&mut connection, &[b"rpush", b"l", b"a"]).await;
+    request(&mut connection, &[b"hset", b"h", b"f", b"v"]).await;
+    request(&mut connection, &[b"sadd", b"set", b"m"]).await;
+    request(&mut connection, &[b"zadd", b"z", b"1", b"m"]).await;
+    request(&mut connection, &[b"xadd", b"x", b"*", b"f", b"v"]).await;
+
+    let types: &[(&[u8], &str)] = &[
+        (b"s", "string"), (b"l", "list"), (b"h", "hash"), (b"set", "set"), (b"z", "zset"), (b"x", "stream"),
+        (b"missing", "none"),
+    ];
+    for (key, expected) in types {
+        assert_eq!(simple(expected), request(&mut connection, &[b"type", key]).await);
+    }
+
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"exists", b"s", b"s", b"l", b"missing"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"touch", b"h", b"z", b"missing"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"del", b"s", b"l", b"missing", b"s"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"s", b"l"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"s"]).await);
+    assert_eq!(Frame::Integer(4), request(&mut connection, &[b"unlink", b"h", b"set", b"z", b"x"]).await);
+    assert_eq!(simple("none"), request(&mut connection, &[b"type", b"x"]).await);
+}
+
+#[tokio::test]
+async fn del_clears_expiration() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    // 删掉的key的过期时间要一起清掉，不然会把后来新建的同名key也删了
+    request(&mut connection, &[b"set", b"a", b"v", b"PX", b"50"]).await;
+    request(&mut connection, &[b"set", b"b", b"v", b"PX", b"50"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"del", b"a"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"unlink", b"b"]).await);
+    request(&mut connection, &[b"set", b"a", b"new"]).await;
+    request(&mut connection, &[b"set", b"b", b"new"]).await;
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(bulk("new"), request(&mut connection, &[b"get", b"a"]).await);
+    assert_eq!(bulk("new"), request(&mut connection, &[b"get", b"b"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"a"]).await);
+}
+
+#[tokio::test]
+async fn unlink_large_value() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let members: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+    let mut args: Vec<&[u8]> = vec![b"sadd", b"big"];
+    args.extend(members.iter().map(|member| member.as_bytes()));
+    assert_eq!(Frame::Integer(1000), request(&mut connection, &args).await);
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"unlink", b"big"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"big"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"scard", b"big"]).await);
+}
+
+#[tokio::test]
+async fn rename_keys() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(error("ERR no such key"), request(&mut connection, &[b"rename", b"a", b"b"]).await);
+    assert_eq!(error("ERR no such key"), request(&mut connection, &[b"renamenx", b"a", b"b"]).await);
+
+    // 过期时间跟着key一起走，目标key原来的值被覆盖
+    request(&mut connection, &[b"set", b"a", b"1", b"EX", b"100"]).await;
+    request(&mut connection, &[b"rpush", b"b", b"x"]).await;
+    assert_eq!(simple("OK"), request(&mut connection, &[b"rename", b"a", b"b"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"exists", b"a"]).await);
+    assert_eq!(bulk("1"), request(&mut connection, &[b"get", b"b"]).await);
+    assert_eq!(Frame::Integer(100), request(&mut connection, &[b"ttl", b"b"]).await);
+    assert_eq!(simple("OK"), request(&mut connection, &[b"rename", b"b", b"b"]).await);
+
+    request(&mut connection, &[b"set", b"c", b"2"]).await;
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"renamenx", b"c", b"b"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"renamenx", b"c", b"c"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"renamenx", b"c", b"d"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"get", b"d"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"d"]).await);
+
+    // 改名之后原来key的过期时间不能再作用到新建的同名key上
+    request(&mut connection, &[b"set", b"e", b"v", b"PX", b"50"]).await;
+    request(&mut connection, &[b"rename", b"e", b"f"]).await;
+    request(&mut connection, &[b"set", b"e", b"kept"]).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(bulk("kept"), request(&mut connection, &[b"get", b"e"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"f"]).await);
+}
+
+#[tokio::test]
+async fn rename_serves_blocked_clients() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let mut blocked = connect(addr).await;
+
+    let frame = Frame::Array(vec![bulk("blpop"), bulk("target"), bulk("0")]);
+    blocked.write_frame(&frame).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut connection, &[b"rpush", b"source", b"a", b"b"]).await;
+    assert_eq!(simple("OK"), request(&mut connection, &[b"rename", b"source", b"target"]).await);
+    assert_eq!(
+        Frame::Array(vec![bulk("target"), bulk("a")]),
+        blocked.read_frame().await.unwrap().unwrap()
+    );
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"llen", b"target"]).await);
+}
+
+#[tokio::test]
+async fn copy_keys() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"copy", b"a", b"b"]).await);
+
+    request(&mut connection, &[b"hset", b"a", b"f", b"1"]).await;
+    request(&mut connection, &[b"expire", b"a", b"100"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"copy", b"a", b"b"]).await);
+    assert_eq!(Frame::Integer(100), request(&mut connection, &[b"ttl", b"b"]).await);
+
+    // 复制出来的值和原来的互不影响
+    request(&mut connection, &[b"hset", b"b", b"f", b"2"]).await;
+    assert_eq!(bulk("1"), request(&mut connection, &[b"hget", b"a", b"f"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"hget", b"b", b"f"]).await);
+
+    request(&mut connection, &[b"set", b"c", b"v"]).await;
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"copy", b"c", b"b"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"copy", b"c", b"b", b"replace"]).await);
+    assert_eq!(bulk("v"), request(&mut connection, &[b"get", b"b"]).await);
+    assert_eq!(Frame::Integer(-1), request(&mut connection, &[b"ttl", b"b"]).await);
+
+    assert_eq!(
+        error("ERR source and destination objects are the same"),
+        request(&mut connection, &[b"copy", b"c", b"c"]).await
+    );
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"copy", b"c", b"d", b"FOO"]).await);
+}
+
+#[tokio::test]
+async fn keys_pattern() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    for key in ["user:1", "user:2", "user:10", "order:1"].iter() {
+        request(&mut connection, &[b"set", key.as_bytes(), b"v"]).await;
+    }
+
+    let mut keys = match request(&mut connection, &[b"keys", b"user:?"]).await {
+        Frame::Array(keys) => keys,
+        frame => panic!("unexpected keys reply {:?}", frame),
+    };
+    keys.sort_by_key(|key| format!("{:?}", key));
+    assert_eq!(vec![bulk("user:1"), bulk("user:2")], keys);
+
+    assert_eq!(Frame::Array(vec![]), request(&mut connection, &[b"keys", b"none*"]).await);
+    match request(&mut connection, &[b"keys", b"*"]).await {
+        Frame::Array(keys) => assert_eq!(4, keys.len()),
+        frame => panic!("unexpected keys reply {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn scan_keyspace() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    for i in 0..100 {
+        let key = format!("key:{}", i);
+        request(&mut connection, &[b"set", key.as_bytes(), b"v"]).await;
+    }
+    request(&mut connection, &[b"rpush", b"key:list", b"a"]).await;
+
+    // 一边遍历一边加新key，一开始就有的key都要遍历到
+    let mut cursor = b"0".to_vec();
+    let mut seen = std::collections::HashSet::new();
+    let mut added = 0;
+    loop {
+        let response = request(&mut connection, &[b"scan", &cursor, b"COUNT", b"7", b"MATCH", b"key:*"]).await;
+        let (next, keys) = match response {
+            Frame::Array(mut parts) => match (parts.remove(0), parts.remove(0)) {
+                (Frame::Bulk(next), Frame::Array(keys)) => (next, keys),
+                parts => panic!("unexpected scan reply {:?}", parts),
+            },
+            frame => panic!("unexpected scan reply {:?}", frame),
+        };
+        for key in keys {
+            match key {
+                Frame::Bulk(key) => seen.insert(key),
+                frame => panic!("unexpected key {:?}", frame),
+            };
+        }
+
+        let key = format!("new:{}", added);
+        request(&mut connection, &[b"set", key.as_bytes(), b"v"]).await;
+        added += 1;
+
+        if &next[..] == b"0" {
+            break;
+        }
+        cursor = next.to_vec();
+    }
+
+    for i in 0..100 {
+        assert!(seen.contains(format!("key:{}", i).as_bytes()), "key:{} missing", i);
+    }
+    assert!(seen.contains(&b"key:list"[..]));
+    assert!(seen.iter().all(|key| key.starts_with(b"key:")));
+
+    // 删掉的key不能再遍历到
+    for i in 1..added {
+        let key = format!("new:{}", i);
+        request(&mut connection, &[b"del", key.as_bytes()]).await;
+    }
+    assert_eq!(
+        Frame::Array(vec![bulk("0"), Frame::Array(vec![bulk("new:0")])]),
+        request(&mut connection, &[b"scan", b"0", b"COUNT", b"1000", b"MATCH", b"new:*"]).await
+    );
+
+    // TYPE过滤
+    let mut cursor = b"0".to_vec();
+    let mut lists = vec![];
+    loop {
+        match request(&mut connection, &[b"scan", &cursor, b"TYPE", b"list"]).await {
+            Frame::Array(mut parts) => {
+                if let Frame::Array(keys) = parts.remove(1) {
+                    lists.extend(keys);
+                }
+                match parts.remove(0) {
+                    Frame::Bulk(next) if &next[..] == b"0" => break,
+                    Frame::Bulk(next) => cursor = next.to_vec(),
+                    frame => panic!("unexpected cursor {:?}", frame),
+                }
+            }
+            frame => panic!("unexpected scan reply {:?}", frame),
+        }
+    }
+    assert_eq!(vec![bulk("key:list")], lists);
+
+    assert_eq!(error("ERR invalid cursor"), request(&mut connection, &[b"scan", b"abc"]).await);
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"scan", b"0", b"COUNT", b"0"]).await);
+}
@@ -0,0 +1,208 @@
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use w::frame::Frame;
+use w::server;
+
+mod common;
+
+use common::{start_server, connect, request};
+
+fn bulks(items: &[&'static [u8]]) -> Frame {
+    Frame::Array(items.iter().map(|item| Frame::Bulk(Bytes::from_static(item))).collect())
+}
+
+#[tokio::test]
+async fn push_and_range() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"rpush", b"list", b"b", b"c"]).await);
+    assert_eq!(Frame::Integer(4), request(&mut connection, &[b"LPUSH", b"list", b"x", b"a"]).await);
+    assert_eq!(Frame::Integer(4), request(&mut connection, &[b"llen", b"list"]).await);
+
+    assert_eq!(bulks(&[b"a", b"x", b"b", b"c"]), request(&mut connection, &[b"lrange", b"list", b"0", b"-1"]).await);
+    assert_eq!(bulks(&[b"x", b"b"]), request(&mut connection, &[b"lrange", b"list", b"1", b"2"]).await);
+    assert_eq!(bulks(&[b"b", b"c"]), request(&mut connection, &[b"lrange", b"list", b"-2", b"100"]).await);
+    assert_eq!(bulks(&[b"a"]), request(&mut connection, &[b"lrange", b"list", b"-100", b"0"]).await);
+    assert_eq!(bulks(&[]), request(&mut connection, &[b"lrange", b"list", b"3", b"1"]).await);
+    assert_eq!(bulks(&[]), request(&mut connection, &[b"lrange", b"list", b"5", b"10"]).await);
+    assert_eq!(bulks(&[]), request(&mut connection, &[b"lrange", b"missing", b"0", b"-1"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"llen", b"missing"]).await);
+}
+
+#[tokio::test]
+async fn pop() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"rpush", b"list", b"a", b"b", b"c", b"d"]).await;
+
+    assert_eq!(Frame::Bulk(Bytes::from_static(b"a")), request(&mut connection, &[b"lpop", b"list"]).await);
+    assert_eq!(Frame::Bulk(Bytes::from_static(b"d")), request(&mut connection, &[b"rpop", b"list"]).await);
+    assert_eq!(bulks(&[b"c", b"b"]), request(&mut connection, &[b"rpop", b"list", b"5"]).await);
+
+    // 弹空之后key就没了
+    assert_eq!(Frame::Null, request(&mut connection, &[b"lpop", b"list"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"lpop", b"list", b"2"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"list"]).await);
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let wrongtype = Frame::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+
+    request(&mut connection, &[b"set", b"string", b"value"]).await;
+    request(&mut connection, &[b"rpush", b"list", b"value"]).await;
+
+    assert_eq!(wrongtype, request(&mut connection, &[b"lpush", b"string", b"a"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"rpop", b"string"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"lrange", b"string", b"0", b"-1"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"llen", b"string"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"get", b"list"]).await);
+
+    // SET不管之前是什么类型都会覆盖
+    assert_eq!(Frame::Simple("OK".to_string()), request(&mut connection, &[b"set", b"list", b"value"]).await);
+    assert_eq!(Frame::Bulk(Bytes::from_static(b"value")), request(&mut connection, &[b"get", b"list"]).await);
+}
+
+#[tokio::test]
+async fn blocking_pop_returns_immediately() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"rpush", b"second", b"a", b"b"]).await;
+
+    assert_eq!(bulks(&[b"second", b"b"]), request(&mut connection, &[b"brpop", b"first", b"second", b"0"]).await);
+    assert_eq!(
+        Frame::Bulk(Bytes::from_static(b"a")),
+        request(&mut connection, &[b"blmove", b"second", b"dest", b"left", b"RIGHT", b"0"]).await,
+    );
+    assert_eq!(bulks(&[b"a"]), request(&mut connection, &[b"lrange", b"dest", b"0", b"-1"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"blpop", b"second", b"0.05"]).await);
+}
+
+#[tokio::test]
+async fn blocking_pop_invalid_timeout() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(
+        Frame::Error("ERR timeout is negative".to_string()),
+        request(&mut connection, &[b"blpop", b"list", b"-1"]).await,
+    );
+    assert_eq!(
+        Frame::Error("ERR timeout is not a float or out of range".to_string()),
+        request(&mut connection, &[b"brpop", b"list", b"abc"]).await,
+    );
+    assert_eq!(
+        Frame::Error("ERR syntax error".to_string()),
+        request(&mut connection, &[b"blmove", b"a", b"b", b"up", b"left", b"0"]).await,
+    );
+}
+
+#[tokio::test]
+async fn blocking_pop_wakes_in_fifo_order() {
+    let addr = start_server().await;
+    let mut first = connect(addr).await;
+    let mut second = connect(addr).await;
+    let mut pusher = connect(addr).await;
+
+    let blpop = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"blpop")),
+        Frame::Bulk(Bytes::from_static(b"list")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+    ]);
+    first.write_frame(&blpop).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    second.write_frame(&blpop).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 两个元素按排队顺序分给两个客户端，所以push的回复还是插入之后的长度
+    assert_eq!(Frame::Integer(2), request(&mut pusher, &[b"rpush", b"list", b"a", b"b"]).await);
+    assert_eq!(bulks(&[b"list", b"a"]), first.read_frame().await.unwrap().unwrap());
+    assert_eq!(bulks(&[b"list", b"b"]), second.read_frame().await.unwrap().unwrap());
+    assert_eq!(Frame::Integer(0), request(&mut pusher, &[b"llen", b"list"]).await);
+}
+
+#[tokio::test]
+async fn blocking_move_wakes_next_waiter() {
+    let addr = start_server().await;
+    let mut mover = connect(addr).await;
+    let mut popper = connect(addr).await;
+    let mut pusher = connect(addr).await;
+
+    let blmove = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"blmove")),
+        Frame::Bulk(Bytes::from_static(b"source")),
+        Frame::Bulk(Bytes::from_static(b"dest")),
+        Frame::Bulk(Bytes::from_static(b"LEFT")),
+        Frame::Bulk(Bytes::from_static(b"LEFT")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+    ]);
+    mover.write_frame(&blmove).await.unwrap();
+    let brpop = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"brpop")),
+        Frame::Bulk(Bytes::from_static(b"dest")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+    ]);
+    popper.write_frame(&brpop).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut pusher, &[b"lpush", b"source", b"job"]).await;
+    assert_eq!(Frame::Bulk(Bytes::from_static(b"job")), mover.read_frame().await.unwrap().unwrap());
+    assert_eq!(bulks(&[b"dest", b"job"]), popper.read_frame().await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn blocking_pop_timeout_does_not_lose_elements() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Null, request(&mut connection, &[b"blpop", b"list", b"0.01"]).await);
+    // 超时的客户端不能再拿走后面push进来的元素
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"llen", b"list"]).await);
+}
+
+#[tokio::test]
+async fn blocking_pop_huge_timeout() {
+    let addr = start_server().await;
+    let mut popper = connect(addr).await;
+    let mut pusher = connect(addr).await;
+
+    // 超过tokio定时器上限的超时时间也要能正常阻塞，不能把连接弄崩了
+    let blpop = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"blpop")),
+        Frame::Bulk(Bytes::from_static(b"list")),
+        Frame::Bulk(Bytes::from_static(b"100000000")),
+    ]);
+    popper.write_frame(&blpop).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut pusher, &[b"rpush", b"list", b"a"]).await;
+    assert_eq!(bulks(&[b"list", b"a"]), popper.read_frame().await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn shutdown_releases_blocked_clients() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(async move { server::run(listener, rx).await });
+
+    let mut connection = connect(addr).await;
+    let blpop = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"blpop")),
+        Frame::Bulk(Bytes::from_static(b"list")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+    ]);
+    connection.write_frame(&blpop).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    tx.send(()).unwrap();
+    assert!(connection.read_frame().await.unwrap().is_none());
+    server.await.unwrap().unwrap();
+}
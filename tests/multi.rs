@@ -0,0 +1,345 @@
+use std::time::Duration;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, send, bulk, simple, error};
+
+#[tokio::test]
+async fn queue_and_exec() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(simple("OK"), request(&mut connection, &[b"multi"]).await);
+    assert_eq!(simple("QUEUED"), request(&mut connection, &[b"set", b"k", b"1"]).await);
+    assert_eq!(simple("QUEUED"), request(&mut connection, &[b"incr", b"k"]).await);
+    assert_eq!(simple("QUEUED"), request(&mut connection, &[b"lpush", b"k", b"a"]).await);
+    assert_eq!(simple("QUEUED"), request(&mut connection, &[b"get", b"k"]).await);
+    // 执行的时候出错不影响其他命令
+    assert_eq!(
+        Frame::Array(vec![
+            simple("OK"),
+            Frame::Integer(2),
+            error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            bulk("2"),
+        ]),
+        request(&mut connection, &[b"exec"]).await
+    );
+
+    // 事务结束了，命令直接执行
+    assert_eq!(bulk("2"), request(&mut connection, &[b"get", b"k"]).await);
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Array(vec![]), request(&mut connection, &[b"exec"]).await);
+}
+
+#[tokio::test]
+async fn discard_and_misuse() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(error("ERR EXEC without MULTI"), request(&mut connection, &[b"exec"]).await);
+    assert_eq!(error("ERR DISCARD without MULTI"), request(&mut connection, &[b"discard"]).await);
+
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(error("ERR MULTI calls can not be nested"), request(&mut connection, &[b"multi"]).await);
+    assert_eq!(error("ERR WATCH inside MULTI is not allowed"), request(&mut connection, &[b"watch", b"k"]).await);
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(simple("OK"), request(&mut connection, &[b"discard"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+    assert_eq!(error("ERR EXEC without MULTI"), request(&mut connection, &[b"exec"]).await);
+}
+
+#[tokio::test]
+async fn queue_errors_abort_exec() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(
+        error("ERR wrong number of arguments for 'get' command"),
+        request(&mut connection, &[b"get"]).await
+    );
+    assert_eq!(
+        error("EXECABORT Transaction discarded because of previous errors."),
+        request(&mut connection, &[b"exec"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert!(matches!(request(&mut connection, &[b"nosuchcommand"]).await, Frame::Error(_)));
+    assert_eq!(
+        error("ERR Command not allowed inside a transaction"),
+        request(&mut connection, &[b"subscribe", b"ch"]).await
+    );
+    assert_eq!(
+        error("EXECABORT Transaction discarded because of previous errors."),
+        request(&mut connection, &[b"exec"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+
+    // 事务里WATCH也算命令出错
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    assert_eq!(error("ERR WATCH inside MULTI is not allowed"), request(&mut connection, &[b"watch", b"k"]).await);
+    assert_eq!(
+        error("EXECABORT Transaction discarded because of previous errors."),
+        request(&mut connection, &[b"exec"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn watch_aborts_on_change() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"stock", b"10"]).await;
+    assert_eq!(simple("OK"), request(&mut connection, &[b"watch", b"stock"]).await);
+    request(&mut other, &[b"decr", b"stock"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"stock", b"0"]).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+    assert_eq!(bulk("9"), request(&mut connection, &[b"get", b"stock"]).await);
+
+    // EXEC之后就不再WATCH了
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"stock", b"0"]).await;
+    assert_eq!(Frame::Array(vec![simple("OK")]), request(&mut connection, &[b"exec"]).await);
+
+    // 没有改过的key不影响执行
+    request(&mut connection, &[b"watch", b"stock", b"missing"]).await;
+    request(&mut other, &[b"get", b"stock"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"incr", b"stock"]).await;
+    assert_eq!(Frame::Array(vec![Frame::Integer(1)]), request(&mut connection, &[b"exec"]).await);
+
+    // 不存在的key被创建出来也算改过
+    request(&mut connection, &[b"watch", b"missing"]).await;
+    request(&mut other, &[b"set", b"missing", b"v"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+
+    // UNWATCH之后改了也没关系
+    request(&mut connection, &[b"watch", b"stock"]).await;
+    assert_eq!(simple("OK"), request(&mut connection, &[b"unwatch"]).await);
+    request(&mut other, &[b"incr", b"stock"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Array(vec![]), request(&mut connection, &[b"exec"]).await);
+
+    // DISCARD也会清掉WATCH
+    request(&mut connection, &[b"watch", b"stock"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"discard"]).await;
+    request(&mut other, &[b"incr", b"stock"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Array(vec![]), request(&mut connection, &[b"exec"]).await);
+}
+
+#[tokio::test]
+async fn watch_sees_every_kind_of_change() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    let changes: &[&[&[u8]]] = &[
+        &[b"rpush", b"list", b"b"],
+        &[b"lpop", b"list"],
+        &[b"expire", b"list", b"100"],
+        &[b"del", b"list"],
+        &[b"flushall"],
+    ];
+    for change in changes {
+        request(&mut other, &[b"rpush", b"list", b"a", b"b"]).await;
+        request(&mut connection, &[b"watch", b"list"]).await;
+        request(&mut other, change).await;
+        request(&mut connection, &[b"multi"]).await;
+        request(&mut connection, &[b"llen", b"list"]).await;
+        assert_eq!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+    }
+
+    // 换库之后原来WATCH的key的内容变了
+    request(&mut other, &[b"set", b"k", b"v"]).await;
+    request(&mut connection, &[b"watch", b"k"]).await;
+    request(&mut other, &[b"swapdb", b"0", b"1"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+
+    // WATCH的是当时选中的库里的key
+    request(&mut connection, &[b"select", b"1"]).await;
+    request(&mut connection, &[b"watch", b"k"]).await;
+    request(&mut connection, &[b"select", b"0"]).await;
+    request(&mut other, &[b"select", b"1"]).await;
+    request(&mut other, &[b"append", b"k", b"x"]).await;
+    request(&mut connection, &[b"multi"]).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+}
+
+#[tokio::test]
+async fn watch_ignores_failed_and_noop_writes() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    request(&mut other, &[b"set", b"string", b"v"]).await;
+    request(&mut other, &[b"hset", b"hash", b"f", b"1"]).await;
+    request(&mut other, &[b"sadd", b"set", b"a"]).await;
+    request(&mut other, &[b"xadd", b"stream", b"1", b"f", b"v"]).await;
+    request(&mut other, &[b"xgroup", b"create", b"stream", b"g", b"0"]).await;
+
+    // 报错或者什么都没改的写命令不会让WATCH的事务失败
+    let writes: &[(&[u8], &[&[u8]])] = &[
+        (b"string", &[b"lpush", b"string", b"a"]),
+        (b"hash", &[b"hincrby", b"hash", b"f", b"9223372036854775807"]),
+        (b"hash", &[b"hdel", b"hash", b"missing"]),
+        (b"set", &[b"sadd", b"set", b"a"]),
+        (b"set", &[b"srem", b"set", b"missing"]),
+        (b"stream", &[b"xack", b"stream", b"g", b"1-0"]),
+        (b"stream", &[b"xadd", b"stream", b"1", b"f", b"v"]),
+    ];
+    for (key, write) in writes {
+        request(&mut connection, &[b"watch", key]).await;
+        assert!(matches!(request(&mut other, write).await, Frame::Integer(0) | Frame::Error(_)));
+        request(&mut connection, &[b"multi"]).await;
+        request(&mut connection, &[b"type", key]).await;
+        assert_ne!(Frame::Null, request(&mut connection, &[b"exec"]).await);
+    }
+}
+
+#[tokio::test]
+async fn select_and_blocking_inside_exec() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"select", b"2"]).await;
+    request(&mut connection, &[b"set", b"k", b"v"]).await;
+    request(&mut connection, &[b"blpop", b"empty", b"0"]).await;
+    request(&mut connection, &[b"xread", b"BLOCK", b"0", b"STREAMS", b"s", b"$"]).await;
+    assert_eq!(
+        Frame::Array(vec![simple("OK"), simple("OK"), Frame::Null, Frame::Null]),
+        request(&mut connection, &[b"exec"]).await
+    );
+
+    // 事务里选中的库在EXEC之后继续生效
+    assert_eq!(bulk("v"), request(&mut connection, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn exec_while_others_blocked() {
+    let addr = start_server().await;
+    let mut blpop = connect(addr).await;
+    let mut xread = connect(addr).await;
+    let mut connection = connect(addr).await;
+
+    // 阻塞着的连接不能挡住事务，测试和服务端跑在同一个线程上，卡住线程的话这里就回不来了
+    send(&mut blpop, &[b"blpop", b"list", b"0"]).await;
+    send(&mut xread, &[b"xread", b"BLOCK", b"0", b"STREAMS", b"s", b"0"]).await;
+
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+    request(&mut connection, &[b"xadd", b"s", b"1-1", b"f", b"v"]).await;
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(1), bulk("1-1")]),
+        request(&mut connection, &[b"exec"]).await
+    );
+
+    assert_eq!(Frame::Array(vec![bulk("list"), bulk("a")]), blpop.read_frame().await.unwrap().unwrap());
+    assert!(matches!(xread.read_frame().await.unwrap().unwrap(), Frame::Array(_)));
+}
+
+#[tokio::test]
+async fn exec_while_client_stops_reading() {
+    let addr = start_server().await;
+    let mut stalled = connect(addr).await;
+    let mut connection = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    // 回复写不出去的连接不能一直占着许可，不然事务和其他连接的命令都要跟着等
+    let value = vec![b'x'; 8 * 1024 * 1024];
+    request(&mut connection, &[b"set", b"big", &value]).await;
+    for _ in 0..16 {
+        send(&mut stalled, &[b"get", b"big"]).await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let exec = async {
+        request(&mut connection, &[b"multi"]).await;
+        request(&mut connection, &[b"set", b"k", b"v"]).await;
+        request(&mut connection, &[b"exec"]).await
+    };
+    let exec = tokio::time::timeout(Duration::from_secs(5), exec).await;
+    assert_eq!(Frame::Array(vec![simple("OK")]), exec.unwrap());
+    let get = tokio::time::timeout(Duration::from_secs(5), request(&mut other, &[b"get", b"k"])).await;
+    assert_eq!(bulk("v"), get.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn check_and_set_under_contention() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    request(&mut connection, &[b"set", b"counter", b"0"]).await;
+
+    let mut handles = vec![];
+    for _ in 0..8 {
+        handles.push(tokio::spawn(async move {
+            let mut connection = connect(addr).await;
+            for _ in 0..20 {
+                // 读出来加一再写回去，被别人抢先改了就重试
+                loop {
+                    request(&mut connection, &[b"watch", b"counter"]).await;
+                    let value = match request(&mut connection, &[b"get", b"counter"]).await {
+                        Frame::Bulk(value) => std::str::from_utf8(&value).unwrap().parse::<u64>().unwrap(),
+                        frame => panic!("unexpected {:?}", frame),
+                    };
+                    request(&mut connection, &[b"multi"]).await;
+                    request(&mut connection, &[b"set", b"counter", (value + 1).to_string().as_bytes()]).await;
+                    if request(&mut connection, &[b"exec"]).await != Frame::Null {
+                        break;
+                    }
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(bulk("160"), request(&mut connection, &[b"get", b"counter"]).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn exec_is_atomic() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let writer = tokio::spawn(async move {
+        let mut connection = connect(addr).await;
+        for _ in 0..200 {
+            request(&mut connection, &[b"incr", b"n"]).await;
+        }
+    });
+
+    // 事务里的INCR中间不会插进别的连接的INCR
+    for _ in 0..10 {
+        request(&mut connection, &[b"multi"]).await;
+        for _ in 0..20 {
+            request(&mut connection, &[b"incr", b"n"]).await;
+        }
+        let replies = match request(&mut connection, &[b"exec"]).await {
+            Frame::Array(replies) => replies,
+            frame => panic!("unexpected {:?}", frame),
+        };
+        let first = match replies[0] {
+            Frame::Integer(first) => first,
+            _ => panic!(),
+        };
+        let expected: Vec<_> = (first..first + 20).map(Frame::Integer).collect();
+        assert_eq!(expected, replies);
+    }
+
+    writer.await.unwrap();
+    assert_eq!(bulk("400"), request(&mut connection, &[b"get", b"n"]).await);
+}
@@ -0,0 +1,108 @@
+use bytes::Bytes;
+use std::net::SocketAddr;
+use w::client::Client;
+use w::cmd::PubSub;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect};
+
+async fn pubsub(addr: SocketAddr, cmd: PubSub) -> Frame {
+    let mut connection = connect(addr).await;
+    connection.write_frame(&cmd.into_frame()).await.unwrap();
+    connection.read_frame().await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn pattern_subscription_receives_pmessage() {
+    let addr = start_server().await;
+
+    let subscriber = Client::connect(addr).await.unwrap();
+    let mut subscriber = subscriber.psubscribe(vec!["orders.*".into()]).await.unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    assert_eq!(1, publisher.publish("orders.created", "42".into()).await.unwrap());
+    assert_eq!(0, publisher.publish("users.created", "7".into()).await.unwrap());
+
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!(Some("orders.*".to_string()), message.pattern);
+    assert_eq!("orders.created", message.channel);
+    assert_eq!(Bytes::from("42"), message.content);
+}
+
+#[tokio::test]
+async fn pattern_subscription_glob_semantics() {
+    let addr = start_server().await;
+
+    let patterns = ["h?llo", "h*llo", "h[ae]llo", "h[^e]llo", "h[a-b]llo", "h\\*llo"];
+    let subscriber = Client::connect(addr).await.unwrap();
+    let _subscriber = subscriber.psubscribe(patterns.iter().map(|p| p.to_string()).collect())
+        .await
+        .unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    // h?llo h*llo h[ae]llo
+    assert_eq!(3, publisher.publish("hello", "".into()).await.unwrap());
+    // h?llo h*llo h[ae]llo h[^e]llo h[a-b]llo
+    assert_eq!(5, publisher.publish("hallo", "".into()).await.unwrap());
+    // h*llo
+    assert_eq!(1, publisher.publish("heeeello", "".into()).await.unwrap());
+    // h?llo h*llo h[^e]llo h\*llo
+    assert_eq!(4, publisher.publish("h*llo", "".into()).await.unwrap());
+    assert_eq!(0, publisher.publish("hllo!", "".into()).await.unwrap());
+}
+
+#[tokio::test]
+async fn channel_and_pattern_subscriptions_on_one_connection() {
+    let addr = start_server().await;
+
+    let subscriber = Client::connect(addr).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news".into()]).await.unwrap();
+    subscriber.psubscribe(&["n*".into()]).await.unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    assert_eq!(2, publisher.publish("news", "hi".into()).await.unwrap());
+
+    let first = subscriber.next_message().await.unwrap().unwrap();
+    let second = subscriber.next_message().await.unwrap().unwrap();
+    let mut patterns = vec![first.pattern, second.pattern];
+    patterns.sort();
+    assert_eq!(vec![None, Some("n*".to_string())], patterns);
+
+    subscriber.punsubscribe(&[]).await.unwrap();
+    assert!(subscriber.get_subscribed_patterns().is_empty());
+    assert_eq!(1, publisher.publish("news", "hi".into()).await.unwrap());
+}
+
+#[tokio::test]
+async fn pubsub_introspection() {
+    let addr = start_server().await;
+
+    let subscriber = Client::connect(addr).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["orders.created".into(), "users.created".into()])
+        .await
+        .unwrap();
+    subscriber.psubscribe(&["orders.*".into(), "users.*".into()]).await.unwrap();
+
+    match pubsub(addr, PubSub::Channels(Some("orders.*".into()))).await {
+        Frame::Array(channels) => {
+            assert_eq!(1, channels.len());
+            assert!(matches!(&channels[0], Frame::Bulk(b) if b == "orders.created"));
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+
+    match pubsub(addr, PubSub::NumSub(vec!["users.created".into(), "nobody".into()])).await {
+        Frame::Array(nums) => match nums.as_slice() {
+            [Frame::Bulk(a), Frame::Integer(1), Frame::Bulk(b), Frame::Integer(0)] => {
+                assert_eq!("users.created", a);
+                assert_eq!("nobody", b);
+            }
+            nums => panic!("unexpected frame {:?}", nums),
+        },
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+
+    assert!(matches!(pubsub(addr, PubSub::NumPat).await, Frame::Integer(2)));
+}
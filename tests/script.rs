@@ -0,0 +1,279 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use w::frame::Frame;
+use w::server::{self, Config};
+
+mod common;
+
+use common::{start_server, connect, request, send, bulk, simple, error, is_error};
+
+#[tokio::test]
+async fn eval_return_values() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let cases: &[(&[u8], Frame)] = &[
+        (b"return 1", Frame::Integer(1)),
+        (b"return 3.99", Frame::Integer(3)),
+        (b"return 'hello'", bulk("hello")),
+        (b"return true", Frame::Integer(1)),
+        (b"return false", Frame::Null),
+        (b"return nil", Frame::Null),
+        (b"return {1, 'a', {2}, nil, 'ignored'}", Frame::Array(vec![Frame::Integer(1), bulk("a"), Frame::Array(vec![Frame::Integer(2)])])),
+        (b"return redis.status_reply('FINE')", simple("FINE")),
+        (b"return redis.error_reply('MY failure')", error("MY failure")),
+        (b"return {err = 'CUSTOM error'}", error("CUSTOM error")),
+    ];
+    for (script, expected) in cases {
+        assert_eq!(*expected, request(&mut connection, &[b"eval", script, b"0"]).await);
+    }
+
+    assert_eq!(
+        Frame::Array(vec![bulk("k1"), bulk("k2"), bulk("a1")]),
+        request(&mut connection, &[b"eval", b"return {KEYS[1], KEYS[2], ARGV[1]}", b"2", b"k1", b"k2", b"a1"]).await
+    );
+    assert_eq!(
+        error("ERR Number of keys can't be greater than number of args"),
+        request(&mut connection, &[b"eval", b"return 1", b"2", b"k1"]).await
+    );
+    assert_eq!(
+        error("ERR Number of keys can't be negative"),
+        request(&mut connection, &[b"eval", b"return 1", b"-1"]).await
+    );
+    assert!(is_error(&request(&mut connection, &[b"eval", b"return +", b"0"]).await, "ERR Error compiling script"));
+    assert!(is_error(&request(&mut connection, &[b"eval", b"error('boom')", b"0"]).await, "ERR Error running script"));
+    assert!(is_error(&request(&mut connection, &[b"eval", b"return dofile('/etc/passwd')", b"0"]).await, "ERR Error running script"));
+}
+
+#[tokio::test]
+async fn redis_call_and_pcall() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let script = b"redis.call('set', KEYS[1], ARGV[1]); redis.call('incrby', KEYS[1], 5); return redis.call('get', KEYS[1])";
+    assert_eq!(bulk("15"), request(&mut connection, &[b"eval", script, b"1", b"counter", b"10"]).await);
+
+    // 回复的类型按RESP2转换
+    assert_eq!(
+        Frame::Array(vec![bulk("OK"), Frame::Integer(0), Frame::Integer(2)]),
+        request(
+            &mut connection,
+            &[b"eval", b"local ok = redis.call('set', 'a', 1); return {ok.ok, redis.call('get', 'nothing') == false and 0 or 1, redis.call('rpush', 'l', 'x', 'y')}", b"0"],
+        ).await
+    );
+
+    // call出错直接中止脚本，pcall把错误当成返回值
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+    assert_eq!(
+        error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        request(&mut connection, &[b"eval", b"redis.call('incr', 'list'); return 'unreachable'", b"0"]).await
+    );
+    assert_eq!(
+        bulk("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        request(&mut connection, &[b"eval", b"return redis.pcall('incr', 'list').err", b"0"]).await
+    );
+    assert!(is_error(
+        &request(&mut connection, &[b"eval", b"return redis.call('nosuchcommand')", b"0"]).await,
+        "ERR unknown command"
+    ));
+    assert_eq!(
+        error("ERR This Redis command is not allowed from script"),
+        request(&mut connection, &[b"eval", b"return redis.call('multi')", b"0"]).await
+    );
+    // HELLO会改调用者的协议版本
+    assert_eq!(
+        error("ERR This Redis command is not allowed from script"),
+        request(&mut connection, &[b"eval", b"return redis.call('hello', '3')", b"0"]).await
+    );
+    assert_eq!(
+        error("ERR Lua redis lib command arguments must be strings or integers"),
+        request(&mut connection, &[b"eval", b"return redis.call('get', {})", b"0"]).await
+    );
+
+    // 脚本里的阻塞命令不等待，SELECT也不影响这个连接
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(0), bulk("in db 1")]),
+        request(
+            &mut connection,
+            &[b"eval", b"local r = redis.call('blpop', 'empty', 0); redis.call('select', 1); redis.call('set', 'k', 'in db 1'); return {r and 1 or 0, redis.call('get', 'k')}", b"0"],
+        ).await
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn script_cache() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let script = b"return ARGV[1]";
+    let sha1 = match request(&mut connection, &[b"script", b"load", script]).await {
+        Frame::Bulk(sha1) => sha1,
+        frame => panic!("unexpected {:?}", frame),
+    };
+    assert_eq!(40, sha1.len());
+    assert_eq!(
+        Frame::Bulk(sha1.clone()),
+        request(&mut connection, &[b"eval", b"return redis.sha1hex(ARGV[1])", b"0", script]).await
+    );
+
+    assert_eq!(bulk("hi"), request(&mut connection, &[b"evalsha", &sha1, b"0", b"hi"]).await);
+    let upper = sha1.to_ascii_uppercase();
+    assert_eq!(bulk("hi"), request(&mut connection, &[b"evalsha", &upper, b"0", b"hi"]).await);
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(1), Frame::Integer(0), Frame::Integer(1)]),
+        request(&mut connection, &[b"script", b"exists", &sha1, b"0000", &upper]).await
+    );
+
+    // EVAL执行过的脚本也能用EVALSHA
+    let sha1 = match request(&mut connection, &[b"eval", b"return redis.sha1hex('return 7')", b"0"]).await {
+        Frame::Bulk(sha1) => sha1,
+        frame => panic!("unexpected {:?}", frame),
+    };
+    request(&mut connection, &[b"eval", b"return 7", b"0"]).await;
+    assert_eq!(Frame::Integer(7), request(&mut connection, &[b"evalsha", &sha1, b"0"]).await);
+
+    assert_eq!(simple("OK"), request(&mut connection, &[b"script", b"flush"]).await);
+    assert_eq!(
+        error("NOSCRIPT No matching script. Please use EVAL."),
+        request(&mut connection, &[b"evalsha", &sha1, b"0"]).await
+    );
+    assert!(is_error(&request(&mut connection, &[b"script", b"load", b"return +"]).await, "ERR Error compiling script"));
+    assert_eq!(
+        error("NOTBUSY No scripts in execution right now."),
+        request(&mut connection, &[b"script", b"kill"]).await
+    );
+}
+
+#[tokio::test]
+async fn eval_inside_multi() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"multi"]).await;
+    request(&mut connection, &[b"set", b"k", b"1"]).await;
+    assert_eq!(simple("QUEUED"), request(&mut connection, &[b"eval", b"return redis.call('incr', KEYS[1])", b"1", b"k"]).await);
+    request(&mut connection, &[b"get", b"k"]).await;
+    assert_eq!(
+        Frame::Array(vec![simple("OK"), Frame::Integer(2), bulk("2")]),
+        request(&mut connection, &[b"exec"]).await
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn scripts_are_atomic() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    request(&mut connection, &[b"set", b"stock", b"100"]).await;
+
+    // 库存够的时候才扣减，并发扣减也不会扣成负数
+    let script = b"local stock = tonumber(redis.call('get', KEYS[1])); \
+        if stock >= tonumber(ARGV[1]) then return redis.call('decrby', KEYS[1], ARGV[1]) end; return -1";
+    let mut handles = vec![];
+    for _ in 0..8 {
+        handles.push(tokio::spawn(async move {
+            let mut connection = connect(addr).await;
+            let mut sold = 0;
+            for _ in 0..20 {
+                if request(&mut connection, &[b"eval", script, b"1", b"stock", b"3"]).await != Frame::Integer(-1) {
+                    sold += 3;
+                }
+            }
+            sold
+        }));
+    }
+
+    let mut sold = 0;
+    for handle in handles {
+        sold += handle.await.unwrap();
+    }
+    assert_eq!(99, sold);
+    assert_eq!(bulk("1"), request(&mut connection, &[b"get", b"stock"]).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn kill_busy_script() {
+    let addr = start_server_with_script_limit().await;
+    let mut busy = connect(addr).await;
+    let mut other = connect(addr).await;
+    request(&mut other, &[b"set", b"k", b"v"]).await;
+
+    // 脚本里的pcall也拦不住SCRIPT KILL
+    send(&mut busy, &[b"eval", b"while true do pcall(function() while true do end end) end", b"0"]).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(is_error(&request(&mut other, &[b"get", b"k"]).await, "BUSY"));
+    assert_eq!(simple("OK"), request(&mut other, &[b"script", b"kill"]).await);
+    assert_eq!(
+        error("ERR Script killed by user with SCRIPT KILL..."),
+        busy.read_frame().await.unwrap().unwrap()
+    );
+
+    assert_eq!(bulk("v"), request(&mut other, &[b"get", b"k"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut busy, &[b"eval", b"return 1", b"0"]).await);
+}
+
+// 服务端和测试都跑在同一个线程上，脚本占着这个线程的话SCRIPT KILL就回不来了
+#[tokio::test]
+async fn kill_script_on_single_thread() {
+    let addr = start_server_with_script_limit().await;
+    let mut busy = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    send(&mut busy, &[b"eval", b"redis.call('get', 'k') while true do end", b"0"]).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(is_error(&request(&mut other, &[b"get", b"k"]).await, "BUSY"));
+    assert_eq!(simple("OK"), request(&mut other, &[b"script", b"kill"]).await);
+    assert_eq!(
+        error("ERR Script killed by user with SCRIPT KILL..."),
+        busy.read_frame().await.unwrap().unwrap()
+    );
+    assert_eq!(Frame::Null, request(&mut other, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn unkillable_after_write() {
+    let addr = start_server_with_script_limit().await;
+    let mut busy = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    // 测试结束的时候服务端的连接被放掉，脚本也就停下来了
+    send(&mut busy, &[b"eval", b"redis.call('set', 'k', 'v') while true do end", b"0"]).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(is_error(&request(&mut other, &[b"get", b"k"]).await, "BUSY"));
+    assert!(is_error(&request(&mut other, &[b"script", b"kill"]).await, "UNKILLABLE"));
+    assert!(is_error(&request(&mut other, &[b"get", b"k"]).await, "BUSY"));
+}
+
+#[tokio::test]
+async fn killable_after_failed_write() {
+    let addr = start_server_with_script_limit().await;
+    let mut busy = connect(addr).await;
+    let mut other = connect(addr).await;
+    request(&mut other, &[b"set", b"k", b"v"]).await;
+    request(&mut other, &[b"sadd", b"set", b"a"]).await;
+
+    // 类型不对的写和什么都没改的写都不算改过数据
+    let script = b"redis.pcall('lpush', 'k', 'a') redis.call('sadd', 'set', 'a') while true do end";
+    send(&mut busy, &[b"eval", script, b"0"]).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(is_error(&request(&mut other, &[b"get", b"k"]).await, "BUSY"));
+    assert_eq!(simple("OK"), request(&mut other, &[b"script", b"kill"]).await);
+    assert_eq!(
+        error("ERR Script killed by user with SCRIPT KILL..."),
+        busy.read_frame().await.unwrap().unwrap()
+    );
+    assert_eq!(bulk("v"), request(&mut other, &[b"get", b"k"]).await);
+}
+
+// 脚本执行超过100毫秒其他连接就回复BUSY
+async fn start_server_with_script_limit() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Config {
+        script_time_limit: Duration::from_millis(100),
+        ..Config::default()
+    };
+    tokio::spawn(async move { server::run_with_config(listener, std::future::pending::<()>(), config).await });
+    addr
+}
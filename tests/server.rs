@@ -0,0 +1,121 @@
+use bytes::Bytes;
+use tokio::net::TcpStream;
+use w::connection::Connection;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request};
+
+fn assert_error(frame: Frame, expected: &str) {
+    match frame {
+        Frame::Error(msg) => assert_eq!(expected, msg),
+        frame => panic!("expected error frame, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn unknown_command_keeps_connection_open() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_error(request(&mut connection, &[b"foo", b"bar"]).await, "ERR unknown command 'foo'");
+    assert!(matches!(request(&mut connection, &[b"get", b"hello"]).await, Frame::Null));
+}
+
+#[tokio::test]
+async fn wrong_number_of_arguments() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_error(
+        request(&mut connection, &[b"GET"]).await,
+        "ERR wrong number of arguments for 'get' command",
+    );
+    assert_error(
+        request(&mut connection, &[b"get", b"a", b"b"]).await,
+        "ERR wrong number of arguments for 'get' command",
+    );
+    assert_error(
+        request(&mut connection, &[b"set", b"a"]).await,
+        "ERR wrong number of arguments for 'set' command",
+    );
+    assert!(matches!(request(&mut connection, &[b"get", b"a"]).await, Frame::Null));
+}
+
+#[tokio::test]
+async fn invalid_arguments() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_error(request(&mut connection, &[b"set", b"a", b"1", b"FOO"]).await, "ERR syntax error");
+    assert_error(
+        request(&mut connection, &[b"set", b"a", b"1", b"EX", b"abc"]).await,
+        "ERR value is not an integer or out of range",
+    );
+    assert!(matches!(request(&mut connection, &[b"get", b"\xff"]).await, Frame::Null));
+    assert!(matches!(request(&mut connection, &[b"get", b"a"]).await, Frame::Null));
+}
+
+// 直接读写原始字节，确认协议切换之后的序列化格式
+async fn raw_request(stream: &mut TcpStream, request: &[u8], expected: &[u8]) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    stream.write_all(request).await.unwrap();
+
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(expected), String::from_utf8_lossy(&response));
+}
+
+#[tokio::test]
+async fn hello_switches_protocol() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    raw_request(&mut stream, b"*2\r\n$3\r\nGET\r\n$1\r\na\r\n", b"$-1\r\n").await;
+
+    let hello = concat!(
+        "%6\r\n",
+        "$6\r\nserver\r\n$5\r\nredis\r\n",
+        "$7\r\nversion\r\n$5\r\n0.1.0\r\n",
+        "$5\r\nproto\r\n:3\r\n",
+        "$4\r\nmode\r\n$10\r\nstandalone\r\n",
+        "$4\r\nrole\r\n$6\r\nmaster\r\n",
+        "$7\r\nmodules\r\n*0\r\n",
+    );
+    raw_request(&mut stream, b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n", hello.as_bytes()).await;
+    raw_request(&mut stream, b"*2\r\n$3\r\nGET\r\n$1\r\na\r\n", b"_\r\n").await;
+
+    raw_request(
+        &mut stream,
+        b"*2\r\n$5\r\nHELLO\r\n$1\r\n4\r\n",
+        b"-NOPROTO unsupported protocol version\r\n",
+    ).await;
+
+    // 切回RESP2之后map会展开成数组
+    let mut connection = Connection::new(stream);
+    match request(&mut connection, &[b"hello", b"2"]).await {
+        Frame::Array(fields) => {
+            assert_eq!(12, fields.len());
+            assert_eq!(Frame::Integer(2), fields[5]);
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"a"]).await);
+}
+
+#[tokio::test]
+async fn binary_safe_keys() {
+    use w::client::Client;
+
+    let addr = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let key: &[u8] = b"\x00\xff\xfe key\r\n";
+    client.set(key, "binary".into()).await.unwrap();
+    assert_eq!(Some(Bytes::from("binary")), client.get(key).await.unwrap());
+
+    // 只差一个字节的key是另一个key
+    assert_eq!(None, client.get(b"\x00\xff\xfe key\r").await.unwrap());
+}
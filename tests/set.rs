@@ -0,0 +1,92 @@
+use bytes::Bytes;
+use w::connection::Connection;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request};
+
+// 集合是无序的，排序之后再比较
+async fn members(connection: &mut Connection, args: &[&[u8]]) -> Vec<Bytes> {
+    let mut members: Vec<Bytes> = match request(connection, args).await {
+        Frame::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Frame::Bulk(member) => member,
+                frame => panic!("expected bulk, got {:?}", frame),
+            })
+            .collect(),
+        frame => panic!("expected array, got {:?}", frame),
+    };
+    members.sort();
+    members
+}
+
+#[tokio::test]
+async fn add_and_remove_members() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"sadd", b"tags", b"a", b"b", b"c", b"a"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"sadd", b"tags", b"c", b"d"]).await);
+    assert_eq!(Frame::Integer(4), request(&mut connection, &[b"scard", b"tags"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"sismember", b"tags", b"a"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"sismember", b"tags", b"z"]).await);
+    assert_eq!(vec!["a", "b", "c", "d"], members(&mut connection, &[b"smembers", b"tags"]).await);
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"srem", b"tags", b"a", b"b", b"z"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"srem", b"tags", b"c", b"d"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"scard", b"tags"]).await);
+    assert!(members(&mut connection, &[b"smembers", b"tags"]).await.is_empty());
+}
+
+#[tokio::test]
+async fn set_algebra() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"sadd", b"a", b"1", b"2", b"3", b"4"]).await;
+    request(&mut connection, &[b"sadd", b"b", b"3", b"4", b"5"]).await;
+    request(&mut connection, &[b"sadd", b"c", b"4", b"6"]).await;
+
+    assert_eq!(vec!["4"], members(&mut connection, &[b"sinter", b"a", b"b", b"c"]).await);
+    assert_eq!(vec!["1", "2", "3", "4", "5", "6"], members(&mut connection, &[b"sunion", b"a", b"b", b"c"]).await);
+    assert_eq!(vec!["1", "2"], members(&mut connection, &[b"sdiff", b"a", b"b", b"c"]).await);
+
+    // 不存在的key当成空集合
+    assert!(members(&mut connection, &[b"sinter", b"a", b"missing"]).await.is_empty());
+    assert_eq!(vec!["3", "4", "5"], members(&mut connection, &[b"sunion", b"missing", b"b"]).await);
+    assert!(members(&mut connection, &[b"sdiff", b"missing", b"a"]).await.is_empty());
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"sinterstore", b"dest", b"a", b"b"]).await);
+    assert_eq!(vec!["3", "4"], members(&mut connection, &[b"smembers", b"dest"]).await);
+    assert_eq!(Frame::Integer(6), request(&mut connection, &[b"sunionstore", b"dest", b"a", b"b", b"c"]).await);
+    assert_eq!(Frame::Integer(6), request(&mut connection, &[b"scard", b"dest"]).await);
+
+    // destination可以是参与运算的key，也会覆盖掉其他类型
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"sdiffstore", b"a", b"a", b"b"]).await);
+    assert_eq!(vec!["1", "2"], members(&mut connection, &[b"smembers", b"a"]).await);
+    request(&mut connection, &[b"set", b"string", b"value"]).await;
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"sinterstore", b"string", b"b", b"c"]).await);
+    assert_eq!(vec!["4"], members(&mut connection, &[b"smembers", b"string"]).await);
+
+    // 结果为空的时候destination会被删掉
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"sinterstore", b"dest", b"a", b"missing"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"scard", b"dest"]).await);
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    let wrongtype = Frame::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+
+    request(&mut connection, &[b"sadd", b"set", b"a"]).await;
+    request(&mut connection, &[b"rpush", b"list", b"a"]).await;
+
+    assert_eq!(wrongtype, request(&mut connection, &[b"sadd", b"list", b"a"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"sinter", b"set", b"list"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"sunionstore", b"dest", b"set", b"list"]).await);
+    assert_eq!(wrongtype, request(&mut connection, &[b"lpush", b"set", b"a"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"scard", b"dest"]).await);
+}
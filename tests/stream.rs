@@ -0,0 +1,287 @@
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use w::frame::Frame;
+use w::server;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, error};
+
+// 一条消息：[id, [field, value, ...]]
+fn entry(id: &str, fields: &[&str]) -> Frame {
+    Frame::Array(vec![bulk(id), Frame::Array(fields.iter().map(bulk).collect())])
+}
+
+fn entries(items: Vec<Frame>) -> Frame {
+    Frame::Array(items)
+}
+
+#[tokio::test]
+async fn add_and_range() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(bulk("1-1"), request(&mut connection, &[b"xadd", b"s", b"1-1", b"a", b"1"]).await);
+    assert_eq!(bulk("1-2"), request(&mut connection, &[b"xadd", b"s", b"1-*", b"b", b"2"]).await);
+    assert_eq!(bulk("5-0"), request(&mut connection, &[b"xadd", b"s", b"5", b"c", b"3", b"d", b"4"]).await);
+    assert_eq!(bulk("6-0"), request(&mut connection, &[b"xadd", b"s", b"6-*", b"e", b"5"]).await);
+    assert_eq!(Frame::Integer(4), request(&mut connection, &[b"xlen", b"s"]).await);
+
+    assert_eq!(
+        error("ERR The ID specified in XADD is equal or smaller than the target stream top item"),
+        request(&mut connection, &[b"xadd", b"s", b"6-0", b"f", b"6"]).await,
+    );
+    assert_eq!(
+        error("ERR The ID specified in XADD is equal or smaller than the target stream top item"),
+        request(&mut connection, &[b"xadd", b"s", b"5-*", b"f", b"6"]).await,
+    );
+    assert_eq!(
+        error("ERR The ID specified in XADD must be greater than 0-0"),
+        request(&mut connection, &[b"xadd", b"other", b"0-0", b"f", b"6"]).await,
+    );
+    // ID不合法的时候不会创建流
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xlen", b"other"]).await);
+    assert_eq!(bulk("0-1"), request(&mut connection, &[b"xadd", b"fresh", b"0-*", b"f", b"v"]).await);
+
+    // 自动生成的ID用当前时间，一定比手动加的大
+    let auto = match request(&mut connection, &[b"xadd", b"s", b"*", b"f", b"7"]).await {
+        Frame::Bulk(id) => String::from_utf8(id.to_vec()).unwrap(),
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert!(auto.split('-').next().unwrap().parse::<u64>().unwrap() > 6);
+
+    assert_eq!(
+        entries(vec![
+            entry("1-1", &["a", "1"]),
+            entry("1-2", &["b", "2"]),
+            entry("5-0", &["c", "3", "d", "4"]),
+            entry("6-0", &["e", "5"]),
+            entry(&auto, &["f", "7"]),
+        ]),
+        request(&mut connection, &[b"xrange", b"s", b"-", b"+"]).await,
+    );
+    assert_eq!(
+        entries(vec![entry("1-2", &["b", "2"]), entry("5-0", &["c", "3", "d", "4"])]),
+        request(&mut connection, &[b"xrange", b"s", b"(1-1", b"5"]).await,
+    );
+    assert_eq!(
+        entries(vec![entry("1-1", &["a", "1"]), entry("1-2", &["b", "2"])]),
+        request(&mut connection, &[b"xrange", b"s", b"1", b"(6", b"COUNT", b"2"]).await,
+    );
+    assert_eq!(entries(vec![]), request(&mut connection, &[b"xrange", b"s", b"6", b"5"]).await);
+    assert_eq!(entries(vec![]), request(&mut connection, &[b"xrange", b"s", b"-", b"+", b"count", b"0"]).await);
+
+    assert_eq!(
+        error("ERR Invalid stream ID specified as stream command argument"),
+        request(&mut connection, &[b"xrange", b"s", b"1-x", b"+"]).await,
+    );
+    assert_eq!(
+        error("ERR Invalid stream ID specified as stream command argument"),
+        request(&mut connection, &[b"xadd", b"s", b"-1", b"f", b"v"]).await,
+    );
+    assert_eq!(
+        error("ERR wrong number of arguments for 'xadd' command"),
+        request(&mut connection, &[b"xadd", b"s", b"*", b"f"]).await,
+    );
+
+    // NOMKSTREAM的时候key不存在就什么也不做
+    assert_eq!(Frame::Null, request(&mut connection, &[b"xadd", b"missing", b"NOMKSTREAM", b"*", b"f", b"v"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xlen", b"missing"]).await);
+
+    request(&mut connection, &[b"set", b"string", b"value"]).await;
+    assert_eq!(
+        error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        request(&mut connection, &[b"xadd", b"string", b"*", b"f", b"v"]).await,
+    );
+    assert_eq!(
+        error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        request(&mut connection, &[b"xlen", b"string"]).await,
+    );
+    request(&mut connection, &[b"xadd", b"stream", b"*", b"f", b"v"]).await;
+    assert_eq!(
+        error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        request(&mut connection, &[b"get", b"stream"]).await,
+    );
+}
+
+#[tokio::test]
+async fn trim() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    for id in &[b"1", b"2", b"3", b"4", b"5"] {
+        request(&mut connection, &[b"xadd", b"s", *id, b"f", b"v"]).await;
+    }
+    assert_eq!(bulk("6-0"), request(&mut connection, &[b"xadd", b"s", b"MAXLEN", b"3", b"6", b"f", b"v"]).await);
+    assert_eq!(
+        entries(vec![entry("4-0", &["f", "v"]), entry("5-0", &["f", "v"]), entry("6-0", &["f", "v"])]),
+        request(&mut connection, &[b"xrange", b"s", b"-", b"+"]).await,
+    );
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xtrim", b"s", b"MINID", b"=", b"5"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xtrim", b"s", b"MINID", b"5"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xtrim", b"s", b"maxlen", b"~", b"0", b"LIMIT", b"1"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xlen", b"s"]).await);
+
+    // 删空了流也还在，之后的ID还是要比删掉的大
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xtrim", b"s", b"MAXLEN", b"0"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xlen", b"s"]).await);
+    assert_eq!(
+        error("ERR The ID specified in XADD is equal or smaller than the target stream top item"),
+        request(&mut connection, &[b"xadd", b"s", b"6", b"f", b"v"]).await,
+    );
+
+    assert_eq!(
+        error("ERR syntax error, LIMIT cannot be used without the special ~ option"),
+        request(&mut connection, &[b"xtrim", b"s", b"MAXLEN", b"1", b"LIMIT", b"1"]).await,
+    );
+    assert_eq!(
+        error("ERR The MAXLEN argument must be >= 0."),
+        request(&mut connection, &[b"xtrim", b"s", b"MAXLEN", b"-1"]).await,
+    );
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"xtrim", b"s", b"LEN", b"1"]).await);
+}
+
+#[tokio::test]
+async fn read() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"xadd", b"a", b"1", b"f", b"1"]).await;
+    request(&mut connection, &[b"xadd", b"a", b"2", b"f", b"2"]).await;
+    request(&mut connection, &[b"xadd", b"b", b"1", b"f", b"3"]).await;
+
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Array(vec![bulk("a"), entries(vec![entry("2-0", &["f", "2"])])]),
+            Frame::Array(vec![bulk("b"), entries(vec![entry("1-0", &["f", "3"])])]),
+        ]),
+        request(&mut connection, &[b"xread", b"STREAMS", b"a", b"b", b"1", b"0"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![Frame::Array(vec![bulk("a"), entries(vec![entry("1-0", &["f", "1"])])])]),
+        request(&mut connection, &[b"xread", b"COUNT", b"1", b"STREAMS", b"a", b"b", b"0", b"1"]).await,
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"xread", b"STREAMS", b"a", b"$"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"xread", b"BLOCK", b"10", b"STREAMS", b"a", b"$"]).await);
+
+    assert_eq!(
+        error("ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."),
+        request(&mut connection, &[b"xread", b"STREAMS", b"a", b"b", b"0"]).await,
+    );
+    assert_eq!(
+        error("ERR timeout is negative"),
+        request(&mut connection, &[b"xread", b"BLOCK", b"-1", b"STREAMS", b"a", b"0"]).await,
+    );
+
+    // RESP3下回复key到消息的Map
+    request(&mut connection, &[b"hello", b"3"]).await;
+    assert_eq!(
+        Frame::Map(vec![(bulk("b"), entries(vec![entry("1-0", &["f", "3"])]))]),
+        request(&mut connection, &[b"xread", b"STREAMS", b"b", b"0-0"]).await,
+    );
+}
+
+#[tokio::test]
+async fn blocking_read_wakes_every_reader() {
+    let addr = start_server().await;
+    let mut first = connect(addr).await;
+    let mut second = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    request(&mut writer, &[b"xadd", b"s", b"1", b"old", b"entry"]).await;
+
+    let xread = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"xread")),
+        Frame::Bulk(Bytes::from_static(b"BLOCK")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+        Frame::Bulk(Bytes::from_static(b"STREAMS")),
+        Frame::Bulk(Bytes::from_static(b"other")),
+        Frame::Bulk(Bytes::from_static(b"s")),
+        Frame::Bulk(Bytes::from_static(b"$")),
+        Frame::Bulk(Bytes::from_static(b"$")),
+    ]);
+    first.write_frame(&xread).await.unwrap();
+    second.write_frame(&xread).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 和BLPOP不一样，同一条消息所有在等的客户端都能读到
+    request(&mut writer, &[b"xadd", b"s", b"2", b"new", b"entry"]).await;
+    let expected = Frame::Array(vec![Frame::Array(vec![bulk("s"), entries(vec![entry("2-0", &["new", "entry"])])])]);
+    assert_eq!(expected, first.read_frame().await.unwrap().unwrap());
+    assert_eq!(expected, second.read_frame().await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn blocking_read_timeout() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    let xread = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"xread")),
+        Frame::Bulk(Bytes::from_static(b"BLOCK")),
+        Frame::Bulk(Bytes::from_static(b"100")),
+        Frame::Bulk(Bytes::from_static(b"STREAMS")),
+        Frame::Bulk(Bytes::from_static(b"s")),
+        Frame::Bulk(Bytes::from_static(b"$")),
+    ]);
+    reader.write_frame(&xread).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // 别的流上的消息不会唤醒它
+    request(&mut writer, &[b"xadd", b"other", b"*", b"f", b"v"]).await;
+    assert_eq!(Frame::Null, reader.read_frame().await.unwrap().unwrap());
+
+    // 超时之后加的消息`$`已经读不到了
+    request(&mut writer, &[b"xadd", b"s", b"*", b"f", b"v"]).await;
+    assert_eq!(Frame::Null, request(&mut reader, &[b"xread", b"BLOCK", b"10", b"STREAMS", b"s", b"$"]).await);
+}
+
+#[tokio::test]
+async fn blocking_read_huge_timeout() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    // 超过tokio定时器上限的超时时间也要能正常阻塞，不能把连接弄崩了
+    let xread = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"xread")),
+        Frame::Bulk(Bytes::from_static(b"BLOCK")),
+        Frame::Bulk(Bytes::from_static(b"99999999999")),
+        Frame::Bulk(Bytes::from_static(b"STREAMS")),
+        Frame::Bulk(Bytes::from_static(b"s")),
+        Frame::Bulk(Bytes::from_static(b"$")),
+    ]);
+    reader.write_frame(&xread).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut writer, &[b"xadd", b"s", b"1", b"f", b"v"]).await;
+    let expected = Frame::Array(vec![Frame::Array(vec![bulk("s"), entries(vec![entry("1-0", &["f", "v"])])])]);
+    assert_eq!(expected, reader.read_frame().await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn shutdown_releases_blocked_readers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(async move { server::run(listener, rx).await });
+
+    let mut connection = connect(addr).await;
+    let xread = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"xread")),
+        Frame::Bulk(Bytes::from_static(b"BLOCK")),
+        Frame::Bulk(Bytes::from_static(b"0")),
+        Frame::Bulk(Bytes::from_static(b"STREAMS")),
+        Frame::Bulk(Bytes::from_static(b"s")),
+        Frame::Bulk(Bytes::from_static(b"$")),
+    ]);
+    connection.write_frame(&xread).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    tx.send(()).unwrap();
+    assert!(connection.read_frame().await.unwrap().is_none());
+    server.await.unwrap().unwrap();
+}
@@ -0,0 +1,372 @@
+use bytes::Bytes;
+use std::time::Duration;
+use w::connection::Connection;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, ok, error};
+
+fn entry(id: &str, fields: &[&str]) -> Frame {
+    Frame::Array(vec![bulk(id), Frame::Array(fields.iter().map(bulk).collect())])
+}
+
+fn ids(items: &[&str]) -> Frame {
+    Frame::Array(items.iter().map(bulk).collect())
+}
+
+// XREADGROUP只读一个流时的回复
+fn stream(key: &str, items: Vec<Frame>) -> Frame {
+    Frame::Array(vec![Frame::Array(vec![bulk(key), Frame::Array(items)])])
+}
+
+// 建一个有3条消息的流s和消费组g
+async fn setup(connection: &mut Connection) {
+    for id in &[b"1", b"2", b"3"] {
+        request(connection, &[b"xadd", b"s", *id, b"f", *id]).await;
+    }
+    assert_eq!(ok(), request(connection, &[b"xgroup", b"create", b"s", b"g", b"0"]).await);
+}
+
+#[tokio::test]
+async fn group_management() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(
+        error("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically."),
+        request(&mut connection, &[b"xgroup", b"create", b"s", b"g", b"$"]).await,
+    );
+    assert_eq!(ok(), request(&mut connection, &[b"xgroup", b"create", b"s", b"g", b"$", b"MKSTREAM"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xlen", b"s"]).await);
+    assert_eq!(
+        error("BUSYGROUP Consumer Group name already exists"),
+        request(&mut connection, &[b"xgroup", b"create", b"s", b"g", b"0"]).await,
+    );
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xgroup", b"createconsumer", b"s", b"g", b"alice"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xgroup", b"createconsumer", b"s", b"g", b"alice"]).await);
+    assert_eq!(
+        error("NOGROUP No such consumer group 'missing' for key name 's'"),
+        request(&mut connection, &[b"xgroup", b"createconsumer", b"s", b"missing", b"alice"]).await,
+    );
+
+    // 组是从`$`建的，之前的消息读不到
+    request(&mut connection, &[b"xadd", b"s", b"1", b"f", b"1"]).await;
+    assert_eq!(ok(), request(&mut connection, &[b"xgroup", b"setid", b"s", b"g", b"$"]).await);
+    assert_eq!(
+        Frame::Null,
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b">"]).await,
+    );
+    assert_eq!(ok(), request(&mut connection, &[b"xgroup", b"setid", b"s", b"g", b"0"]).await);
+    assert_eq!(
+        stream("s", vec![entry("1-0", &["f", "1"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b">"]).await,
+    );
+
+    // 删掉消费者的时候回复它还没确认的条数
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xgroup", b"delconsumer", b"s", b"g", b"alice"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"xgroup", b"destroy", b"s", b"g"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xgroup", b"destroy", b"s", b"g"]).await);
+    assert_eq!(
+        error("ERR unknown subcommand 'foo'. Try XGROUP HELP."),
+        request(&mut connection, &[b"xgroup", b"foo"]).await,
+    );
+}
+
+#[tokio::test]
+async fn read_and_ack() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    setup(&mut connection).await;
+
+    // 新消息在消费者之间分摊，同一条不会发给两个消费者
+    assert_eq!(
+        stream("s", vec![entry("1-0", &["f", "1"]), entry("2-0", &["f", "2"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"COUNT", b"2", b"STREAMS", b"s", b">"]).await,
+    );
+    assert_eq!(
+        stream("s", vec![entry("3-0", &["f", "3"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b">"]).await,
+    );
+    assert_eq!(
+        Frame::Null,
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b">"]).await,
+    );
+
+    // 带ID读的是自己的PEL
+    assert_eq!(
+        stream("s", vec![entry("2-0", &["f", "2"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b"1"]).await,
+    );
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"xack", b"s", b"g", b"1", b"3", b"9"]).await);
+    assert_eq!(
+        stream("s", vec![entry("2-0", &["f", "2"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b"0"]).await,
+    );
+    assert_eq!(
+        stream("s", vec![]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b"0"]).await,
+    );
+
+    // 被删掉的消息还在PEL里，内容是Null
+    request(&mut connection, &[b"xtrim", b"s", b"MAXLEN", b"0"]).await;
+    assert_eq!(
+        stream("s", vec![Frame::Array(vec![bulk("2-0"), Frame::Null])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b"0"]).await,
+    );
+
+    // NOACK读到的消息不进PEL
+    request(&mut connection, &[b"xadd", b"s", b"4", b"f", b"4"]).await;
+    assert_eq!(
+        stream("s", vec![entry("4-0", &["f", "4"])]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"NOACK", b"STREAMS", b"s", b">"]).await,
+    );
+    assert_eq!(
+        stream("s", vec![]),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b"0"]).await,
+    );
+
+    assert_eq!(
+        error("NOGROUP No such key 'other' or consumer group 'g' in XREADGROUP with GROUP option"),
+        request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b"other", b">", b">"]).await,
+    );
+    assert_eq!(
+        error("ERR Missing GROUP option for XREADGROUP"),
+        request(&mut connection, &[b"xreadgroup", b"STREAMS", b"s", b">"]).await,
+    );
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"xack", b"other", b"g", b"1"]).await);
+}
+
+#[tokio::test]
+async fn pending() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    setup(&mut connection).await;
+
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(0), Frame::Null, Frame::Null, Frame::Null]),
+        request(&mut connection, &[b"xpending", b"s", b"g"]).await,
+    );
+
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"COUNT", b"2", b"STREAMS", b"s", b">"]).await;
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"bob", b"STREAMS", b"s", b">"]).await;
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b"0"]).await;
+
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Integer(3),
+            bulk("1-0"),
+            bulk("3-0"),
+            Frame::Array(vec![
+                Frame::Array(vec![bulk("alice"), bulk("2")]),
+                Frame::Array(vec![bulk("bob"), bulk("1")]),
+            ]),
+        ]),
+        request(&mut connection, &[b"xpending", b"s", b"g"]).await,
+    );
+
+    // 读过两次历史的消息投递次数是2
+    let detail = match request(&mut connection, &[b"xpending", b"s", b"g", b"-", b"+", b"10", b"alice"]).await {
+        Frame::Array(detail) => detail,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert_eq!(2, detail.len());
+    for (item, id) in detail.into_iter().zip(&["1-0", "2-0"]) {
+        match item {
+            Frame::Array(fields) => {
+                assert_eq!(bulk(id), fields[0]);
+                assert_eq!(bulk("alice"), fields[1]);
+                assert_eq!(Frame::Integer(2), fields[3]);
+            }
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut connection, &[b"xpending", b"s", b"g", b"IDLE", b"60000", b"-", b"+", b"10"]).await,
+    );
+    assert_eq!(
+        error("NOGROUP No such key 's' or consumer group 'missing'"),
+        request(&mut connection, &[b"xpending", b"s", b"missing"]).await,
+    );
+}
+
+#[tokio::test]
+async fn claim() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    setup(&mut connection).await;
+
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b">"]).await;
+
+    // 还没空闲够
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"60000", b"1"]).await,
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        Frame::Array(vec![entry("1-0", &["f", "1"])]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"20", b"1"]).await,
+    );
+    // 认领之后空闲时间重新算
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"carol", b"20", b"1"]).await,
+    );
+    assert_eq!(
+        ids(&["2-0"]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"20", b"2", b"JUSTID", b"RETRYCOUNT", b"5"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Integer(3),
+            bulk("1-0"),
+            bulk("3-0"),
+            Frame::Array(vec![
+                Frame::Array(vec![bulk("alice"), bulk("1")]),
+                Frame::Array(vec![bulk("bob"), bulk("2")]),
+            ]),
+        ]),
+        request(&mut connection, &[b"xpending", b"s", b"g"]).await,
+    );
+
+    // 不在PEL里的消息要FORCE才能认领
+    request(&mut connection, &[b"xack", b"s", b"g", b"3"]).await;
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"0", b"3"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![entry("3-0", &["f", "3"])]),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"0", b"3", b"FORCE"]).await,
+    );
+    assert_eq!(
+        error("ERR Unrecognized XCLAIM option 'BOGUS'"),
+        request(&mut connection, &[b"xclaim", b"s", b"g", b"bob", b"0", b"3", b"BOGUS"]).await,
+    );
+}
+
+#[tokio::test]
+async fn autoclaim() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    setup(&mut connection).await;
+
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"STREAMS", b"s", b">"]).await;
+    // 删掉第一条，认领的时候报告出来并从PEL里清掉
+    request(&mut connection, &[b"xtrim", b"s", b"MINID", b"2"]).await;
+
+    assert_eq!(
+        Frame::Array(vec![bulk("3-0"), Frame::Array(vec![entry("2-0", &["f", "2"])]), ids(&["1-0"])]),
+        request(&mut connection, &[b"xautoclaim", b"s", b"g", b"bob", b"0", b"-", b"COUNT", b"1"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![bulk("0-0"), Frame::Array(vec![entry("3-0", &["f", "3"])]), ids(&[])]),
+        request(&mut connection, &[b"xautoclaim", b"s", b"g", b"bob", b"0", b"3-0"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![bulk("0-0"), ids(&["2-0", "3-0"]), ids(&[])]),
+        request(&mut connection, &[b"xautoclaim", b"s", b"g", b"carol", b"0", b"0", b"JUSTID"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(2), bulk("2-0"), bulk("3-0"), Frame::Array(vec![Frame::Array(vec![bulk("carol"), bulk("2")])])]),
+        request(&mut connection, &[b"xpending", b"s", b"g"]).await,
+    );
+    assert_eq!(
+        error("ERR COUNT must be > 0"),
+        request(&mut connection, &[b"xautoclaim", b"s", b"g", b"carol", b"0", b"0", b"COUNT", b"0"]).await,
+    );
+}
+
+#[tokio::test]
+async fn info() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+    setup(&mut connection).await;
+    request(&mut connection, &[b"xreadgroup", b"GROUP", b"g", b"alice", b"COUNT", b"2", b"STREAMS", b"s", b">"]).await;
+    request(&mut connection, &[b"xgroup", b"createconsumer", b"s", b"g", b"bob"]).await;
+
+    // RESP2下Map是平铺的数组
+    assert_eq!(
+        Frame::Array(vec![
+            bulk("length"), Frame::Integer(3),
+            bulk("last-generated-id"), bulk("3-0"),
+            bulk("entries-added"), Frame::Integer(3),
+            bulk("groups"), Frame::Integer(1),
+            bulk("first-entry"), entry("1-0", &["f", "1"]),
+            bulk("last-entry"), entry("3-0", &["f", "3"]),
+        ]),
+        request(&mut connection, &[b"xinfo", b"stream", b"s"]).await,
+    );
+    assert_eq!(
+        Frame::Array(vec![Frame::Array(vec![
+            bulk("name"), bulk("g"),
+            bulk("consumers"), Frame::Integer(2),
+            bulk("pending"), Frame::Integer(2),
+            bulk("last-delivered-id"), bulk("2-0"),
+        ])]),
+        request(&mut connection, &[b"xinfo", b"groups", b"s"]).await,
+    );
+
+    let consumers = match request(&mut connection, &[b"xinfo", b"consumers", b"s", b"g"]).await {
+        Frame::Array(consumers) => consumers,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert_eq!(2, consumers.len());
+    match &consumers[0] {
+        Frame::Array(fields) => {
+            assert_eq!(&[bulk("name"), bulk("alice"), bulk("pending"), Frame::Integer(2)], &fields[..4]);
+            assert_eq!(bulk("inactive"), fields[6]);
+            assert!(matches!(fields[7], Frame::Integer(_)));
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+    // 从来没读到过消息的消费者inactive是-1
+    match &consumers[1] {
+        Frame::Array(fields) => {
+            assert_eq!(&[bulk("name"), bulk("bob"), bulk("pending"), Frame::Integer(0)], &fields[..4]);
+            assert_eq!(Frame::Integer(-1), fields[7]);
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+
+    assert_eq!(error("ERR no such key"), request(&mut connection, &[b"xinfo", b"stream", b"missing"]).await);
+    assert_eq!(
+        error("NOGROUP No such consumer group 'missing' for key name 's'"),
+        request(&mut connection, &[b"xinfo", b"consumers", b"s", b"missing"]).await,
+    );
+}
+
+#[tokio::test]
+async fn blocking_read_group() {
+    let addr = start_server().await;
+    let mut first = connect(addr).await;
+    let mut second = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    request(&mut writer, &[b"xgroup", b"create", b"s", b"g", b"$", b"MKSTREAM"]).await;
+
+    let xreadgroup = |consumer: &'static [u8]| {
+        Frame::Array(
+            [&b"xreadgroup"[..], b"GROUP", b"g", consumer, b"BLOCK", b"0", b"STREAMS", b"s", b">"]
+                .iter()
+                .map(|arg| Frame::Bulk(Bytes::from_static(arg)))
+                .collect(),
+        )
+    };
+    first.write_frame(&xreadgroup(b"alice")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    second.write_frame(&xreadgroup(b"bob")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 组里的一条消息只会发给一个等着的消费者，另一个继续等下一条
+    request(&mut writer, &[b"xadd", b"s", b"1", b"f", b"1"]).await;
+    let received = first.read_frame().await.unwrap().unwrap();
+    assert_eq!(stream("s", vec![entry("1-0", &["f", "1"])]), received);
+
+    request(&mut writer, &[b"xadd", b"s", b"2", b"f", b"2"]).await;
+    assert_eq!(stream("s", vec![entry("2-0", &["f", "2"])]), second.read_frame().await.unwrap().unwrap());
+}
@@ -0,0 +1,311 @@
+use bytes::Bytes;
+use std::time::Duration;
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, error};
+
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+#[tokio::test]
+async fn append_and_ranges() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(5), request(&mut connection, &[b"append", b"s", b"Hello"]).await);
+    assert_eq!(Frame::Integer(11), request(&mut connection, &[b"append", b"s", b" World"]).await);
+    assert_eq!(Frame::Integer(11), request(&mut connection, &[b"strlen", b"s"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"strlen", b"missing"]).await);
+
+    assert_eq!(bulk("Hello"), request(&mut connection, &[b"getrange", b"s", b"0", b"4"]).await);
+    assert_eq!(bulk("World"), request(&mut connection, &[b"getrange", b"s", b"-5", b"-1"]).await);
+    assert_eq!(bulk("Hello World"), request(&mut connection, &[b"getrange", b"s", b"-100", b"100"]).await);
+    assert_eq!(bulk(""), request(&mut connection, &[b"getrange", b"s", b"5", b"3"]).await);
+    assert_eq!(bulk(""), request(&mut connection, &[b"getrange", b"s", b"-1", b"-5"]).await);
+    assert_eq!(bulk(""), request(&mut connection, &[b"getrange", b"missing", b"0", b"-1"]).await);
+
+    assert_eq!(Frame::Integer(11), request(&mut connection, &[b"setrange", b"s", b"6", b"Redis"]).await);
+    assert_eq!(bulk("Hello Redis"), request(&mut connection, &[b"get", b"s"]).await);
+    // 超出长度的部分中间补0
+    assert_eq!(Frame::Integer(8), request(&mut connection, &[b"setrange", b"padded", b"5", b"abc"]).await);
+    assert_eq!(
+        Frame::Bulk(Bytes::from_static(b"\0\0\0\0\0abc")),
+        request(&mut connection, &[b"get", b"padded"]).await,
+    );
+    // 空串不会新建key
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"setrange", b"empty", b"10", b""]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"empty"]).await);
+    assert_eq!(
+        error("ERR string exceeds maximum allowed size (proto-max-bulk-len)"),
+        request(&mut connection, &[b"setrange", b"s", b"536870911", b"ab"]).await,
+    );
+
+    request(&mut connection, &[b"lpush", b"list", b"a"]).await;
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"append", b"list", b"a"]).await);
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"strlen", b"list"]).await);
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"setrange", b"list", b"0", b"a"]).await);
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"getrange", b"list", b"0", b"1"]).await);
+}
+
+#[tokio::test]
+async fn getdel_and_getex() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"a", b"1"]).await;
+    assert_eq!(bulk("1"), request(&mut connection, &[b"getdel", b"a"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"getdel", b"a"]).await);
+    request(&mut connection, &[b"lpush", b"list", b"a"]).await;
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"getdel", b"list"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"llen", b"list"]).await);
+
+    request(&mut connection, &[b"set", b"a", b"1"]).await;
+    request(&mut connection, &[b"set", b"b", b"2", b"PX", b"100"]).await;
+    assert_eq!(bulk("1"), request(&mut connection, &[b"getex", b"a", b"px", b"100"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"getex", b"b", b"PERSIST"]).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"a"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"get", b"b"]).await);
+
+    // 过去的时间戳直接删掉
+    assert_eq!(bulk("2"), request(&mut connection, &[b"getex", b"b", b"EXAT", b"1"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"b"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"getex", b"missing", b"EX", b"10"]).await);
+
+    assert_eq!(
+        error("ERR invalid expire time in 'getex' command"),
+        request(&mut connection, &[b"getex", b"a", b"EX", b"0"]).await,
+    );
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"getex", b"a", b"EX", b"10", b"PERSIST"]).await);
+    assert_eq!(error("ERR syntax error"), request(&mut connection, &[b"getex", b"a", b"KEEPTTL"]).await);
+}
+
+#[tokio::test]
+async fn append_keeps_expiration() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"s", b"a", b"PX", b"100"]).await;
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"append", b"s", b"b"]).await);
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"setrange", b"s", b"2", b"c"]).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"s"]).await);
+}
+
+#[tokio::test]
+async fn set_conditions() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    // 分布式锁的用法，第二次抢锁失败回复nil
+    let ok = Frame::Simple("OK".to_string());
+    assert_eq!(ok, request(&mut connection, &[b"set", b"lock", b"a", b"NX", b"PX", b"30000"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"set", b"lock", b"b", b"px", b"30000", b"nx"]).await);
+    assert_eq!(bulk("a"), request(&mut connection, &[b"get", b"lock"]).await);
+
+    assert_eq!(Frame::Null, request(&mut connection, &[b"set", b"missing", b"v", b"XX"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"missing"]).await);
+    assert_eq!(ok, request(&mut connection, &[b"set", b"lock", b"c", b"Xx"]).await);
+    assert_eq!(bulk("c"), request(&mut connection, &[b"get", b"lock"]).await);
+
+    // GET回复旧值，NX没有写入的时候也是
+    assert_eq!(Frame::Null, request(&mut connection, &[b"set", b"g", b"1", b"GET"]).await);
+    assert_eq!(bulk("1"), request(&mut connection, &[b"set", b"g", b"2", b"get"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"set", b"g", b"3", b"NX", b"GET"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"get", b"g"]).await);
+
+    // 旧值不是字符串的时候GET报错，也不会覆盖
+    request(&mut connection, &[b"lpush", b"list", b"x"]).await;
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"set", b"list", b"v", b"GET"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"llen", b"list"]).await);
+    assert_eq!(ok, request(&mut connection, &[b"set", b"list", b"v"]).await);
+    assert_eq!(bulk("v"), request(&mut connection, &[b"get", b"list"]).await);
+
+    let syntax = error("ERR syntax error");
+    assert_eq!(syntax, request(&mut connection, &[b"set", b"k", b"v", b"NX", b"XX"]).await);
+    assert_eq!(syntax, request(&mut connection, &[b"set", b"k", b"v", b"EX", b"10", b"PX", b"100"]).await);
+    assert_eq!(syntax, request(&mut connection, &[b"set", b"k", b"v", b"KEEPTTL", b"EX", b"10"]).await);
+    assert_eq!(syntax, request(&mut connection, &[b"set", b"k", b"v", b"PXAT", b"10", b"KEEPTTL"]).await);
+    assert_eq!(
+        error("ERR wrong number of arguments for 'set' command"),
+        request(&mut connection, &[b"set", b"k", b"v", b"EX"]).await
+    );
+    assert_eq!(
+        error("ERR invalid expire time in 'set' command"),
+        request(&mut connection, &[b"set", b"k", b"v", b"EX", b"0"]).await
+    );
+    assert_eq!(
+        error("ERR invalid expire time in 'set' command"),
+        request(&mut connection, &[b"set", b"k", b"v", b"PX", b"-5"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"k"]).await);
+}
+
+#[tokio::test]
+async fn set_expirations() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    // KEEPTTL保留原来的过期时间，不带选项的SET会把它去掉
+    request(&mut connection, &[b"set", b"keep", b"a", b"PX", b"100"]).await;
+    request(&mut connection, &[b"set", b"keep", b"b", b"KEEPTTL"]).await;
+    request(&mut connection, &[b"set", b"plain", b"a", b"PX", b"100"]).await;
+    request(&mut connection, &[b"set", b"plain", b"b"]).await;
+
+    // 已经过去的时间戳写进去之后马上就过期了
+    let past = b"1000".as_ref();
+    let ok = Frame::Simple("OK".to_string());
+    assert_eq!(ok, request(&mut connection, &[b"set", b"past", b"a", b"PXAT", past]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"past"]).await);
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    let at = (now.as_millis() + 100).to_string();
+    request(&mut connection, &[b"set", b"at", b"a", b"pxat", at.as_bytes()]).await;
+    let at = (now.as_secs() + 100).to_string();
+    request(&mut connection, &[b"set", b"later", b"a", b"EXAT", at.as_bytes()]).await;
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"keep"]).await);
+    assert_eq!(bulk("b"), request(&mut connection, &[b"get", b"plain"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"at"]).await);
+    assert_eq!(bulk("a"), request(&mut connection, &[b"get", b"later"]).await);
+}
+
+#[tokio::test]
+async fn mset_and_mget() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"lpush", b"list", b"a"]).await;
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut connection, &[b"mset", b"a", b"1", b"b", b"2", b"a", b"3"]).await,
+    );
+    // 不是字符串的key也回复Null
+    assert_eq!(
+        Frame::Array(vec![bulk("3"), bulk("2"), Frame::Null, Frame::Null]),
+        request(&mut connection, &[b"mget", b"a", b"b", b"missing", b"list"]).await,
+    );
+
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"msetnx", b"c", b"1", b"list", b"2"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"c"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"msetnx", b"c", b"1", b"d", b"2"]).await);
+    assert_eq!(
+        Frame::Array(vec![bulk("1"), bulk("2")]),
+        request(&mut connection, &[b"mget", b"c", b"d"]).await,
+    );
+
+    // MSET会覆盖其他类型的值
+    request(&mut connection, &[b"mset", b"list", b"v"]).await;
+    assert_eq!(bulk("v"), request(&mut connection, &[b"get", b"list"]).await);
+    assert_eq!(
+        error("ERR wrong number of arguments for 'mset' command"),
+        request(&mut connection, &[b"mset", b"a", b"1", b"b"]).await,
+    );
+}
+
+#[tokio::test]
+async fn mset_is_atomic() {
+    let addr = start_server().await;
+    let mut writer = connect(addr).await;
+    let mut reader = connect(addr).await;
+
+    request(&mut writer, &[b"mset", b"a", b"0", b"b", b"0"]).await;
+    let writes = tokio::spawn(async move {
+        for i in 1..200 {
+            let value = i.to_string();
+            request(&mut writer, &[b"mset", b"a", value.as_bytes(), b"b", value.as_bytes()]).await;
+        }
+    });
+
+    // 读的时候两个key的值总是一样的，看不到只写了一半的状态
+    for _ in 0..200 {
+        match request(&mut reader, &[b"mget", b"a", b"b"]).await {
+            Frame::Array(values) => assert_eq!(values[0], values[1]),
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+    writes.await.unwrap();
+}
+
+#[tokio::test]
+async fn counters() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"incr", b"n"]).await);
+    assert_eq!(Frame::Integer(11), request(&mut connection, &[b"incrby", b"n", b"10"]).await);
+    assert_eq!(Frame::Integer(10), request(&mut connection, &[b"decr", b"n"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"decrby", b"n", b"10"]).await);
+    assert_eq!(Frame::Integer(-5), request(&mut connection, &[b"incrby", b"n", b"-5"]).await);
+    assert_eq!(bulk("-5"), request(&mut connection, &[b"get", b"n"]).await);
+
+    let not_integer = error("ERR value is not an integer or out of range");
+    request(&mut connection, &[b"set", b"s", b"abc"]).await;
+    assert_eq!(not_integer, request(&mut connection, &[b"incr", b"s"]).await);
+    request(&mut connection, &[b"set", b"s", b" 1"]).await;
+    assert_eq!(not_integer, request(&mut connection, &[b"incr", b"s"]).await);
+    request(&mut connection, &[b"set", b"s", b"9223372036854775808"]).await;
+    assert_eq!(not_integer, request(&mut connection, &[b"incr", b"s"]).await);
+    assert_eq!(not_integer, request(&mut connection, &[b"incrby", b"n", b"1.5"]).await);
+
+    request(&mut connection, &[b"set", b"max", b"9223372036854775807"]).await;
+    assert_eq!(
+        error("ERR increment or decrement would overflow"),
+        request(&mut connection, &[b"incr", b"max"]).await,
+    );
+    assert_eq!(
+        error("ERR decrement would overflow"),
+        request(&mut connection, &[b"decrby", b"n", b"-9223372036854775808"]).await,
+    );
+    request(&mut connection, &[b"lpush", b"list", b"a"]).await;
+    assert_eq!(error(WRONGTYPE), request(&mut connection, &[b"incr", b"list"]).await);
+
+    assert_eq!(bulk("10.5"), request(&mut connection, &[b"incrbyfloat", b"f", b"10.5"]).await);
+    assert_eq!(bulk("10.6"), request(&mut connection, &[b"incrbyfloat", b"f", b"0.1"]).await);
+    assert_eq!(bulk("5"), request(&mut connection, &[b"incrbyfloat", b"f", b"-5.6"]).await);
+    assert_eq!(bulk("5"), request(&mut connection, &[b"get", b"f"]).await);
+    // 整数也能按浮点数加
+    assert_eq!(bulk("-4.5"), request(&mut connection, &[b"incrbyfloat", b"n", b"0.5"]).await);
+    request(&mut connection, &[b"set", b"s", b"abc"]).await;
+    assert_eq!(error("ERR value is not a valid float"), request(&mut connection, &[b"incrbyfloat", b"s", b"1"]).await);
+    assert_eq!(error("ERR value is not a valid float"), request(&mut connection, &[b"incrbyfloat", b"f", b"abc"]).await);
+    assert_eq!(
+        error("ERR increment would produce NaN or Infinity"),
+        request(&mut connection, &[b"incrbyfloat", b"f", b"inf"]).await,
+    );
+}
+
+#[tokio::test]
+async fn counters_keep_expiration() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"set", b"n", b"1", b"PX", b"100"]).await;
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"incr", b"n"]).await);
+    assert_eq!(bulk("2.5"), request(&mut connection, &[b"incrbyfloat", b"n", b"0.5"]).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"n"]).await);
+}
+
+#[tokio::test]
+async fn concurrent_increments() {
+    let addr = start_server().await;
+
+    let mut tasks = vec![];
+    for _ in 0..4 {
+        tasks.push(tokio::spawn(async move {
+            let mut connection = connect(addr).await;
+            for _ in 0..100 {
+                request(&mut connection, &[b"incr", b"n"]).await;
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut connection = connect(addr).await;
+    assert_eq!(bulk("400"), request(&mut connection, &[b"get", b"n"]).await);
+}
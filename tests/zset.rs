@@ -0,0 +1,202 @@
+use w::frame::Frame;
+
+mod common;
+
+use common::{start_server, connect, request, bulk, error};
+
+fn bulks(items: &[&str]) -> Frame {
+    Frame::Array(items.iter().map(bulk).collect())
+}
+
+#[tokio::test]
+async fn zadd_options() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"zadd", b"z", b"1", b"a", b"2", b"b", b"3", b"c"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"zadd", b"z", b"10", b"a", b"4", b"d"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"zadd", b"z", b"ch", b"1", b"a", b"5", b"e"]).await);
+
+    // NX只加新的，XX只改旧的
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zadd", b"z", b"NX", b"100", b"a"]).await);
+    assert_eq!(bulk("1"), request(&mut connection, &[b"zscore", b"z", b"a"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zadd", b"z", b"XX", b"1", b"new"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"zscore", b"z", b"new"]).await);
+
+    // GT/LT只在分数变大/变小的时候更新
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zadd", b"z", b"GT", b"CH", b"0", b"b"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"zadd", b"z", b"GT", b"CH", b"20", b"b"]).await);
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"zadd", b"z", b"LT", b"CH", b"0.5", b"c"]).await);
+
+    assert_eq!(bulk("3.5"), request(&mut connection, &[b"zadd", b"z", b"INCR", b"3", b"c"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"zadd", b"z", b"NX", b"INCR", b"3", b"c"]).await);
+    assert_eq!(bulk("-1.5"), request(&mut connection, &[b"zincrby", b"z", b"-5", b"c"]).await);
+    assert_eq!(bulk("2"), request(&mut connection, &[b"zincrby", b"z", b"2", b"fresh"]).await);
+
+    assert_eq!(
+        error("ERR XX and NX options at the same time are not compatible"),
+        request(&mut connection, &[b"zadd", b"z", b"nx", b"xx", b"1", b"a"]).await,
+    );
+    assert_eq!(
+        error("ERR GT, LT, and/or NX options at the same time are not compatible"),
+        request(&mut connection, &[b"zadd", b"z", b"gt", b"lt", b"1", b"a"]).await,
+    );
+    assert_eq!(
+        error("ERR INCR option supports a single increment-element pair"),
+        request(&mut connection, &[b"zadd", b"z", b"incr", b"1", b"a", b"2", b"b"]).await,
+    );
+    assert_eq!(error("ERR value is not a valid float"), request(&mut connection, &[b"zadd", b"z", b"abc", b"a"]).await);
+    assert_eq!(error("ERR value is not a valid float"), request(&mut connection, &[b"zadd", b"z", b"nan", b"a"]).await);
+    // inf加上-inf的结果是NaN
+    assert_eq!(Frame::Integer(1), request(&mut connection, &[b"zadd", b"z", b"+inf", b"x"]).await);
+    assert_eq!(
+        error("ERR resulting score is not a number (NaN)"),
+        request(&mut connection, &[b"zincrby", b"z", b"-inf", b"x"]).await,
+    );
+    assert_eq!(bulk("inf"), request(&mut connection, &[b"zscore", b"z", b"x"]).await);
+
+    // XX的时候一个都没加进去，不能留下空的key
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zadd", b"empty", b"XX", b"1", b"a"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zcard", b"empty"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"get", b"empty"]).await);
+}
+
+#[tokio::test]
+async fn zrange_variants() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"zadd", b"z", b"1", b"a", b"2", b"b", b"3", b"c", b"4", b"d", b"5", b"e"]).await;
+
+    assert_eq!(bulks(&["a", "b", "c", "d", "e"]), request(&mut connection, &[b"zrange", b"z", b"0", b"-1"]).await);
+    assert_eq!(bulks(&["d", "e"]), request(&mut connection, &[b"zrange", b"z", b"-2", b"10"]).await);
+    assert_eq!(bulks(&["e", "d"]), request(&mut connection, &[b"zrange", b"z", b"0", b"1", b"REV"]).await);
+    assert_eq!(bulks(&[]), request(&mut connection, &[b"zrange", b"z", b"3", b"1"]).await);
+    assert_eq!(
+        bulks(&["a", "1", "b", "2"]),
+        request(&mut connection, &[b"zrange", b"z", b"0", b"1", b"withscores"]).await,
+    );
+
+    assert_eq!(bulks(&["b", "c", "d"]), request(&mut connection, &[b"zrange", b"z", b"2", b"4", b"BYSCORE"]).await);
+    assert_eq!(bulks(&["c", "d"]), request(&mut connection, &[b"zrange", b"z", b"(2", b"4", b"BYSCORE"]).await);
+    assert_eq!(
+        bulks(&["d", "c"]),
+        request(&mut connection, &[b"zrange", b"z", b"+inf", b"-inf", b"BYSCORE", b"REV", b"LIMIT", b"1", b"2"]).await,
+    );
+    assert_eq!(
+        bulks(&["b", "c", "d", "e"]),
+        request(&mut connection, &[b"zrange", b"z", b"-inf", b"+inf", b"BYSCORE", b"LIMIT", b"1", b"-1"]).await,
+    );
+    assert_eq!(
+        bulks(&["c", "3", "d", "4"]),
+        request(&mut connection, &[b"zrangebyscore", b"z", b"(2", b"(5", b"WITHSCORES"]).await,
+    );
+    assert_eq!(
+        bulks(&["d"]),
+        request(&mut connection, &[b"zrangebyscore", b"z", b"-inf", b"inf", b"LIMIT", b"3", b"1"]).await,
+    );
+    // offset再大也只是取不到东西，不能溢出
+    assert_eq!(
+        bulks(&[]),
+        request(&mut connection, &[b"zrange", b"z", b"(1", b"+inf", b"BYSCORE", b"REV", b"LIMIT", b"9223372036854775807", b"1"]).await,
+    );
+    assert_eq!(
+        bulks(&[]),
+        request(&mut connection, &[b"zrangebyscore", b"z", b"(1", b"+inf", b"LIMIT", b"9223372036854775807", b"1"]).await,
+    );
+    assert_eq!(bulks(&["b"]), request(&mut connection, &[b"zrangebyscore", b"z", b"(1", b"2"]).await);
+
+    // 分数都一样的时候按字典序
+    request(&mut connection, &[b"zadd", b"lex", b"0", b"apple", b"0", b"banana", b"0", b"cherry", b"0", b"date"]).await;
+    assert_eq!(
+        bulks(&["banana", "cherry"]),
+        request(&mut connection, &[b"zrange", b"lex", b"[b", b"(d", b"BYLEX"]).await,
+    );
+    assert_eq!(
+        bulks(&["date", "cherry"]),
+        request(&mut connection, &[b"zrange", b"lex", b"+", b"(banana", b"BYLEX", b"REV"]).await,
+    );
+    assert_eq!(
+        bulks(&["apple", "banana"]),
+        request(&mut connection, &[b"zrange", b"lex", b"-", b"+", b"BYLEX", b"LIMIT", b"0", b"2"]).await,
+    );
+
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"zrank", b"z", b"c"]).await);
+    assert_eq!(Frame::Null, request(&mut connection, &[b"zrank", b"z", b"missing"]).await);
+    assert_eq!(Frame::Integer(2), request(&mut connection, &[b"zrem", b"z", b"a", b"c", b"missing"]).await);
+    assert_eq!(Frame::Integer(3), request(&mut connection, &[b"zcard", b"z"]).await);
+    assert_eq!(Frame::Integer(0), request(&mut connection, &[b"zrank", b"z", b"b"]).await);
+
+    assert_eq!(
+        error("ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"),
+        request(&mut connection, &[b"zrange", b"z", b"0", b"1", b"LIMIT", b"0", b"1"]).await,
+    );
+    assert_eq!(
+        error("ERR syntax error, WITHSCORES not supported in combination with BYLEX"),
+        request(&mut connection, &[b"zrange", b"z", b"-", b"+", b"BYLEX", b"WITHSCORES"]).await,
+    );
+    assert_eq!(error("ERR min or max is not a float"), request(&mut connection, &[b"zrangebyscore", b"z", b"x", b"1"]).await);
+    assert_eq!(
+        error("ERR min or max not valid string range item"),
+        request(&mut connection, &[b"zrange", b"lex", b"a", b"+", b"BYLEX"]).await,
+    );
+}
+
+#[tokio::test]
+async fn zrange_withscores_resp3() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    request(&mut connection, &[b"hello", b"3"]).await;
+    request(&mut connection, &[b"zadd", b"z", b"1.5", b"a"]).await;
+    assert_eq!(
+        Frame::Array(vec![Frame::Array(vec![bulk("a"), Frame::Double(1.5)])]),
+        request(&mut connection, &[b"zrange", b"z", b"0", b"-1", b"WITHSCORES"]).await,
+    );
+    assert_eq!(Frame::Double(1.5), request(&mut connection, &[b"zscore", b"z", b"a"]).await);
+}
+
+// 随机增删之后，和一个简单的模型比较排名和范围查询的结果
+#[tokio::test]
+async fn skiplist_matches_model() {
+    let addr = start_server().await;
+    let mut connection = connect(addr).await;
+
+    let mut model: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
+    let mut seed: u64 = 42;
+    let mut random = move |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+
+    for _ in 0..600 {
+        let member = format!("m{}", random(80));
+        if random(4) == 0 {
+            request(&mut connection, &[b"zrem", b"z", member.as_bytes()]).await;
+            model.remove(&member);
+        } else {
+            let score = random(30) as i64 - 10;
+            request(&mut connection, &[b"zadd", b"z", score.to_string().as_bytes(), member.as_bytes()]).await;
+            model.insert(member, score);
+        }
+    }
+
+    let mut sorted: Vec<(i64, String)> = model.iter().map(|(member, score)| (*score, member.clone())).collect();
+    sorted.sort();
+    let members: Vec<&str> = sorted.iter().map(|(_, member)| &member[..]).collect();
+
+    assert_eq!(bulks(&members), request(&mut connection, &[b"zrange", b"z", b"0", b"-1"]).await);
+    for (rank, (_, member)) in sorted.iter().enumerate() {
+        assert_eq!(Frame::Integer(rank as i64), request(&mut connection, &[b"zrank", b"z", member.as_bytes()]).await);
+    }
+
+    let in_range: Vec<&str> = sorted
+        .iter()
+        .filter(|(score, _)| *score > -3 && *score <= 7)
+        .map(|(_, member)| &member[..])
+        .collect();
+    assert_eq!(bulks(&in_range), request(&mut connection, &[b"zrangebyscore", b"z", b"(-3", b"7"]).await);
+
+    let reversed: Vec<&str> = members.iter().rev().skip(5).take(10).copied().collect();
+    assert_eq!(bulks(&reversed), request(&mut connection, &[b"zrange", b"z", b"5", b"14", b"REV"]).await);
+}